@@ -0,0 +1,201 @@
+/// An x86-64 program, ready to be emitted as AT&T assembly
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub top_level: Vec<TopLevel>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TopLevel {
+    Function(Function),
+    StaticVariable(StaticVariable),
+    StaticConstant(StaticConstant),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    /// Bytes reserved below `%rbp` for local variables (always a multiple of 16)
+    pub stack_size: i64,
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StaticVariable {
+    pub name: String,
+    pub init: StaticInit,
+}
+
+/// Data in the read-only section: string literals and floating-point constants
+#[derive(Debug, PartialEq)]
+pub struct StaticConstant {
+    pub name: String,
+    pub alignment: i64,
+    pub init: StaticInit,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StaticInit {
+    Long(i32),
+    Float(f32),
+    Double(f64),
+    /// Null-terminated string
+    String(Vec<u8>),
+}
+
+impl StaticInit {
+    /// Size in bytes
+    pub fn size(&self) -> i64 {
+        match self {
+            StaticInit::Long(_) | StaticInit::Float(_) => 4,
+            StaticInit::Double(_) => 8,
+            StaticInit::String(bytes) => bytes.len() as i64,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            StaticInit::Long(value) => *value == 0,
+            // `-0.0` is not all zero bits
+            StaticInit::Float(value) => value.to_bits() == 0,
+            StaticInit::Double(value) => value.to_bits() == 0,
+            StaticInit::String(_) => false,
+        }
+    }
+}
+
+/// Size and kind of the operands of an instruction
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum AsmType {
+    Longword,
+    Quadword,
+    Float,
+    Double,
+}
+
+impl AsmType {
+    pub fn size(self) -> i64 {
+        match self {
+            AsmType::Longword | AsmType::Float => 4,
+            AsmType::Quadword | AsmType::Double => 8,
+        }
+    }
+
+    /// Floating-point values live in XMM registers and use SSE instructions
+    pub fn is_floating(self) -> bool {
+        matches!(self, AsmType::Float | AsmType::Double)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Instruction {
+    Mov(AsmType, Operand, Operand),
+    /// Loads the address of the (memory) source operand into the destination register
+    Lea(Operand, Operand),
+    /// Converts an `int` to the floating-point type (`cvtsi2sd`, `cvtsi2ss`)
+    Cvtsi2f(AsmType, Operand, Operand),
+    /// Converts from the floating-point type to an `int`, rounding toward zero (`cvttsd2si`,
+    /// `cvttss2si`)
+    Cvttf2si(AsmType, Operand, Operand),
+    /// Converts between `float` and `double`, the type is the one of the destination
+    /// (`cvtss2sd`, `cvtsd2ss`)
+    Cvtf2f(AsmType, Operand, Operand),
+    Unary(UnaryOperator, AsmType, Operand),
+    Binary(BinaryOperator, AsmType, Operand, Operand),
+    Cmp(AsmType, Operand, Operand),
+    Idiv(Operand),
+    Cdq,
+    Jmp(String),
+    JmpCC(CondCode, String),
+    SetCC(CondCode, Operand),
+    Label(String),
+    AllocateStack(i64),
+    DeallocateStack(i64),
+    Push(Operand),
+    Call(String),
+    /// Tears down the stack frame and returns
+    Ret,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOperator {
+    Neg,
+    /// Bitwise complement
+    Not,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOperator {
+    Add,
+    Sub,
+    Mult,
+    /// Floating-point division, integers are divided with `Idiv`
+    Div,
+    And,
+    Or,
+    Xor,
+    /// Shift left, the count is an immediate or `%cl`
+    Sal,
+    /// Arithmetic (sign-preserving) shift right, the count is an immediate or `%cl`
+    Sar,
+}
+
+/// Condition codes. `A`, `AE`, `B` and `BE` are the unsigned comparisons, which are also the
+/// ones set by floating-point comparisons. `P` is set when a floating-point comparison is
+/// unordered (one of the operands is NaN)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CondCode {
+    E,
+    NE,
+    L,
+    LE,
+    G,
+    GE,
+    A,
+    AE,
+    B,
+    BE,
+    P,
+    NP,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operand {
+    Imm(i64),
+    Reg(Reg),
+    /// A TACKY variable that has not been assigned a stack slot yet
+    Pseudo(String),
+    /// Offset from `%rbp`
+    Stack(i64),
+    /// RIP-relative reference to a static variable or constant
+    Data(String),
+}
+
+impl Operand {
+    /// Whether the operand lives in memory (x86 allows at most one memory operand per instruction)
+    pub fn is_memory(&self) -> bool {
+        matches!(self, Operand::Stack(_) | Operand::Data(_))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Reg {
+    AX,
+    CX,
+    DX,
+    DI,
+    SI,
+    R8,
+    R9,
+    R10,
+    R11,
+    XMM0,
+    XMM1,
+    XMM2,
+    XMM3,
+    XMM4,
+    XMM5,
+    XMM6,
+    XMM7,
+    XMM14,
+    XMM15,
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+
+use crate::codegen::asm::{
+    AsmType, BinaryOperator, CondCode, Function, Instruction, Operand, Program, Reg,
+    StaticConstant, StaticInit, StaticVariable, TopLevel, UnaryOperator,
+};
+
+/// Size of a register reference in the emitted assembly
+#[derive(Clone, Copy)]
+enum Width {
+    Byte,
+    Long,
+    Quad,
+}
+
+impl From<AsmType> for Width {
+    fn from(ty: AsmType) -> Self {
+        match ty {
+            // XMM registers have a single name
+            AsmType::Longword | AsmType::Float => Width::Long,
+            AsmType::Quadword | AsmType::Double => Width::Quad,
+        }
+    }
+}
+
+fn reg_name(reg: Reg, width: Width) -> &'static str {
+    let [byte, long, quad] = match reg {
+        Reg::AX => ["%al", "%eax", "%rax"],
+        Reg::CX => ["%cl", "%ecx", "%rcx"],
+        Reg::DX => ["%dl", "%edx", "%rdx"],
+        Reg::DI => ["%dil", "%edi", "%rdi"],
+        Reg::SI => ["%sil", "%esi", "%rsi"],
+        Reg::R8 => ["%r8b", "%r8d", "%r8"],
+        Reg::R9 => ["%r9b", "%r9d", "%r9"],
+        Reg::R10 => ["%r10b", "%r10d", "%r10"],
+        Reg::R11 => ["%r11b", "%r11d", "%r11"],
+        Reg::XMM0 => ["%xmm0"; 3],
+        Reg::XMM1 => ["%xmm1"; 3],
+        Reg::XMM2 => ["%xmm2"; 3],
+        Reg::XMM3 => ["%xmm3"; 3],
+        Reg::XMM4 => ["%xmm4"; 3],
+        Reg::XMM5 => ["%xmm5"; 3],
+        Reg::XMM6 => ["%xmm6"; 3],
+        Reg::XMM7 => ["%xmm7"; 3],
+        Reg::XMM14 => ["%xmm14"; 3],
+        Reg::XMM15 => ["%xmm15"; 3],
+    };
+
+    match width {
+        Width::Byte => byte,
+        Width::Long => long,
+        Width::Quad => quad,
+    }
+}
+
+/// Formats an operand as AT&T syntax
+struct Op<'a>(&'a Operand, Width);
+
+impl fmt::Display for Op<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Operand::Imm(value) => write!(f, "${}", value),
+            Operand::Reg(reg) => write!(f, "{}", reg_name(*reg, self.1)),
+            Operand::Pseudo(name) => unreachable!("pseudo operand '{}' was never replaced", name),
+            Operand::Stack(offset) => write!(f, "{}(%rbp)", offset),
+            Operand::Data(name) => write!(f, "{}(%rip)", name),
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for top_level in &self.top_level {
+            match top_level {
+                TopLevel::Function(function) => write!(f, "{}", function)?,
+                TopLevel::StaticVariable(variable) => write!(f, "{}", variable)?,
+                TopLevel::StaticConstant(constant) => write!(f, "{}", constant)?,
+            }
+        }
+
+        // Mark the stack as non-executable
+        writeln!(f, "\t.section .note.GNU-stack,\"\",@progbits")
+    }
+}
+
+impl fmt::Display for StaticVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\t.globl {}", self.name)?;
+        if self.init.is_zero() {
+            writeln!(f, "\t.bss")?;
+        } else {
+            writeln!(f, "\t.data")?;
+        }
+        writeln!(f, "\t.balign {}", self.init.size())?;
+        writeln!(f, "{}:", self.name)?;
+        if self.init.is_zero() {
+            writeln!(f, "\t.zero {}", self.init.size())
+        } else {
+            writeln!(f, "{}", self.init)
+        }
+    }
+}
+
+impl fmt::Display for StaticConstant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Local to the translation unit, so there is no `.globl`
+        writeln!(f, "\t.section .rodata")?;
+        if self.alignment > 1 {
+            writeln!(f, "\t.balign {}", self.alignment)?;
+        }
+        writeln!(f, "{}:", self.name)?;
+        writeln!(f, "{}", self.init)?;
+        // Pad to the alignment, 16-byte constants are read whole by `xorps`/`xorpd`
+        if self.alignment > self.init.size() {
+            writeln!(f, "\t.zero {}", self.alignment - self.init.size())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StaticInit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaticInit::Long(value) => write!(f, "\t.long {}", value),
+            // Written as bits, the assembler can't be trusted to round decimal values the same
+            StaticInit::Float(value) => write!(f, "\t.long {} # {:?}", value.to_bits(), value),
+            StaticInit::Double(value) => write!(f, "\t.quad {} # {:?}", value.to_bits(), value),
+            StaticInit::String(bytes) => {
+                write!(f, "\t.ascii \"")?;
+                for byte in bytes {
+                    match byte {
+                        b'"' | b'\\' => write!(f, "\\{}", *byte as char)?,
+                        b' '..=b'~' => write!(f, "{}", *byte as char)?,
+                        // The assembler reads at most three octal digits
+                        byte => write!(f, "\\{:03o}", byte)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\t.globl {}", self.name)?;
+        writeln!(f, "\t.text")?;
+        writeln!(f, "{}:", self.name)?;
+        writeln!(f, "\tpushq %rbp")?;
+        writeln!(f, "\tmovq %rsp, %rbp")?;
+        if self.stack_size > 0 {
+            writeln!(f, "\tsubq ${}, %rsp", self.stack_size)?;
+        }
+
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Width::*;
+
+        match self {
+            Instruction::Mov(ty, src, dst) => {
+                let mnemonic = match ty {
+                    AsmType::Longword => "movl",
+                    AsmType::Quadword => "movq",
+                    AsmType::Float => "movss",
+                    AsmType::Double => "movsd",
+                };
+                let width = Width::from(*ty);
+                write!(f, "\t{} {}, {}", mnemonic, Op(src, width), Op(dst, width))
+            }
+            Instruction::Lea(src, dst) => write!(f, "\tleaq {}, {}", Op(src, Quad), Op(dst, Quad)),
+            Instruction::Cvtsi2f(ty, src, dst) => {
+                let mnemonic = if *ty == AsmType::Float {
+                    "cvtsi2ssl"
+                } else {
+                    "cvtsi2sdl"
+                };
+                write!(f, "\t{} {}, {}", mnemonic, Op(src, Long), Op(dst, Long))
+            }
+            Instruction::Cvttf2si(ty, src, dst) => {
+                let mnemonic = if *ty == AsmType::Float {
+                    "cvttss2si"
+                } else {
+                    "cvttsd2si"
+                };
+                write!(f, "\t{} {}, {}", mnemonic, Op(src, Long), Op(dst, Long))
+            }
+            Instruction::Cvtf2f(ty, src, dst) => {
+                let mnemonic = if *ty == AsmType::Float {
+                    "cvtsd2ss"
+                } else {
+                    "cvtss2sd"
+                };
+                write!(f, "\t{} {}, {}", mnemonic, Op(src, Long), Op(dst, Long))
+            }
+            Instruction::Unary(operator, ty, operand) => {
+                let mnemonic = match operator {
+                    UnaryOperator::Neg => "neg",
+                    UnaryOperator::Not => "not",
+                };
+                let width = Width::from(*ty);
+                write!(f, "\t{}{} {}", mnemonic, suffix(*ty), Op(operand, width))
+            }
+            Instruction::Binary(
+                operator @ (BinaryOperator::Sal | BinaryOperator::Sar),
+                ty,
+                src,
+                dst,
+            ) => {
+                let mnemonic = if *operator == BinaryOperator::Sal {
+                    "sal"
+                } else {
+                    "sar"
+                };
+                // The shift count register is always `%cl`
+                let width = Width::from(*ty);
+                write!(
+                    f,
+                    "\t{}{} {}, {}",
+                    mnemonic,
+                    suffix(*ty),
+                    Op(src, Byte),
+                    Op(dst, width)
+                )
+            }
+            Instruction::Binary(operator, ty, src, dst) if ty.is_floating() => {
+                let mnemonic = match operator {
+                    BinaryOperator::Add => "adds",
+                    BinaryOperator::Sub => "subs",
+                    BinaryOperator::Mult => "muls",
+                    BinaryOperator::Div => "divs",
+                    // Packed instructions, the scalar ones only exist for integers
+                    BinaryOperator::Xor => "xorp",
+                    _ => unreachable!("no floating-point {:?}", operator),
+                };
+                write!(
+                    f,
+                    "\t{}{} {}, {}",
+                    mnemonic,
+                    suffix(*ty),
+                    Op(src, Long),
+                    Op(dst, Long)
+                )
+            }
+            Instruction::Binary(operator, ty, src, dst) => {
+                let mnemonic = match operator {
+                    BinaryOperator::Add => "add",
+                    BinaryOperator::Sub => "sub",
+                    BinaryOperator::Mult => "imul",
+                    BinaryOperator::And => "and",
+                    BinaryOperator::Or => "or",
+                    BinaryOperator::Xor => "xor",
+                    BinaryOperator::Div | BinaryOperator::Sal | BinaryOperator::Sar => {
+                        unreachable!()
+                    }
+                };
+                let width = Width::from(*ty);
+                write!(
+                    f,
+                    "\t{}{} {}, {}",
+                    mnemonic,
+                    suffix(*ty),
+                    Op(src, width),
+                    Op(dst, width)
+                )
+            }
+            Instruction::Cmp(ty, left, right) => {
+                let mnemonic = match ty {
+                    AsmType::Float => "ucomiss",
+                    AsmType::Double => "ucomisd",
+                    AsmType::Longword => "cmpl",
+                    AsmType::Quadword => "cmpq",
+                };
+                let width = Width::from(*ty);
+                write!(
+                    f,
+                    "\t{} {}, {}",
+                    mnemonic,
+                    Op(left, width),
+                    Op(right, width)
+                )
+            }
+            Instruction::Idiv(operand) => write!(f, "\tidivl {}", Op(operand, Long)),
+            Instruction::Cdq => write!(f, "\tcdq"),
+            Instruction::Jmp(label) => write!(f, "\tjmp .L{}", label),
+            Instruction::JmpCC(cond, label) => write!(f, "\tj{} .L{}", cond_suffix(*cond), label),
+            Instruction::SetCC(cond, operand) => {
+                write!(f, "\tset{} {}", cond_suffix(*cond), Op(operand, Byte))
+            }
+            Instruction::Label(label) => write!(f, ".L{}:", label),
+            Instruction::AllocateStack(bytes) => write!(f, "\tsubq ${}, %rsp", bytes),
+            Instruction::DeallocateStack(bytes) => write!(f, "\taddq ${}, %rsp", bytes),
+            Instruction::Push(operand) => write!(f, "\tpushq {}", Op(operand, Quad)),
+            Instruction::Call(name) => write!(f, "\tcall {}@PLT", name),
+            Instruction::Ret => write!(f, "\tmovq %rbp, %rsp\n\tpopq %rbp\n\tret"),
+        }
+    }
+}
+
+/// Instruction suffix for the operand type: `l`/`q` for integers, `s`/`d` (single or double
+/// precision) for SSE instructions
+fn suffix(ty: AsmType) -> &'static str {
+    match ty {
+        AsmType::Longword => "l",
+        AsmType::Quadword => "q",
+        AsmType::Float => "s",
+        AsmType::Double => "d",
+    }
+}
+
+fn cond_suffix(cond: CondCode) -> &'static str {
+    match cond {
+        CondCode::E => "e",
+        CondCode::NE => "ne",
+        CondCode::L => "l",
+        CondCode::LE => "le",
+        CondCode::G => "g",
+        CondCode::GE => "ge",
+        CondCode::A => "a",
+        CondCode::AE => "ae",
+        CondCode::B => "b",
+        CondCode::BE => "be",
+        CondCode::P => "p",
+        CondCode::NP => "np",
+    }
+}
@@ -0,0 +1,987 @@
+pub mod asm;
+mod emit;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    codegen::asm::{
+        AsmType, BinaryOperator, CondCode, Function, Instruction, Operand, Program, Reg,
+        StaticConstant, StaticInit, StaticVariable, TopLevel, UnaryOperator,
+    },
+    parser::ast::CType,
+    semantic::{Constant, SymbolKind, SymbolTable},
+    tacky::ir,
+};
+
+/// Public API for lowering a TACKY `Program` to x86-64 assembly. `symbols` gives the types of
+/// all variables, including the temporaries added by TACKY generation
+pub fn generate(program: &ir::Program, symbols: &SymbolTable) -> Program {
+    let strings: HashSet<&str> = program
+        .top_level
+        .iter()
+        .filter_map(|top_level| match top_level {
+            ir::TopLevel::StaticConstant(constant) => Some(constant.name.as_str()),
+            ir::TopLevel::Function(_) | ir::TopLevel::StaticVariable(_) => None,
+        })
+        .collect();
+
+    let mut generator = Generator {
+        symbols,
+        strings,
+        constants: HashMap::new(),
+        static_constants: Vec::new(),
+        counter: 0,
+    };
+
+    let mut top_level: Vec<TopLevel> = program
+        .top_level
+        .iter()
+        .map(|top_level| match top_level {
+            ir::TopLevel::Function(function) => TopLevel::Function(generator.function(function)),
+            ir::TopLevel::StaticVariable(variable) => TopLevel::StaticVariable(StaticVariable {
+                name: variable.name.clone(),
+                init: static_init(variable.init),
+            }),
+            ir::TopLevel::StaticConstant(constant) => TopLevel::StaticConstant(StaticConstant {
+                name: constant.name.clone(),
+                alignment: 1,
+                init: StaticInit::String(constant.init.clone()),
+            }),
+        })
+        .collect();
+    top_level.extend(
+        generator
+            .static_constants
+            .into_iter()
+            .map(TopLevel::StaticConstant),
+    );
+
+    Program { top_level }
+}
+
+/// Registers holding the first integer arguments in the System V calling convention
+const ARG_REGISTERS: [Reg; 6] = [Reg::DI, Reg::SI, Reg::DX, Reg::CX, Reg::R8, Reg::R9];
+
+/// Registers holding the first floating-point arguments in the System V calling convention
+const FLOAT_ARG_REGISTERS: [Reg; 8] = [
+    Reg::XMM0,
+    Reg::XMM1,
+    Reg::XMM2,
+    Reg::XMM3,
+    Reg::XMM4,
+    Reg::XMM5,
+    Reg::XMM6,
+    Reg::XMM7,
+];
+
+fn static_init(constant: Constant) -> StaticInit {
+    match constant {
+        Constant::Int(value) => StaticInit::Long(value as i32),
+        Constant::Float(value) => StaticInit::Float(value),
+        Constant::Double(value) => StaticInit::Double(value),
+    }
+}
+
+fn asm_type(ctype: &CType) -> AsmType {
+    match ctype {
+        // `void` only appears as the type of discarded call results
+        CType::Int | CType::Char | CType::Void => AsmType::Longword,
+        CType::Float => AsmType::Float,
+        CType::Double => AsmType::Double,
+        CType::Pointer(_) => AsmType::Quadword,
+    }
+}
+
+/// Where each argument of a call (or parameter of a function) is passed. The arguments that
+/// don't fit in registers are passed on the stack, in order
+struct ArgumentLocations<'a, T> {
+    registers: Vec<(&'a T, Reg)>,
+    stack: Vec<&'a T>,
+}
+
+/// Assigns arguments to the next free integer or XMM register, depending on their type
+fn classify<T>(args: &[T], asm_type: impl Fn(&T) -> AsmType) -> ArgumentLocations<'_, T> {
+    let mut int_registers = ARG_REGISTERS.iter();
+    let mut float_registers = FLOAT_ARG_REGISTERS.iter();
+    let mut locations = ArgumentLocations {
+        registers: Vec::new(),
+        stack: Vec::new(),
+    };
+
+    for arg in args {
+        let reg = if asm_type(arg).is_floating() {
+            float_registers.next()
+        } else {
+            int_registers.next()
+        };
+        match reg {
+            Some(reg) => locations.registers.push((arg, *reg)),
+            None => locations.stack.push(arg),
+        }
+    }
+
+    locations
+}
+
+struct Generator<'a> {
+    symbols: &'a SymbolTable,
+    /// Names of the string literals, which are passed by address
+    strings: HashSet<&'a str>,
+    /// Names of the floating-point constants already in `static_constants`, by type, bit
+    /// pattern and alignment
+    constants: HashMap<(AsmType, u64, i64), String>,
+    static_constants: Vec<StaticConstant>,
+    /// Counter for unique constant names and labels
+    counter: usize,
+}
+
+impl Generator<'_> {
+    fn function(&mut self, function: &ir::Function) -> Function {
+        let mut instructions = Vec::new();
+
+        // Copy the parameters out of their registers and the caller's stack frame
+        let params = classify(&function.params, |param: &String| self.var_type(param));
+        for (param, reg) in &params.registers {
+            instructions.push(Instruction::Mov(
+                self.var_type(param),
+                Operand::Reg(*reg),
+                Operand::Pseudo(param.to_string()),
+            ));
+        }
+        for (i, param) in params.stack.iter().enumerate() {
+            // Above the saved `%rbp` and the return address
+            instructions.push(Instruction::Mov(
+                self.var_type(param),
+                Operand::Stack(16 + 8 * i as i64),
+                Operand::Pseudo(param.to_string()),
+            ));
+        }
+
+        for instruction in &function.body {
+            self.select_instruction(instruction, &mut instructions);
+        }
+
+        let stack_size = self.replace_pseudos(&mut instructions);
+        let instructions = fix_up_instructions(instructions);
+
+        Function {
+            name: function.name.clone(),
+            stack_size: (stack_size + 15) / 16 * 16,
+            instructions,
+        }
+    }
+
+    /// Instruction selection: translates one TACKY instruction, leaving variables as pseudo
+    /// operands
+    fn select_instruction(&mut self, instruction: &ir::Instruction, out: &mut Vec<Instruction>) {
+        match instruction {
+            ir::Instruction::Return(value) => {
+                let ty = self.value_type(value);
+                let reg = if ty.is_floating() { Reg::XMM0 } else { Reg::AX };
+                out.push(Instruction::Mov(ty, self.operand(value), Operand::Reg(reg)));
+                out.push(Instruction::Ret);
+            }
+            ir::Instruction::Unary {
+                op: ir::UnaryOperator::Not,
+                src,
+                dst,
+            } => {
+                let ty = self.value_type(src);
+                if ty.is_floating() {
+                    self.zero_xmm0(ty, out);
+                    self.float_equality(CondCode::E, ty, src, Operand::Reg(Reg::XMM0), dst, out);
+                } else {
+                    out.push(Instruction::Cmp(ty, Operand::Imm(0), self.operand(src)));
+                    out.push(Instruction::Mov(
+                        AsmType::Longword,
+                        Operand::Imm(0),
+                        self.operand(dst),
+                    ));
+                    out.push(Instruction::SetCC(CondCode::E, self.operand(dst)));
+                }
+            }
+            ir::Instruction::Unary { op, src, dst } => {
+                let ty = self.value_type(src);
+                out.push(Instruction::Mov(ty, self.operand(src), self.operand(dst)));
+                match op {
+                    // Flipping the sign bit also negates zero and NaN correctly
+                    ir::UnaryOperator::Negate if ty.is_floating() => {
+                        let negative_zero = match ty {
+                            AsmType::Float => Constant::Float(-0.0),
+                            _ => Constant::Double(-0.0),
+                        };
+                        // `xorps`/`xorpd` read 16 bytes, which have to be aligned
+                        let mask = self.float_constant(negative_zero, 16);
+                        out.push(Instruction::Binary(
+                            BinaryOperator::Xor,
+                            ty,
+                            mask,
+                            self.operand(dst),
+                        ));
+                    }
+                    ir::UnaryOperator::Negate => out.push(Instruction::Unary(
+                        UnaryOperator::Neg,
+                        ty,
+                        self.operand(dst),
+                    )),
+                    ir::UnaryOperator::Complement => out.push(Instruction::Unary(
+                        UnaryOperator::Not,
+                        ty,
+                        self.operand(dst),
+                    )),
+                    ir::UnaryOperator::Not => unreachable!(),
+                }
+            }
+            ir::Instruction::Binary {
+                op,
+                left,
+                right,
+                dst,
+            } => self.select_binary(*op, left, right, dst, out),
+            ir::Instruction::Copy { src, dst } => {
+                out.push(Instruction::Mov(
+                    self.value_type(src),
+                    self.operand(src),
+                    self.operand(dst),
+                ));
+            }
+            ir::Instruction::Convert { src, dst, to } => {
+                let (from, to) = (self.value_type(src), asm_type(to));
+                let (src, dst) = (self.operand(src), self.operand(dst));
+                out.push(match (from.is_floating(), to.is_floating()) {
+                    (false, true) => Instruction::Cvtsi2f(to, src, dst),
+                    (true, false) => Instruction::Cvttf2si(from, src, dst),
+                    (true, true) if from != to => Instruction::Cvtf2f(to, src, dst),
+                    _ => Instruction::Mov(to, src, dst),
+                });
+            }
+            ir::Instruction::Jump(label) => out.push(Instruction::Jmp(label.clone())),
+            ir::Instruction::JumpIfZero(value, label)
+            | ir::Instruction::JumpIfNotZero(value, label) => {
+                let jump_if_zero = matches!(instruction, ir::Instruction::JumpIfZero(..));
+                let ty = self.value_type(value);
+                if !ty.is_floating() {
+                    let cond = if jump_if_zero {
+                        CondCode::E
+                    } else {
+                        CondCode::NE
+                    };
+                    out.push(Instruction::Cmp(ty, Operand::Imm(0), self.operand(value)));
+                    out.push(Instruction::JmpCC(cond, label.clone()));
+                    return;
+                }
+
+                // NaN compares unordered with zero and counts as not zero
+                self.zero_xmm0(ty, out);
+                out.push(Instruction::Cmp(
+                    ty,
+                    self.operand(value),
+                    Operand::Reg(Reg::XMM0),
+                ));
+                if jump_if_zero {
+                    let unordered = self.make_label("unordered");
+                    out.push(Instruction::JmpCC(CondCode::P, unordered.clone()));
+                    out.push(Instruction::JmpCC(CondCode::E, label.clone()));
+                    out.push(Instruction::Label(unordered));
+                } else {
+                    out.push(Instruction::JmpCC(CondCode::NE, label.clone()));
+                    out.push(Instruction::JmpCC(CondCode::P, label.clone()));
+                }
+            }
+            ir::Instruction::Label(label) => out.push(Instruction::Label(label.clone())),
+            ir::Instruction::FunCall { name, args, dst } => self.select_call(name, args, dst, out),
+        }
+    }
+
+    fn select_binary(
+        &mut self,
+        op: ir::BinaryOperator,
+        left: &ir::Value,
+        right: &ir::Value,
+        dst: &ir::Value,
+        out: &mut Vec<Instruction>,
+    ) {
+        let ty = self.value_type(left);
+        match op {
+            ir::BinaryOperator::Add
+            | ir::BinaryOperator::Subtract
+            | ir::BinaryOperator::Multiply
+            | ir::BinaryOperator::BitwiseAnd
+            | ir::BinaryOperator::BitwiseOr
+            | ir::BinaryOperator::BitwiseXor => {
+                let op = match op {
+                    ir::BinaryOperator::Add => BinaryOperator::Add,
+                    ir::BinaryOperator::Subtract => BinaryOperator::Sub,
+                    ir::BinaryOperator::Multiply => BinaryOperator::Mult,
+                    ir::BinaryOperator::BitwiseAnd => BinaryOperator::And,
+                    ir::BinaryOperator::BitwiseOr => BinaryOperator::Or,
+                    _ => BinaryOperator::Xor,
+                };
+                out.push(Instruction::Mov(ty, self.operand(left), self.operand(dst)));
+                out.push(Instruction::Binary(
+                    op,
+                    ty,
+                    self.operand(right),
+                    self.operand(dst),
+                ));
+            }
+            ir::BinaryOperator::ShiftLeft | ir::BinaryOperator::ShiftRight => {
+                let op = if op == ir::BinaryOperator::ShiftLeft {
+                    BinaryOperator::Sal
+                } else {
+                    BinaryOperator::Sar
+                };
+                // A variable shift count has to be in `%cl`
+                let count = match self.operand(right) {
+                    count @ Operand::Imm(_) => count,
+                    count => {
+                        out.push(Instruction::Mov(
+                            AsmType::Longword,
+                            count,
+                            Operand::Reg(Reg::CX),
+                        ));
+                        Operand::Reg(Reg::CX)
+                    }
+                };
+                out.push(Instruction::Mov(ty, self.operand(left), self.operand(dst)));
+                out.push(Instruction::Binary(op, ty, count, self.operand(dst)));
+            }
+            ir::BinaryOperator::Divide if ty.is_floating() => {
+                out.push(Instruction::Mov(ty, self.operand(left), self.operand(dst)));
+                out.push(Instruction::Binary(
+                    BinaryOperator::Div,
+                    ty,
+                    self.operand(right),
+                    self.operand(dst),
+                ));
+            }
+            ir::BinaryOperator::Divide | ir::BinaryOperator::Remainder => {
+                // `idiv` leaves the quotient in `%eax` and the remainder in `%edx`
+                let result = if op == ir::BinaryOperator::Divide {
+                    Reg::AX
+                } else {
+                    Reg::DX
+                };
+                out.push(Instruction::Mov(
+                    ty,
+                    self.operand(left),
+                    Operand::Reg(Reg::AX),
+                ));
+                out.push(Instruction::Cdq);
+                out.push(Instruction::Idiv(self.operand(right)));
+                out.push(Instruction::Mov(
+                    ty,
+                    Operand::Reg(result),
+                    self.operand(dst),
+                ));
+            }
+            ir::BinaryOperator::Equal | ir::BinaryOperator::NotEqual if ty.is_floating() => {
+                let cond = if op == ir::BinaryOperator::Equal {
+                    CondCode::E
+                } else {
+                    CondCode::NE
+                };
+                let right = self.operand(right);
+                self.float_equality(cond, ty, left, right, dst, out);
+            }
+            ir::BinaryOperator::Equal
+            | ir::BinaryOperator::NotEqual
+            | ir::BinaryOperator::LessThan
+            | ir::BinaryOperator::LessOrEqual
+            | ir::BinaryOperator::GreaterThan
+            | ir::BinaryOperator::GreaterOrEqual => {
+                let (cond, left, right) = if ty.is_floating() {
+                    // Only "above" and "above or equal" are false for unordered operands, so
+                    // `a < b` is computed as `b > a`
+                    match op {
+                        ir::BinaryOperator::LessThan => (CondCode::A, right, left),
+                        ir::BinaryOperator::LessOrEqual => (CondCode::AE, right, left),
+                        ir::BinaryOperator::GreaterThan => (CondCode::A, left, right),
+                        _ => (CondCode::AE, left, right),
+                    }
+                } else {
+                    let cond = match op {
+                        ir::BinaryOperator::Equal => CondCode::E,
+                        ir::BinaryOperator::NotEqual => CondCode::NE,
+                        ir::BinaryOperator::LessThan => CondCode::L,
+                        ir::BinaryOperator::LessOrEqual => CondCode::LE,
+                        ir::BinaryOperator::GreaterThan => CondCode::G,
+                        _ => CondCode::GE,
+                    };
+                    (cond, left, right)
+                };
+                out.push(Instruction::Cmp(
+                    ty,
+                    self.operand(right),
+                    self.operand(left),
+                ));
+                out.push(Instruction::Mov(
+                    AsmType::Longword,
+                    Operand::Imm(0),
+                    self.operand(dst),
+                ));
+                out.push(Instruction::SetCC(cond, self.operand(dst)));
+            }
+        }
+    }
+
+    /// Sets `dst` to whether the floating-point `left` is equal (`E`) or not equal (`NE`) to
+    /// `right`. An unordered comparison sets `ZF` like equal operands do, so the parity flag
+    /// has to be checked as well
+    fn float_equality(
+        &mut self,
+        cond: CondCode,
+        ty: AsmType,
+        left: &ir::Value,
+        right: Operand,
+        dst: &ir::Value,
+        out: &mut Vec<Instruction>,
+    ) {
+        let (parity, combine) = if cond == CondCode::E {
+            (CondCode::NP, BinaryOperator::And)
+        } else {
+            (CondCode::P, BinaryOperator::Or)
+        };
+        let dst = self.operand(dst);
+        let scratch = Operand::Reg(Reg::R11);
+
+        out.push(Instruction::Cmp(ty, right, self.operand(left)));
+        out.push(Instruction::Mov(
+            AsmType::Longword,
+            Operand::Imm(0),
+            dst.clone(),
+        ));
+        out.push(Instruction::Mov(
+            AsmType::Longword,
+            Operand::Imm(0),
+            scratch.clone(),
+        ));
+        out.push(Instruction::SetCC(cond, dst.clone()));
+        out.push(Instruction::SetCC(parity, scratch.clone()));
+        out.push(Instruction::Binary(
+            combine,
+            AsmType::Longword,
+            scratch,
+            dst,
+        ));
+    }
+
+    fn zero_xmm0(&self, ty: AsmType, out: &mut Vec<Instruction>) {
+        out.push(Instruction::Binary(
+            BinaryOperator::Xor,
+            ty,
+            Operand::Reg(Reg::XMM0),
+            Operand::Reg(Reg::XMM0),
+        ));
+    }
+
+    fn select_call(
+        &mut self,
+        name: &str,
+        args: &[ir::Value],
+        dst: &ir::Value,
+        out: &mut Vec<Instruction>,
+    ) {
+        let locations = classify(args, |arg: &ir::Value| self.arg_type(arg));
+
+        // `%rsp` must be 16-byte aligned at the call, each stack argument takes 8 bytes
+        let padding = if locations.stack.len() % 2 == 1 { 8 } else { 0 };
+        if padding != 0 {
+            out.push(Instruction::AllocateStack(padding));
+        }
+
+        for (arg, reg) in &locations.registers {
+            if self.is_string(arg) {
+                out.push(Instruction::Lea(self.operand(arg), Operand::Reg(*reg)));
+            } else {
+                out.push(Instruction::Mov(
+                    self.value_type(arg),
+                    self.operand(arg),
+                    Operand::Reg(*reg),
+                ));
+            }
+        }
+
+        // Pushed in reverse order, so the first stack argument ends up lowest
+        for arg in locations.stack.iter().rev() {
+            if self.is_string(arg) {
+                out.push(Instruction::Lea(self.operand(arg), Operand::Reg(Reg::AX)));
+                out.push(Instruction::Push(Operand::Reg(Reg::AX)));
+                continue;
+            }
+            match (self.value_type(arg), self.operand(arg)) {
+                (_, arg @ (Operand::Imm(_) | Operand::Reg(_))) => out.push(Instruction::Push(arg)),
+                (AsmType::Quadword | AsmType::Double, arg) => out.push(Instruction::Push(arg)),
+                (_, arg) => {
+                    // Pushing a 4-byte variable directly would read past its end
+                    out.push(Instruction::Mov(
+                        AsmType::Longword,
+                        arg,
+                        Operand::Reg(Reg::AX),
+                    ));
+                    out.push(Instruction::Push(Operand::Reg(Reg::AX)));
+                }
+            }
+        }
+
+        // A function declared without a prototype may be variadic, which needs an upper bound
+        // of the number of XMM registers used in `%al`
+        if let Some(SymbolKind::Function { params: None, .. }) =
+            self.symbols.get(name).map(|symbol| &symbol.kind)
+        {
+            let float_registers = locations
+                .registers
+                .iter()
+                .filter(|(_, reg)| FLOAT_ARG_REGISTERS.contains(reg))
+                .count();
+            out.push(Instruction::Mov(
+                AsmType::Longword,
+                Operand::Imm(float_registers as i64),
+                Operand::Reg(Reg::AX),
+            ));
+        }
+
+        out.push(Instruction::Call(name.to_string()));
+
+        let bytes_to_remove = 8 * locations.stack.len() as i64 + padding;
+        if bytes_to_remove != 0 {
+            out.push(Instruction::DeallocateStack(bytes_to_remove));
+        }
+        let ty = self.value_type(dst);
+        let result = if ty.is_floating() { Reg::XMM0 } else { Reg::AX };
+        out.push(Instruction::Mov(
+            ty,
+            Operand::Reg(result),
+            self.operand(dst),
+        ));
+    }
+
+    fn operand(&mut self, value: &ir::Value) -> Operand {
+        match value {
+            ir::Value::Constant(Constant::Int(value)) => Operand::Imm(*value as i32 as i64),
+            // There are no floating-point immediates, constants are loaded from memory
+            ir::Value::Constant(constant) => {
+                let alignment = asm_type(&constant.ctype()).size();
+                self.float_constant(*constant, alignment)
+            }
+            ir::Value::Var(name) if self.strings.contains(name.as_str()) => {
+                Operand::Data(name.clone())
+            }
+            ir::Value::Var(name) => Operand::Pseudo(name.clone()),
+        }
+    }
+
+    /// Read-only static constant holding `constant`, shared by all its uses
+    fn float_constant(&mut self, constant: Constant, alignment: i64) -> Operand {
+        let (ty, bits) = match constant {
+            Constant::Float(value) => (AsmType::Float, value.to_bits() as u64),
+            Constant::Double(value) => (AsmType::Double, value.to_bits()),
+            Constant::Int(_) => unreachable!("integer constants are immediates"),
+        };
+
+        let name = match self.constants.get(&(ty, bits, alignment)) {
+            Some(name) => name.clone(),
+            None => {
+                let name = format!("const.{}", self.counter);
+                self.counter += 1;
+                self.static_constants.push(StaticConstant {
+                    name: name.clone(),
+                    alignment,
+                    init: static_init(constant),
+                });
+                self.constants.insert((ty, bits, alignment), name.clone());
+                name
+            }
+        };
+
+        Operand::Data(name)
+    }
+
+    /// Label unique in the program. TACKY labels all have a different prefix
+    fn make_label(&mut self, prefix: &str) -> String {
+        let label = format!("{}.{}", prefix, self.counter);
+        self.counter += 1;
+        label
+    }
+
+    fn is_string(&self, value: &ir::Value) -> bool {
+        matches!(value, ir::Value::Var(name) if self.strings.contains(name.as_str()))
+    }
+
+    /// Type an argument is passed as, strings are passed by address
+    fn arg_type(&self, value: &ir::Value) -> AsmType {
+        if self.is_string(value) {
+            AsmType::Quadword
+        } else {
+            self.value_type(value)
+        }
+    }
+
+    fn value_type(&self, value: &ir::Value) -> AsmType {
+        match value {
+            ir::Value::Constant(constant) => asm_type(&constant.ctype()),
+            ir::Value::Var(name) => self.var_type(name),
+        }
+    }
+
+    fn var_type(&self, name: &str) -> AsmType {
+        match self.symbols.get(name).map(|symbol| &symbol.kind) {
+            Some(SymbolKind::Global { ctype, .. } | SymbolKind::Local(ctype)) => asm_type(ctype),
+            _ => unreachable!("'{}' is not a variable", name),
+        }
+    }
+
+    /// Assigns every pseudo operand a stack slot (or its static storage) and returns the stack
+    /// size
+    fn replace_pseudos(&self, instructions: &mut [Instruction]) -> i64 {
+        let mut offsets: HashMap<String, i64> = HashMap::new();
+        let mut stack_size = 0;
+
+        let mut replace = |operand: &mut Operand| {
+            let Operand::Pseudo(name) = operand else {
+                return;
+            };
+
+            *operand = match self.symbols.get(name).map(|symbol| &symbol.kind) {
+                Some(SymbolKind::Global { .. }) => Operand::Data(std::mem::take(name)),
+                Some(SymbolKind::Local(ctype)) => {
+                    let size = asm_type(ctype).size();
+                    let offset = *offsets.entry(std::mem::take(name)).or_insert_with(|| {
+                        // Each slot is aligned to its size
+                        stack_size = (stack_size + size + size - 1) / size * size;
+                        -stack_size
+                    });
+                    Operand::Stack(offset)
+                }
+                _ => unreachable!("'{}' is not a variable", name),
+            };
+        };
+
+        for instruction in instructions {
+            match instruction {
+                Instruction::Mov(_, src, dst)
+                | Instruction::Lea(src, dst)
+                | Instruction::Cvtsi2f(_, src, dst)
+                | Instruction::Cvttf2si(_, src, dst)
+                | Instruction::Cvtf2f(_, src, dst)
+                | Instruction::Binary(_, _, src, dst)
+                | Instruction::Cmp(_, src, dst) => {
+                    replace(src);
+                    replace(dst);
+                }
+                Instruction::Unary(_, _, operand)
+                | Instruction::Idiv(operand)
+                | Instruction::SetCC(_, operand)
+                | Instruction::Push(operand) => replace(operand),
+                Instruction::Cdq
+                | Instruction::Jmp(_)
+                | Instruction::JmpCC(..)
+                | Instruction::Label(_)
+                | Instruction::AllocateStack(_)
+                | Instruction::DeallocateStack(_)
+                | Instruction::Call(_)
+                | Instruction::Ret => {}
+            }
+        }
+
+        stack_size
+    }
+}
+
+/// Rewrites instructions whose operand combination x86 can't encode, using scratch registers.
+/// `%r10` and `%r11` are used for integers, `%xmm14` and `%xmm15` for floating-point values
+fn fix_up_instructions(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut out = Vec::with_capacity(instructions.len());
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Mov(ty, src, dst) if src.is_memory() && dst.is_memory() => {
+                let scratch = if ty.is_floating() {
+                    Reg::XMM14
+                } else {
+                    Reg::R10
+                };
+                out.push(Instruction::Mov(ty, src, Operand::Reg(scratch)));
+                out.push(Instruction::Mov(ty, Operand::Reg(scratch), dst));
+            }
+            Instruction::Idiv(Operand::Imm(value)) => {
+                out.push(Instruction::Mov(
+                    AsmType::Longword,
+                    Operand::Imm(value),
+                    Operand::Reg(Reg::R10),
+                ));
+                out.push(Instruction::Idiv(Operand::Reg(Reg::R10)));
+            }
+            // SSE arithmetic needs a register destination
+            Instruction::Binary(op, ty, src, dst) if ty.is_floating() && dst.is_memory() => {
+                out.push(Instruction::Mov(ty, dst.clone(), Operand::Reg(Reg::XMM15)));
+                out.push(Instruction::Binary(op, ty, src, Operand::Reg(Reg::XMM15)));
+                out.push(Instruction::Mov(ty, Operand::Reg(Reg::XMM15), dst));
+            }
+            Instruction::Binary(BinaryOperator::Mult, ty, src, dst) if dst.is_memory() => {
+                out.push(Instruction::Mov(ty, dst.clone(), Operand::Reg(Reg::R11)));
+                out.push(Instruction::Binary(
+                    BinaryOperator::Mult,
+                    ty,
+                    src,
+                    Operand::Reg(Reg::R11),
+                ));
+                out.push(Instruction::Mov(ty, Operand::Reg(Reg::R11), dst));
+            }
+            Instruction::Binary(op, ty, src, dst) if src.is_memory() && dst.is_memory() => {
+                out.push(Instruction::Mov(ty, src, Operand::Reg(Reg::R10)));
+                out.push(Instruction::Binary(op, ty, Operand::Reg(Reg::R10), dst));
+            }
+            // `ucomiss`/`ucomisd` need a register as the second operand
+            Instruction::Cmp(ty, left, right) if ty.is_floating() && right.is_memory() => {
+                out.push(Instruction::Mov(ty, right, Operand::Reg(Reg::XMM15)));
+                out.push(Instruction::Cmp(ty, left, Operand::Reg(Reg::XMM15)));
+            }
+            Instruction::Cmp(ty, left, right) if left.is_memory() && right.is_memory() => {
+                out.push(Instruction::Mov(ty, left, Operand::Reg(Reg::R10)));
+                out.push(Instruction::Cmp(ty, Operand::Reg(Reg::R10), right));
+            }
+            Instruction::Cmp(ty, left, Operand::Imm(value)) => {
+                out.push(Instruction::Mov(
+                    ty,
+                    Operand::Imm(value),
+                    Operand::Reg(Reg::R11),
+                ));
+                out.push(Instruction::Cmp(ty, left, Operand::Reg(Reg::R11)));
+            }
+            Instruction::Cvtsi2f(ty, src, dst) => {
+                let src = match src {
+                    Operand::Imm(value) => {
+                        out.push(Instruction::Mov(
+                            AsmType::Longword,
+                            Operand::Imm(value),
+                            Operand::Reg(Reg::R10),
+                        ));
+                        Operand::Reg(Reg::R10)
+                    }
+                    src => src,
+                };
+                if let Operand::Reg(_) = dst {
+                    out.push(Instruction::Cvtsi2f(ty, src, dst));
+                } else {
+                    out.push(Instruction::Cvtsi2f(ty, src, Operand::Reg(Reg::XMM15)));
+                    out.push(Instruction::Mov(ty, Operand::Reg(Reg::XMM15), dst));
+                }
+            }
+            Instruction::Cvttf2si(ty, src, dst) if !matches!(dst, Operand::Reg(_)) => {
+                out.push(Instruction::Cvttf2si(ty, src, Operand::Reg(Reg::R11)));
+                out.push(Instruction::Mov(
+                    AsmType::Longword,
+                    Operand::Reg(Reg::R11),
+                    dst,
+                ));
+            }
+            Instruction::Cvtf2f(ty, src, dst) if !matches!(dst, Operand::Reg(_)) => {
+                out.push(Instruction::Cvtf2f(ty, src, Operand::Reg(Reg::XMM15)));
+                out.push(Instruction::Mov(ty, Operand::Reg(Reg::XMM15), dst));
+            }
+            instruction => out.push(instruction),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, semantic, tacky};
+    use pretty_assertions::assert_eq;
+
+    fn compile(src: &str) -> Program {
+        let mut unit = parse(src).unwrap();
+        let mut symbols = semantic::analyze(&mut unit).unwrap();
+        let ir = tacky::generate(&unit, &mut symbols);
+        generate(&ir, &symbols)
+    }
+
+    #[test]
+    fn test_codegen_return_constant() {
+        let program = compile("int main(void) { return 2; }");
+
+        let expected = Program {
+            top_level: vec![TopLevel::Function(Function {
+                name: "main".to_string(),
+                stack_size: 0,
+                instructions: vec![
+                    Instruction::Mov(AsmType::Longword, Operand::Imm(2), Operand::Reg(Reg::AX)),
+                    Instruction::Ret,
+                    Instruction::Mov(AsmType::Longword, Operand::Imm(0), Operand::Reg(Reg::AX)),
+                    Instruction::Ret,
+                ],
+            })],
+        };
+
+        assert_eq!(expected, program);
+    }
+
+    #[test]
+    fn test_codegen_memory_operands_are_fixed_up() {
+        let program = compile("int a = 3; int main(void) { return a * -a; }");
+
+        let TopLevel::Function(main) = &program.top_level[1] else {
+            panic!("expected a function");
+        };
+        assert_eq!(16, main.stack_size);
+        assert_eq!(
+            main.instructions[..5],
+            [
+                // tmp.0 = -a
+                Instruction::Mov(
+                    AsmType::Longword,
+                    Operand::Data("a".to_string()),
+                    Operand::Reg(Reg::R10)
+                ),
+                Instruction::Mov(
+                    AsmType::Longword,
+                    Operand::Reg(Reg::R10),
+                    Operand::Stack(-4)
+                ),
+                Instruction::Unary(UnaryOperator::Neg, AsmType::Longword, Operand::Stack(-4)),
+                // tmp.1 = a * tmp.0
+                Instruction::Mov(
+                    AsmType::Longword,
+                    Operand::Data("a".to_string()),
+                    Operand::Reg(Reg::R10)
+                ),
+                Instruction::Mov(
+                    AsmType::Longword,
+                    Operand::Reg(Reg::R10),
+                    Operand::Stack(-8)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_call_with_stack_arguments() {
+        let src = "int f(int a, int b, int c, int d, int e, int f, int g);\n\
+                   int main(void) { return f(1, 2, 3, 4, 5, 6, 7); }";
+        let program = compile(src);
+
+        let TopLevel::Function(main) = &program.top_level[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            main.instructions[..10],
+            [
+                Instruction::AllocateStack(8),
+                Instruction::Mov(AsmType::Longword, Operand::Imm(1), Operand::Reg(Reg::DI)),
+                Instruction::Mov(AsmType::Longword, Operand::Imm(2), Operand::Reg(Reg::SI)),
+                Instruction::Mov(AsmType::Longword, Operand::Imm(3), Operand::Reg(Reg::DX)),
+                Instruction::Mov(AsmType::Longword, Operand::Imm(4), Operand::Reg(Reg::CX)),
+                Instruction::Mov(AsmType::Longword, Operand::Imm(5), Operand::Reg(Reg::R8)),
+                Instruction::Mov(AsmType::Longword, Operand::Imm(6), Operand::Reg(Reg::R9)),
+                Instruction::Push(Operand::Imm(7)),
+                Instruction::Call("f".to_string()),
+                Instruction::DeallocateStack(16),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_emit_binary_expression() {
+        let asm = compile("int a = 3; int main(void) { return a / 2; }").to_string();
+
+        assert!(asm.contains("a:\n\t.long 3\n"));
+        assert!(asm.contains("\tmovl a(%rip), %eax\n\tcdq\n"));
+        assert!(asm.contains("\tmovl $2, %r10d\n\tidivl %r10d\n"));
+    }
+
+    #[test]
+    fn test_codegen_emit_shift_and_comparison() {
+        let asm = compile("int a = 3; int main(void) { return (a << a) <= a % 2; }").to_string();
+
+        assert!(asm.contains("\tmovl a(%rip), %ecx\n"));
+        assert!(asm.contains("\tsall %cl, -4(%rbp)\n"));
+        assert!(asm.contains("\tmovl %edx, -8(%rbp)\n"));
+        assert!(asm.contains("\tsetle -12(%rbp)\n"));
+    }
+
+    #[test]
+    fn test_codegen_emit_string_literals() {
+        let src = r#"int f(); int main(void) { return f("a\"\n", 1, 2, 3, 4, 5, "b"); }"#;
+        let asm = compile(src).to_string();
+
+        assert!(asm.contains("\tleaq string.0(%rip), %rdi\n"));
+        assert!(asm.contains("\tleaq string.1(%rip), %rax\n\tpushq %rax\n"));
+        assert!(asm.contains("\t.section .rodata\nstring.0:\n\t.ascii \"a\\\"\\012\\000\"\n"));
+        assert!(!asm.contains(".globl string"));
+    }
+
+    #[test]
+    fn test_codegen_double_arguments_use_xmm_registers() {
+        let src = "double f(int a, double b, float c);\n\
+                   double main(void) { return f(1, 2.5, 3); }";
+        let program = compile(src);
+
+        let TopLevel::Function(main) = &program.top_level[0] else {
+            panic!("expected a function");
+        };
+        let double = Operand::Data("const.0".to_string());
+        let float = Operand::Data("const.1".to_string());
+        assert_eq!(
+            main.instructions[..5],
+            [
+                Instruction::Mov(AsmType::Longword, Operand::Imm(1), Operand::Reg(Reg::DI)),
+                Instruction::Mov(AsmType::Double, double, Operand::Reg(Reg::XMM0)),
+                Instruction::Mov(AsmType::Float, float, Operand::Reg(Reg::XMM1)),
+                Instruction::Call("f".to_string()),
+                Instruction::Mov(AsmType::Double, Operand::Reg(Reg::XMM0), Operand::Stack(-8)),
+            ]
+        );
+        assert_eq!(
+            program.top_level[1..],
+            [
+                TopLevel::StaticConstant(StaticConstant {
+                    name: "const.0".to_string(),
+                    alignment: 8,
+                    init: StaticInit::Double(2.5),
+                }),
+                TopLevel::StaticConstant(StaticConstant {
+                    name: "const.1".to_string(),
+                    alignment: 4,
+                    init: StaticInit::Float(3.0),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_emit_floating_point() {
+        let src = "double d = 1.5; int printf();\n\
+                   int main(void) { printf(\"%f\", -d); return d < 2 && (int)d; }";
+        let asm = compile(src).to_string();
+
+        assert!(asm.contains("\t.balign 8\nd:\n\t.quad 4609434218613702656 # 1.5\n"));
+        assert!(asm.contains("\tmovsd d(%rip), %xmm14\n\tmovsd %xmm14, -8(%rbp)\n"));
+        assert!(asm.contains("\txorpd const.0(%rip), %xmm15\n"));
+        assert!(
+            asm.contains("\t.balign 16\nconst.0:\n\t.quad 9223372036854775808 # -0.0\n\t.zero 8\n")
+        );
+        // `printf` has no prototype, so `%al` holds the number of vector registers used
+        assert!(asm.contains("\tmovsd -8(%rbp), %xmm0\n\tmovl $1, %eax\n\tcall printf@PLT\n"));
+        assert!(
+            asm.contains("\tucomisd d(%rip), %xmm15\n\tmovl $0, -16(%rbp)\n\tseta -16(%rbp)\n")
+        );
+        assert!(asm.contains("\tcvttsd2si d(%rip), %r11d\n"));
+    }
+
+    #[test]
+    fn test_codegen_emit_non_ascii_names() {
+        let src = "int café = 1; int 𝑓(void) { return café; }\n\
+                   int main(void) { return 𝑓(); }";
+        let asm = compile(src).to_string();
+
+        // Written as UTF-8 like gcc does, the assembler takes any byte above 0x7f in a symbol
+        assert!(asm.contains("\t.globl café\n"));
+        assert!(asm.contains("\tmovl café(%rip), %eax\n"));
+        assert!(asm.contains("\t.globl 𝑓\n\t.text\n𝑓:\n"));
+        assert!(asm.contains("\tcall 𝑓@PLT\n"));
+    }
+}
@@ -0,0 +1,102 @@
+//! Thin wrappers around the system toolchain (`gcc`) for the stages we don't implement ourselves
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    process::{self, Command, ExitStatus},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub type DriverResult<T> = Result<T, DriverError>;
+
+#[derive(Debug)]
+pub enum DriverError {
+    /// The tool could not be started at all
+    Spawn(&'static str, io::Error),
+    /// The tool ran, but reported failure
+    Failed(&'static str, ExitStatus),
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverError::Spawn(stage, e) => write!(f, "ERROR: Failed to run the {}: {}", stage, e),
+            DriverError::Failed(stage, status) => {
+                write!(f, "ERROR: The {} failed with {}", stage, status)
+            }
+        }
+    }
+}
+
+/// Assemble `asm` into the object file `out`
+pub fn assemble(asm: &Path, out: &Path) -> DriverResult<()> {
+    let mut command = Command::new("gcc");
+    command.arg("-c").arg(asm).arg("-o").arg(out);
+    run("assembler", command)
+}
+
+/// Assemble (if needed) and link `inputs` into the executable `out`
+pub fn link(inputs: &[PathBuf], out: &Path) -> DriverResult<()> {
+    let mut command = Command::new("gcc");
+    command.args(inputs).arg("-o").arg(out);
+    run("linker", command)
+}
+
+/// A private directory for intermediate files, removed with everything in it when dropped
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates a new, uniquely named directory under the system temporary directory
+    pub fn new() -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let mut attempt = 0u32;
+        loop {
+            let name = format!("c_compiler-{}-{}-{}", process::id(), nanos, attempt);
+            let path = env::temp_dir().join(name);
+            match private_dir_builder().create(&path) {
+                Ok(()) => return Ok(Self { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Nothing useful can be done if cleanup fails
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Only the owner can read the directory, so other users can't tamper with the files in it
+#[cfg(unix)]
+fn private_dir_builder() -> fs::DirBuilder {
+    use std::os::unix::fs::DirBuilderExt;
+    let mut builder = fs::DirBuilder::new();
+    builder.mode(0o700);
+    builder
+}
+
+#[cfg(not(unix))]
+fn private_dir_builder() -> fs::DirBuilder {
+    fs::DirBuilder::new()
+}
+
+fn run(stage: &'static str, mut command: Command) -> DriverResult<()> {
+    let status = command.status().map_err(|e| DriverError::Spawn(stage, e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(DriverError::Failed(stage, status))
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use crate::{Token, TokenKind, span::LineIndex};
+
+/// Listing of lexed tokens printed by `--lex`: one token per line, with its span as
+/// `line:column-line:column`, its category and its source text. Errors add their message
+pub struct TokenTable<'a> {
+    lines: &'a LineIndex<'a>,
+    tokens: &'a [Token],
+}
+
+/// The same listing as a JSON array, one object per token:
+///
+/// ```json
+/// {"kind": "identifier", "text": "main", "span": {"start": {"offset": 4, "line": 1, "column": 5}, "end": {...}}}
+/// ```
+///
+/// Errors have `"kind": "error"` and a `"message"`
+pub struct TokenJson<'a> {
+    lines: &'a LineIndex<'a>,
+    tokens: &'a [Token],
+}
+
+impl<'a> TokenTable<'a> {
+    pub fn new(lines: &'a LineIndex<'a>, tokens: &'a [Token]) -> Self {
+        Self { lines, tokens }
+    }
+}
+
+impl<'a> TokenJson<'a> {
+    pub fn new(lines: &'a LineIndex<'a>, tokens: &'a [Token]) -> Self {
+        Self { lines, tokens }
+    }
+}
+
+impl fmt::Display for TokenTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.tokens {
+            let (line, column) = self.lines.line_col(token.span.start);
+            let (end_line, end_column) = self.lines.line_col(token.span.end);
+            let span = format!("{}:{}-{}:{}", line, column, end_line, end_column);
+            write!(f, "{:<16}{:<12}", span, token.kind.category())?;
+
+            // Error tokens can cover several lines, e.g. an unterminated comment
+            let text = &self.lines.src()[token.span.start..token.span.end];
+            for ch in text.chars() {
+                write_escaped(f, ch)?;
+            }
+            if let TokenKind::Error(message, _) = token.kind {
+                // The message quotes the unexpected character, which can be a control character
+                write!(f, "  ")?;
+                for ch in message.as_str().chars() {
+                    write_escaped(f, ch)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TokenJson<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[")?;
+        for (i, token) in self.tokens.iter().enumerate() {
+            let text = &self.lines.src()[token.span.start..token.span.end];
+            write!(
+                f,
+                "  {{\"kind\": \"{}\", \"text\": {}",
+                token.kind.category(),
+                JsonString(text)
+            )?;
+            if let TokenKind::Error(message, _) = token.kind {
+                write!(f, ", \"message\": {}", JsonString(&message.as_str()))?;
+            }
+
+            write!(f, ", \"span\": {{")?;
+            for (name, offset) in [("start", token.span.start), ("end", token.span.end)] {
+                let (line, column) = self.lines.line_col(offset);
+                if name == "end" {
+                    write!(f, ", ")?;
+                }
+                write!(
+                    f,
+                    "\"{}\": {{\"offset\": {}, \"line\": {}, \"column\": {}}}",
+                    name, offset, line, column
+                )?;
+            }
+            write!(f, "}}}}")?;
+
+            if i + 1 < self.tokens.len() {
+                write!(f, ",")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "]")
+    }
+}
+
+/// A JSON string literal, quoted and escaped
+struct JsonString<'a>(&'a str);
+
+impl fmt::Display for JsonString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"")?;
+        for ch in self.0.chars() {
+            match ch {
+                '"' => write!(f, "\\\"")?,
+                '\\' => write!(f, "\\\\")?,
+                ch => write_escaped(f, ch)?,
+            }
+        }
+        write!(f, "\"")
+    }
+}
+
+/// Writes `ch`, with control characters escaped so that they can't break the line or the JSON
+/// string they are printed in
+fn write_escaped(f: &mut fmt::Formatter<'_>, ch: char) -> fmt::Result {
+    match ch {
+        '\n' => write!(f, "\\n"),
+        '\r' => write!(f, "\\r"),
+        '\t' => write!(f, "\\t"),
+        ch if ch.is_control() => write!(f, "\\u{:04x}", ch as u32),
+        ch => write!(f, "{}", ch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::lexer::Lexer;
+
+    #[test]
+    fn test_dump_token_table() {
+        let src = "int main(void) {\n\treturn 1foo; // \"done\"\n}";
+        let lines = LineIndex::new(src);
+        let tokens = Lexer::new(src).lex_all();
+
+        assert_eq!(
+            TokenTable::new(&lines, &tokens).to_string(),
+            "1:1-1:4         keyword     int\n\
+             1:5-1:9         identifier  main\n\
+             1:9-1:10        punctuator  (\n\
+             1:10-1:14       keyword     void\n\
+             1:14-1:15       punctuator  )\n\
+             1:16-1:17       punctuator  {\n\
+             2:2-2:8         keyword     return\n\
+             2:9-2:13        error       1foo  \
+             Invalid suffix 'foo' on integer literal '1': identifiers can't start with a digit\n\
+             2:13-2:14       punctuator  ;\n\
+             3:1-3:2         punctuator  }\n\
+             3:2-3:2         eof         \n"
+        );
+    }
+
+    #[test]
+    fn test_dump_token_table_escapes_control_characters() {
+        let src = "a\x01\r\n/* \x0b\r\n";
+        let lines = LineIndex::new(src);
+        let tokens = Lexer::new(src).lex_all();
+
+        let table = TokenTable::new(&lines, &tokens).to_string();
+        assert_eq!(table.lines().count(), tokens.len());
+        assert!(table.contains("error       \\u0001  Unexpected character: '\\u0001'"));
+        assert!(table.contains("error       /* \\u000b\\r\\n  Unterminated multi-line comment"));
+    }
+
+    #[test]
+    fn test_dump_token_json() {
+        let src = "x = \"a\\\"\";\n'";
+        let lines = LineIndex::new(src);
+        let tokens = Lexer::new(src).lex_all();
+
+        assert_eq!(
+            TokenJson::new(&lines, &tokens).to_string(),
+            r#"[
+  {"kind": "identifier", "text": "x", "span": {"start": {"offset": 0, "line": 1, "column": 1}, "end": {"offset": 1, "line": 1, "column": 2}}},
+  {"kind": "punctuator", "text": "=", "span": {"start": {"offset": 2, "line": 1, "column": 3}, "end": {"offset": 3, "line": 1, "column": 4}}},
+  {"kind": "string", "text": "\"a\\\"\"", "span": {"start": {"offset": 4, "line": 1, "column": 5}, "end": {"offset": 9, "line": 1, "column": 10}}},
+  {"kind": "punctuator", "text": ";", "span": {"start": {"offset": 9, "line": 1, "column": 10}, "end": {"offset": 10, "line": 1, "column": 11}}},
+  {"kind": "error", "text": "'", "message": "Missing terminating ' character", "span": {"start": {"offset": 11, "line": 2, "column": 1}, "end": {"offset": 12, "line": 2, "column": 2}}},
+  {"kind": "eof", "text": "", "span": {"start": {"offset": 12, "line": 2, "column": 2}, "end": {"offset": 12, "line": 2, "column": 2}}}
+]
+"#
+        );
+    }
+}
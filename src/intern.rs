@@ -0,0 +1,129 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasherDefault, Hasher},
+    marker::PhantomData,
+};
+
+/// Interned byte string: identifiers, the contents of string literals and lexing error
+/// messages. Equal strings get the same `Interned`, so comparing and copying is free and tokens
+/// don't own any heap data
+///
+/// The interner is per thread and never frees its strings, so an `Interned` is neither `Send`
+/// nor `Sync` and can't leave the thread that created it:
+///
+/// ```compile_fail
+/// let name = c_compiler::intern::Interned::new("main");
+/// std::thread::spawn(move || name.as_str().len());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interned(u32, PhantomData<*const ()>);
+
+#[derive(Default)]
+struct Interner {
+    ids: FxHashMap<&'static [u8], Interned>,
+    strings: Vec<&'static [u8]>,
+}
+
+/// `HashMap` with the fast hasher below, for keys like `Interned`
+pub(crate) type FxHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
+
+/// The multiply-rotate hash used by rustc. Much faster than the default SipHash on short keys
+/// like identifiers, and the keys are not attacker-controlled in a way that matters here
+#[derive(Default)]
+pub(crate) struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        for &byte in chunks.remainder() {
+            self.add(byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, byte: u8) {
+        self.add(byte as u64);
+    }
+
+    fn write_usize(&mut self, word: usize) {
+        self.add(word as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::default();
+}
+
+impl Interned {
+    pub fn new(string: impl AsRef<[u8]>) -> Self {
+        let bytes = string.as_ref();
+        INTERNER.with_borrow_mut(|interner| {
+            if let Some(&interned) = interner.ids.get(bytes) {
+                return interned;
+            }
+
+            // Leaked, so that lookups can hand out `&'static` references
+            let bytes: &'static [u8] = Box::leak(bytes.into());
+            let interned = Interned(interner.strings.len() as u32, PhantomData);
+            interner.strings.push(bytes);
+            interner.ids.insert(bytes, interned);
+            interned
+        })
+    }
+
+    pub fn as_bytes(self) -> &'static [u8] {
+        INTERNER.with_borrow(|interner| interner.strings[self.0 as usize])
+    }
+
+    /// The string as UTF-8, with invalid sequences replaced. Identifiers and error messages are
+    /// always valid UTF-8
+    pub fn as_str(self) -> std::borrow::Cow<'static, str> {
+        String::from_utf8_lossy(self.as_bytes())
+    }
+}
+
+impl fmt::Debug for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_same_string_same_id() {
+        let a = Interned::new("main");
+        let b = Interned::new(String::from("main"));
+        let c = Interned::new(b"puts");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.as_str(), "main");
+        assert_eq!(c.as_bytes(), b"puts");
+        assert_eq!(format!("{:?}", a), "\"main\"");
+    }
+}
@@ -1,174 +1,915 @@
-use std::{iter::Peekable, str::Chars};
+use std::collections::VecDeque;
 
-use crate::Token;
+use crate::{
+    Token,
+    intern::Interned,
+    span::Span,
+    standard::Standard,
+    token::{Checkpoint, FloatType, IntegerType, TokenKind, TokenSource},
+};
 
+/// Lexes a source buffer by indexing into its bytes. Tokens don't borrow from nor own any
+/// part of the source: identifiers and string literals are interned
+///
+/// As an `Iterator`, the lexer yields every token up to and including `EOF`
 pub struct Lexer<'a> {
-    buffer: Peekable<Chars<'a>>,
+    src: &'a [u8],
+    /// Byte offset of the next character
+    pos: usize,
+    /// Tokens already lexed by `peek_nth` but not consumed yet
+    lookahead: VecDeque<Token>,
+    /// Whether the iterator has yielded `EOF`
+    finished: bool,
+    /// Contents of the quoted literal or spelled-out identifier being read, reused between
+    /// tokens
+    scratch: Vec<u8>,
+    /// Decides which characters identifiers may contain
+    standard: Standard,
+}
+
+/// Whitespace before a token. The preprocessor needs it: a directive ends with its line, and
+/// `#define f(x)` is not `#define f (x)`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Spacing {
+    /// The token is the first on its line
+    pub line_start: bool,
+    /// Whitespace or a comment comes right before the token
+    pub leading_space: bool,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(s: &'a str) -> Lexer<'a> {
+    pub fn new<S: AsRef<[u8]> + ?Sized>(src: &'a S) -> Lexer<'a> {
         Lexer {
-            buffer: s.chars().peekable(),
+            src: src.as_ref(),
+            pos: 0,
+            lookahead: VecDeque::new(),
+            finished: false,
+            scratch: Vec::new(),
+            standard: Standard::default(),
         }
     }
 
+    /// Lexes according to `standard` instead of the default C17
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
     /// Lex all tokens
-    pub fn lex_all(mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
+    pub fn lex_all(self) -> Vec<Token> {
+        self.collect()
+    }
+
+    /// Get the next token. Once the end of the source is reached, every call returns `EOF`
+    pub fn next_token(&mut self) -> Token {
+        match self.lookahead.pop_front() {
+            Some(token) => token,
+            None => self.lex_token(),
+        }
+    }
+
+    /// Looks at the token `n` tokens past the next one without consuming anything
+    pub fn peek_nth(&mut self, n: usize) -> Token {
+        while self.lookahead.len() <= n {
+            let token = self.lex_token();
+            self.lookahead.push_back(token);
+        }
+        self.lookahead[n]
+    }
+
+    /// Gets the next token along with the whitespace before it. Must not be mixed with `peek_nth`,
+    /// lookahead tokens don't keep their spacing
+    pub fn next_token_spaced(&mut self) -> (Token, Spacing) {
+        debug_assert!(
+            self.lookahead.is_empty(),
+            "spacing of peeked tokens is lost"
+        );
+        self.lex_token_spaced()
+    }
+
+    /// Saves the current position, so that the tokens consumed from now on can be read again
+    pub fn checkpoint(&self) -> Checkpoint {
+        let pos = match self.lookahead.front() {
+            Some(token) => token.span.start,
+            None => self.pos,
+        };
+        Checkpoint { pos }
+    }
+
+    /// Goes back to a position saved with `checkpoint`. The tokens after it are lexed again
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.pos;
+        self.lookahead.clear();
+        self.finished = false;
+    }
+
+    fn lex_token(&mut self) -> Token {
+        self.lex_token_spaced().0
+    }
+
+    fn lex_token_spaced(&mut self) -> (Token, Spacing) {
+        let mut spacing = Spacing {
+            line_start: self.pos == 0,
+            leading_space: false,
+        };
         loop {
-            let token = self.next_token();
-            tokens.push(token);
-            if tokens.last() == Some(&Token::EOF) {
-                break;
+            let whitespace_start = self.pos;
+            spacing.line_start |= self.consume_whitespace();
+            spacing.leading_space |= self.pos != whitespace_start;
+
+            let start = self.pos;
+            // `None` means a comment was skipped
+            match self.next_token_at(start) {
+                Some(token) => return (token, spacing),
+                None => spacing.leading_space = true,
             }
         }
-        tokens
     }
 
-    /// Get the next token
-    pub fn next_token(&mut self) -> Token {
-        self.consume_whitespace();
-
-        let ch = match self.buffer.next() {
-            Some(ch) => ch,
-            None => return Token::EOF,
+    fn next_token_at(&mut self, start: usize) -> Option<Token> {
+        let byte = match self.bump() {
+            Some(byte) => byte,
+            None => return Some(Token::new(TokenKind::EOF, Span::new(start, start))),
         };
 
-        match ch {
+        let kind = match byte {
             // Punctuation
-            '(' => Token::OpenParen,
-            ')' => Token::CloseParen,
-            '{' => Token::OpenBrace,
-            '}' => Token::CloseBrace,
-            ';' => Token::Semicolon,
-
-            // Operators
-            '+' => Token::Plus,
-            '-' => Token::Minus,
-            '*' => Token::Asterix,
-            '/' => {
+            b'(' => TokenKind::OpenParen,
+            b')' => TokenKind::CloseParen,
+            b'{' => TokenKind::OpenBrace,
+            b'}' => TokenKind::CloseBrace,
+            b'[' => TokenKind::OpenBracket,
+            b']' => TokenKind::CloseBracket,
+            b';' => TokenKind::Semicolon,
+            b',' => TokenKind::Comma,
+            b'?' => TokenKind::Question,
+            b'~' => TokenKind::Tilde,
+            b':' if self.eat(b'>') => TokenKind::CloseBracket,
+            b':' => TokenKind::Colon,
+            b'#' if self.eat(b'#') => TokenKind::HashHash,
+            b'#' => TokenKind::Hash,
+            b'.' if self.peek_byte_nth(0) == Some(b'.') && self.peek_byte_nth(1) == Some(b'.') => {
+                self.pos += 2;
+                TokenKind::Ellipsis
+            }
+            b'.' if self.peek_byte().is_some_and(|byte| byte.is_ascii_digit()) => {
+                return Some(self.read_number(start));
+            }
+            b'.' => TokenKind::Dot,
+
+            // Operators, longest match first
+            b'+' if self.eat(b'+') => TokenKind::Increment,
+            b'+' if self.eat(b'=') => TokenKind::PlusAssignment,
+            b'+' => TokenKind::Plus,
+            b'-' if self.eat(b'-') => TokenKind::Decrement,
+            b'-' if self.eat(b'=') => TokenKind::MinusAssignment,
+            b'-' if self.eat(b'>') => TokenKind::Arrow,
+            b'-' => TokenKind::Minus,
+            b'*' if self.eat(b'=') => TokenKind::AsterixAssignment,
+            b'*' => TokenKind::Asterix,
+            b'/' => {
                 // Check if it is a comment
-                match self.buffer.peek() {
-                    Some('/') => {
-                        self.buffer.next(); // Consume the second '/'
+                match self.peek_byte() {
+                    Some(b'/') => {
+                        self.bump(); // Consume the second '/'
                         self.consume_comment();
-
-                        // Get the next token (recursive call)
-                        self.next_token()
+                        return None;
                     }
-                    Some('*') => {
-                        self.buffer.next(); // Consume '*'
+                    Some(b'*') => {
+                        self.bump(); // Consume '*'
                         match self.consume_multi_line_comment() {
-                            Ok(()) => self.next_token(),
-                            Err(err) => Token::Error(err),
+                            Ok(()) => return None,
+                            Err(err) => error(err),
                         }
                     }
+                    Some(b'=') => {
+                        self.bump(); // Consume '='
+                        TokenKind::SlashAssignment
+                    }
 
-                    _ => Token::Slash,
+                    _ => TokenKind::Slash,
+                }
+            }
+            b'%' if self.eat(b'=') => TokenKind::PercentAssignment,
+            b'%' if self.eat(b'>') => TokenKind::CloseBrace,
+            b'%' if self.eat(b':') => {
+                if self.peek_byte_nth(0) == Some(b'%') && self.peek_byte_nth(1) == Some(b':') {
+                    self.pos += 2;
+                    TokenKind::HashHash
+                } else {
+                    TokenKind::Hash
                 }
             }
-            '=' => {
-                if let Some('=') = self.buffer.peek() {
-                    self.buffer.next(); // Consume the second `=`
-                    Token::Equal
+            b'%' => TokenKind::Percent,
+            b'&' if self.eat(b'&') => TokenKind::LogicalAnd,
+            b'&' if self.eat(b'=') => TokenKind::AmpersandAssignment,
+            b'&' => TokenKind::Ampersand,
+            b'|' if self.eat(b'|') => TokenKind::LogicalOr,
+            b'|' if self.eat(b'=') => TokenKind::PipeAssignment,
+            b'|' => TokenKind::Pipe,
+            b'^' if self.eat(b'=') => TokenKind::CaretAssignment,
+            b'^' => TokenKind::Caret,
+            b'!' if self.eat(b'=') => TokenKind::NotEqual,
+            b'!' => TokenKind::Bang,
+            b'=' if self.eat(b'=') => TokenKind::Equal,
+            b'=' => TokenKind::Assignment,
+            b'<' if self.eat(b'<') => {
+                if self.eat(b'=') {
+                    TokenKind::ShiftLeftAssignment
                 } else {
-                    Token::Assignment
+                    TokenKind::ShiftLeft
                 }
             }
+            b'<' if self.eat(b'=') => TokenKind::LessEqual,
+            b'<' if self.eat(b':') => TokenKind::OpenBracket,
+            b'<' if self.eat(b'%') => TokenKind::OpenBrace,
+            b'<' => TokenKind::Less,
+            b'>' if self.eat(b'>') => {
+                if self.eat(b'=') {
+                    TokenKind::ShiftRightAssignment
+                } else {
+                    TokenKind::ShiftRight
+                }
+            }
+            b'>' if self.eat(b'=') => TokenKind::GreaterEqual,
+            b'>' => TokenKind::Greater,
 
             // Keyword or identifier
-            ch if ch.is_alphabetic() || ch == '_' => self.read_keyword_or_identifier(ch),
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+                return Some(self.read_keyword_or_identifier(start));
+            }
+            // Universal character names and non-ASCII letters can start an identifier too
+            b'\\' if matches!(self.peek_byte(), Some(b'u' | b'U')) => {
+                self.pos = start;
+                return Some(self.read_keyword_or_identifier(start));
+            }
+            0x80.. if self.char_at(start).is_some_and(unicode_ident::is_xid_start) => {
+                self.pos = start;
+                return Some(self.read_keyword_or_identifier(start));
+            }
 
-            // Integer literal
-            ch if ch.is_ascii_digit() => self.read_integer_literal(ch),
+            // Integer or floating literal
+            b'0'..=b'9' => return Some(self.read_number(start)),
+
+            // Character and string literals
+            b'\'' => match self.read_quoted(b'\'') {
+                Ok(()) if self.scratch.is_empty() => error("Empty character constant".to_string()),
+                Ok(()) => TokenKind::CharacterLiteral(character_value(&self.scratch)),
+                Err(err) => error(err),
+            },
+            b'"' => match self.read_quoted(b'"') {
+                Ok(()) => TokenKind::StringLiteral(Interned::new(&self.scratch)),
+                Err(err) => error(err),
+            },
+
+            0x80.. => {
+                self.pos = start;
+                error(format!("Unexpected character: '{}'", self.bump_char()))
+            }
 
             // Unexpected token
-            ch => Token::Error(format!("Unexpected character: '{}'", ch)),
-        }
+            byte => error(format!("Unexpected character: '{}'", byte as char)),
+        };
+
+        Some(Token::new(kind, Span::new(start, self.pos)))
     }
 
-    fn read_keyword_or_identifier(&mut self, first: char) -> Token {
-        let mut literal = String::new();
-        literal.push(first);
+    /// Reads the rest of an identifier starting at `start`. ASCII characters take a fast path,
+    /// others are decoded as UTF-8 and must be XID_Start/XID_Continue. A universal character
+    /// name stands for its character, so `caf\u00e9` and `café` are the same identifier
+    ///
+    /// In C89 the whole identifier is still read, but a non-ASCII character makes it an error
+    fn read_keyword_or_identifier(&mut self, start: usize) -> Token {
+        // Set once a universal character name is seen: from then on the name is built in
+        // `scratch`, since it differs from the source text
+        let mut decoded = false;
+        // The first invalid character and its offset in the identifier
+        let mut invalid: Option<(String, usize)> = None;
 
-        while let Some(&ch) = self.buffer.peek() {
-            if ch.is_alphanumeric() || ch == '_' {
-                literal.push(self.buffer.next().unwrap());
-            } else {
-                break;
+        loop {
+            let char_start = self.pos;
+            let is_start = char_start == start;
+            match self.peek_byte() {
+                Some(byte) if byte.is_ascii_alphanumeric() || byte == b'_' => {
+                    self.pos += 1;
+                    if decoded {
+                        self.scratch.push(byte);
+                    }
+                }
+                Some(0x80..) => {
+                    let ch = match self.peek_char() {
+                        Some(ch) if is_start && unicode_ident::is_xid_start(ch) => ch,
+                        Some(ch) if !is_start && unicode_ident::is_xid_continue(ch) => ch,
+                        _ => break,
+                    };
+                    self.pos += ch.len_utf8();
+                    if decoded {
+                        self.scratch
+                            .extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                    if !self.standard.extended_identifiers() && invalid.is_none() {
+                        let message = format!(
+                            "Character '{}' is not allowed in identifiers before C99",
+                            ch
+                        );
+                        invalid = Some((message, char_start - start));
+                    }
+                }
+                Some(b'\\') if matches!(self.peek_byte_nth(1), Some(b'u' | b'U')) => {
+                    if !decoded {
+                        self.scratch.clear();
+                        self.scratch.extend_from_slice(&self.src[start..char_start]);
+                        decoded = true;
+                    }
+                    self.pos += 2;
+                    let escape = self.src[char_start + 1];
+                    let result = self.read_universal_character(escape).and_then(|ch| {
+                        let allowed = if is_start {
+                            unicode_ident::is_xid_start(ch)
+                        } else {
+                            unicode_ident::is_xid_continue(ch)
+                        };
+                        // C17 6.4.3p2: only `$`, `@` and `` ` `` may be named below U+00A0,
+                        // and none of them is an identifier character
+                        if ch as u32 >= 0xa0 && allowed {
+                            Ok(ch)
+                        } else {
+                            Err(format!(
+                                "Universal character name '{}' is not valid in an identifier",
+                                self.text(char_start, self.pos)
+                            ))
+                        }
+                    });
+                    let result = result.and_then(|ch| {
+                        if self.standard.extended_identifiers() {
+                            Ok(ch)
+                        } else {
+                            Err(format!(
+                                "Universal character name '{}' is not allowed in identifiers \
+                                 before C99",
+                                self.text(char_start, self.pos)
+                            ))
+                        }
+                    });
+                    match result {
+                        Ok(ch) => self
+                            .scratch
+                            .extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+                        Err(message) => {
+                            if invalid.is_none() {
+                                invalid = Some((message, char_start - start));
+                            }
+                        }
+                    }
+                }
+                _ => break,
             }
         }
 
-        match literal.as_str() {
-            "int" => Token::Int,
-            "void" => Token::Void,
-            "return" => Token::Return,
-            _ => Token::Identifier(literal),
+        // The token is the whole identifier, so that its name isn't lexed again as another one
+        if let Some((message, offset)) = invalid {
+            return Token::new(error_at(message, offset), Span::new(start, self.pos));
         }
+
+        let name = if decoded {
+            &self.scratch[..]
+        } else {
+            &self.src[start..self.pos]
+        };
+        let kind = TokenKind::from_keyword(name)
+            .unwrap_or_else(|| TokenKind::Identifier(Interned::new(name)));
+        Token::new(kind, Span::new(start, self.pos))
+    }
+
+    /// Reads a preprocessing number (C17 6.4.8): the longest run of characters that can be part
+    /// of a number, so `1foo` is one token. It is then validated as an integer or floating
+    /// literal. An invalid one is an error token spanning the whole number, whose diagnostic
+    /// points at the invalid suffix
+    fn read_number(&mut self, start: usize) -> Token {
+        let end = self.pp_number_end();
+        let token = if self.src[start] == b'.' {
+            self.read_digits(10);
+            self.read_float_literal(start, end, 10)
+        } else {
+            self.read_number_literal(start, end)
+        };
+        self.pos = end;
+        token
     }
 
-    fn read_integer_literal(&mut self, first: char) -> Token {
-        let mut literal = String::new();
-        literal.push(first);
+    /// End of the preprocessing number whose first character was just consumed
+    fn pp_number_end(&self) -> usize {
+        let mut end = self.pos;
+        while let Some(&byte) = self.src.get(end) {
+            match byte {
+                b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'.' => end += 1,
+                // A sign only belongs to an exponent
+                b'+' | b'-' if matches!(self.src[end - 1], b'e' | b'E' | b'p' | b'P') => end += 1,
+                0x80.. => match self.char_at(end) {
+                    Some(ch) if unicode_ident::is_xid_continue(ch) => end += ch.len_utf8(),
+                    _ => break,
+                },
+                _ => break,
+            }
+        }
+        end
+    }
 
-        while let Some(&ch) = self.buffer.peek() {
-            if ch.is_ascii_digit() {
-                literal.push(self.buffer.next().unwrap());
+    /// Reads an integer literal ending at `end`, or a floating literal if a `.` or an exponent
+    /// follows the digits
+    fn read_number_literal(&mut self, start: usize, end: usize) -> Token {
+        let radix = match (self.src[start], self.peek_byte()) {
+            (b'0', Some(b'x' | b'X')) => 16,
+            (b'0', Some(b'b' | b'B')) => 2,
+            (b'0', _) => 8,
+            _ => 10,
+        };
+        let digits_start = if radix == 16 || radix == 2 {
+            self.pos += 1; // Consume the `x` or `b` of the prefix
+            self.pos
+        } else {
+            start
+        };
+
+        while let Some(byte) = self.peek_byte() {
+            if byte.is_ascii_digit() || (radix == 16 && byte.is_ascii_hexdigit()) {
+                self.pos += 1;
             } else {
                 break;
             }
         }
+        match (radix, self.peek_byte()) {
+            (8 | 10, Some(b'.' | b'e' | b'E')) => return self.read_float_literal(start, end, 10),
+            (16, Some(b'.' | b'p' | b'P')) => return self.read_float_literal(start, end, 16),
+            _ => {}
+        }
+        let literal = self.text(start, self.pos);
+        let digits = self.text(digits_start, self.pos);
+        let suffix = self.text(self.pos, end);
+        let span = Span::new(start, end);
+
+        if digits.is_empty() {
+            return Token::new(
+                error(format!("Invalid integer literal: '{}'", literal)),
+                span,
+            );
+        }
+        if let Some(digit) = digits.chars().find(|ch| !ch.is_digit(radix)) {
+            let base = if radix == 8 { "octal" } else { "binary" };
+            let message = format!(
+                "Invalid digit '{}' in {} literal '{}'",
+                digit, base, literal
+            );
+            return Token::new(error(message), span);
+        }
+        let Some((unsigned, longs)) = parse_integer_suffix(suffix) else {
+            let starts_like_identifier =
+                suffix.starts_with(|ch: char| unicode_ident::is_xid_start(ch) || ch == '_');
+            let reason = if starts_like_identifier && suffix.contains(|ch| !"uUlL".contains(ch)) {
+                "identifiers can't start with a digit"
+            } else {
+                "only 'u' and 'l' or 'll' are allowed, at most once each"
+            };
+            let message = format!(
+                "Invalid suffix '{}' on integer literal '{}': {}",
+                suffix, literal, reason
+            );
+            return Token::new(error_at(message, self.pos - start), span);
+        };
 
-        match literal.parse() {
-            Ok(num) => Token::IntegerLiteral(num),
-            Err(e) => Token::Error(format!(
-                "Invalid integer literal: '{}', with: {}",
-                literal, e
+        let value = u64::from_str_radix(digits, radix).ok();
+        let ty =
+            value.and_then(|value| IntegerType::for_literal(value, radix == 10, unsigned, longs));
+        let kind = match (value, ty) {
+            (Some(value), Some(ty)) => TokenKind::IntegerLiteral(value, ty),
+            _ => error(format!(
+                "Integer literal '{}{}' is too large for any integer type",
+                literal, suffix
             )),
+        };
+        Token::new(kind, span)
+    }
+
+    /// Reads the rest of a floating literal from `start` to `end`, whose leading digits are
+    /// already consumed. Decimal literals have an optional `e` exponent, hexadecimal ones a
+    /// mandatory `p` exponent
+    fn read_float_literal(&mut self, start: usize, end: usize, radix: u32) -> Token {
+        let span = Span::new(start, end);
+        if !self.text(start, self.pos).contains('.') && self.eat(b'.') {
+            self.read_digits(radix);
+        }
+
+        let mantissa_end = self.pos;
+        if radix == 16
+            && !self
+                .text(start + 2, self.pos)
+                .contains(|ch: char| ch.is_ascii_hexdigit())
+        {
+            let message = format!("Invalid floating literal: '{}'", self.text(start, self.pos));
+            return Token::new(error(message), span);
         }
+        let exponent_marker: &[u8] = if radix == 16 { b"pP" } else { b"eE" };
+        if self
+            .peek_byte()
+            .is_some_and(|byte| exponent_marker.contains(&byte))
+        {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek_byte() {
+                self.pos += 1;
+            }
+            let digits_start = self.pos;
+            self.read_digits(10);
+            if self.pos == digits_start {
+                let message = format!(
+                    "Exponent has no digits in floating literal '{}'",
+                    self.text(start, self.pos)
+                );
+                return Token::new(error(message), span);
+            }
+        } else if radix == 16 {
+            let message = format!(
+                "Hexadecimal floating literal '{}' requires an exponent",
+                self.text(start, self.pos)
+            );
+            return Token::new(error(message), span);
+        }
+        let exponent_end = self.pos;
+
+        let ty = match self.text(exponent_end, end) {
+            "" => FloatType::Double,
+            "f" | "F" => FloatType::Float,
+            "l" | "L" => FloatType::LongDouble,
+            suffix => {
+                let message = format!(
+                    "Invalid suffix '{}' on floating literal '{}': only 'f' or 'l' is allowed",
+                    suffix,
+                    self.text(start, exponent_end)
+                );
+                return Token::new(error_at(message, exponent_end - start), span);
+            }
+        };
+
+        let value = if radix == 16 {
+            let exponent = self.text(mantissa_end + 1, exponent_end);
+            parse_hex_float(self.text(start + 2, mantissa_end), exponent)
+        } else {
+            let literal = self.text(start, exponent_end);
+            literal.parse().expect("a valid decimal floating literal")
+        };
+        let overflows = match ty {
+            FloatType::Float => (value as f32).is_infinite(),
+            FloatType::Double | FloatType::LongDouble => value.is_infinite(),
+        };
+        let kind = if overflows {
+            error(format!(
+                "Floating literal '{}' is too large for its type",
+                self.text(start, end)
+            ))
+        } else {
+            TokenKind::FloatLiteral(value, ty)
+        };
+        Token::new(kind, span)
     }
 
-    /// Consumes whitespace
-    fn consume_whitespace(&mut self) {
-        while let Some(ch) = self.buffer.peek() {
-            if ch.is_whitespace() {
-                self.buffer.next();
-            } else {
-                break;
+    /// Consumes the digits in `radix` that follow
+    fn read_digits(&mut self, radix: u32) {
+        while self
+            .peek_byte()
+            .is_some_and(|byte| (byte as char).is_digit(radix))
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads the contents of a character constant or string literal up to the closing `quote`
+    /// into `scratch`, with escape sequences replaced. On error the rest of the literal is
+    /// still consumed
+    fn read_quoted(&mut self, quote: u8) -> Result<(), String> {
+        self.scratch.clear();
+        let mut error = None;
+
+        loop {
+            match self.peek_byte() {
+                None | Some(b'\n') => {
+                    return Err(format!("Missing terminating {} character", quote as char));
+                }
+                Some(byte) if byte == quote => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    if let Err(err) = self.read_escape() {
+                        error.get_or_insert(err);
+                    }
+                }
+                Some(byte) => {
+                    self.pos += 1;
+                    self.scratch.push(byte);
+                }
             }
         }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
 
-    /// Consumes single line comment
-    fn consume_comment(&mut self) {
-        while let Some(ch) = self.buffer.peek() {
-            if *ch == '\n' {
-                break;
+    /// Reads an escape sequence after its `\` and appends the bytes it stands for to `scratch`
+    fn read_escape(&mut self) -> Result<(), String> {
+        let Some(escape) = self.bump() else {
+            return Err("Unterminated escape sequence".to_string());
+        };
+
+        let byte = match escape {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'\\' | b'\'' | b'"' | b'?' => escape,
+            b'0'..=b'7' => {
+                let mut value = (escape - b'0') as u32;
+                for _ in 0..2 {
+                    match self.peek_byte() {
+                        Some(digit @ b'0'..=b'7') => {
+                            self.pos += 1;
+                            value = value * 8 + (digit - b'0') as u32;
+                        }
+                        _ => break,
+                    }
+                }
+                u8::try_from(value).map_err(|_| "Octal escape sequence out of range".to_string())?
+            }
+            b'x' => {
+                let mut value: u32 = 0;
+                let mut digits = 0;
+                while let Some(digit) = self
+                    .peek_byte()
+                    .and_then(|byte| (byte as char).to_digit(16))
+                {
+                    self.pos += 1;
+                    value = value.saturating_mul(16).saturating_add(digit);
+                    digits += 1;
+                }
+                if digits == 0 {
+                    return Err("\\x used with no following hex digits".to_string());
+                }
+                u8::try_from(value).map_err(|_| "Hex escape sequence out of range".to_string())?
             }
-            self.buffer.next();
+            b'u' | b'U' => {
+                let ch = self.read_universal_character(escape)?;
+                self.scratch
+                    .extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                return Ok(());
+            }
+            _ => {
+                self.pos -= 1;
+                let ch = self.bump_char();
+                return Err(format!("Unknown escape sequence '\\{}'", ch));
+            }
+        };
+
+        self.scratch.push(byte);
+        Ok(())
+    }
+
+    /// Reads the hex digits of a universal character name after its `\u` or `\U`
+    fn read_universal_character(&mut self, escape: u8) -> Result<char, String> {
+        let len = if escape == b'u' { 4 } else { 8 };
+        let mut value: u32 = 0;
+        for _ in 0..len {
+            match self
+                .peek_byte()
+                .and_then(|byte| (byte as char).to_digit(16))
+            {
+                Some(digit) => {
+                    self.pos += 1;
+                    value = value * 16 + digit;
+                }
+                None => {
+                    return Err(format!(
+                        "Incomplete universal character name \\{}",
+                        escape as char
+                    ));
+                }
+            }
+        }
+        char::from_u32(value)
+            .ok_or_else(|| format!("Invalid universal character name U+{:X}", value))
+    }
+
+    /// Source text from `start` to `end`, which must be valid UTF-8
+    fn text(&self, start: usize, end: usize) -> &'a str {
+        std::str::from_utf8(&self.src[start..end]).expect("a UTF-8 range of the source")
+    }
+
+    fn peek_byte(&self) -> Option<u8> {
+        self.src.get(self.pos).copied()
+    }
+
+    /// Looks `n` bytes past the next one without consuming anything
+    fn peek_byte_nth(&self, n: usize) -> Option<u8> {
+        self.src.get(self.pos + n).copied()
+    }
+
+    /// Consumes the next byte
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek_byte()?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    /// Consumes the next byte if it is `expected`
+    fn eat(&mut self, expected: u8) -> bool {
+        if self.peek_byte() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
         }
     }
 
+    /// Decodes the UTF-8 character at the current position. `None` at the end of the source or
+    /// on an invalid sequence
+    fn peek_char(&self) -> Option<char> {
+        self.char_at(self.pos)
+    }
+
+    /// Decodes the UTF-8 character at byte offset `pos`
+    fn char_at(&self, pos: usize) -> Option<char> {
+        let len = match *self.src.get(pos)? {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return None,
+        };
+        let bytes = self.src.get(pos..pos + len)?;
+        std::str::from_utf8(bytes).ok()?.chars().next()
+    }
+
+    /// Consumes the UTF-8 character at the current position, which must not be the end of the
+    /// source. An invalid byte is consumed on its own and decoded as U+FFFD
+    fn bump_char(&mut self) -> char {
+        match self.peek_char() {
+            Some(ch) => {
+                self.pos += ch.len_utf8();
+                ch
+            }
+            None => {
+                self.pos += 1;
+                char::REPLACEMENT_CHARACTER
+            }
+        }
+    }
+
+    /// Consumes whitespace. Only ASCII whitespace separates tokens in C
+    ///
+    /// A backslash before a newline splices the two lines (C17 5.1.1.2), which is only supported
+    /// between tokens. Returns whether a newline that isn't spliced was consumed
+    fn consume_whitespace(&mut self) -> bool {
+        let mut newline = false;
+        loop {
+            match self.peek_byte() {
+                Some(b'\n') => {
+                    newline = true;
+                    self.pos += 1;
+                }
+                Some(b' ' | b'\t' | b'\r' | 0x0b | 0x0c) => self.pos += 1,
+                Some(b'\\') => match (self.peek_byte_nth(1), self.peek_byte_nth(2)) {
+                    (Some(b'\n'), _) => self.pos += 2,
+                    (Some(b'\r'), Some(b'\n')) => self.pos += 3,
+                    _ => break,
+                },
+                _ => break,
+            }
+        }
+        newline
+    }
+
+    /// Consumes single line comment
+    fn consume_comment(&mut self) {
+        self.pos = match self.src[self.pos..].iter().position(|&byte| byte == b'\n') {
+            Some(len) => self.pos + len,
+            None => self.src.len(),
+        };
+    }
+
     /// Consumes multi line comment
     fn consume_multi_line_comment(&mut self) -> Result<(), String> {
-        while let Some(ch) = self.buffer.next() {
-            if ch == '*' {
-                if let Some('/') = self.buffer.peek() {
-                    self.buffer.next(); // Consume the '/' as the end
-                    return Ok(());
-                }
+        match self.src[self.pos..]
+            .windows(2)
+            .position(|pair| pair == b"*/")
+        {
+            Some(len) => {
+                self.pos += len + 2; // Consume the `*/` as the end
+                Ok(())
+            }
+            None => {
+                self.pos = self.src.len();
+                Err("Unterminated multi-line comment".to_string())
             }
         }
+    }
+}
+
+impl TokenSource for Lexer<'_> {
+    fn next_token(&mut self) -> Token {
+        Lexer::next_token(self)
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Token {
+        Lexer::peek_nth(self, n)
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        Lexer::checkpoint(self)
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        Lexer::restore(self, checkpoint)
+    }
+
+    /// The lexer can go back anywhere in its source, it keeps nothing for checkpoints
+    fn release(&mut self, _checkpoint: Checkpoint) {}
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if self.finished {
+            return None;
+        }
+
+        let token = self.next_token();
+        self.finished = token.kind == TokenKind::EOF;
+        Some(token)
+    }
+}
+
+/// Lexing errors are tokens, so that the parser can report them where they occur
+fn error(message: String) -> TokenKind {
+    error_at(message, 0)
+}
+
+/// A lexing error about the part of the token `offset` bytes from its start
+fn error_at(message: String, offset: usize) -> TokenKind {
+    TokenKind::Error(Interned::new(message), offset as u32)
+}
+
+/// Value of a character constant. A single character is a (signed) `char` converted to `int`,
+/// multi-character constants pack their bytes big-endian into an `int`, as gcc does
+fn character_value(bytes: &[u8]) -> i32 {
+    match bytes {
+        [byte] => *byte as i8 as i32,
+        bytes => bytes
+            .iter()
+            .fold(0u32, |value, byte| (value << 8) | *byte as u32) as i32,
+    }
+}
+
+/// Value of a hexadecimal floating literal from its hex digits (with an optional `.`) and
+/// its binary exponent
+fn parse_hex_float(mantissa: &str, exponent: &str) -> f64 {
+    let (int_digits, frac_digits) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    // Exact for up to 13 significant hex digits, rounded after that
+    let value = int_digits
+        .chars()
+        .chain(frac_digits.chars())
+        .fold(0.0, |value, ch| {
+            value * 16.0 + ch.to_digit(16).unwrap() as f64
+        });
+    let exponent: i32 = exponent.parse().unwrap_or(i32::MAX);
+    let exponent = exponent.saturating_sub(4 * frac_digits.len() as i32);
+    value * 2f64.powi(exponent)
+}
+
+/// Splits an integer suffix into whether it has a `u` and its number of `l`s. The `u` may come
+/// before or after the `l`s, which must have the same case
+fn parse_integer_suffix(suffix: &str) -> Option<(bool, usize)> {
+    let (unsigned, longs) = if let Some(rest) = suffix.strip_prefix(['u', 'U']) {
+        (true, rest)
+    } else if let Some(rest) = suffix.strip_suffix(['u', 'U']) {
+        (true, rest)
+    } else {
+        (false, suffix)
+    };
 
-        Err("Unterminated multi-line comment".to_string())
+    match longs {
+        "" => Some((unsigned, 0)),
+        "l" | "L" => Some((unsigned, 1)),
+        "ll" | "LL" => Some((unsigned, 2)),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Token;
-
     use super::*;
 
     #[test]
@@ -176,17 +917,23 @@ mod tests {
         let input = "int main(void) { return 2; }";
         let mut lexer = Lexer::new(input);
 
-        assert_eq!(lexer.next_token(), Token::Int);
-        assert_eq!(lexer.next_token(), Token::Identifier("main".to_string()));
-        assert_eq!(lexer.next_token(), Token::OpenParen);
-        assert_eq!(lexer.next_token(), Token::Void);
-        assert_eq!(lexer.next_token(), Token::CloseParen);
-        assert_eq!(lexer.next_token(), Token::OpenBrace);
-        assert_eq!(lexer.next_token(), Token::Return);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(2));
-        assert_eq!(lexer.next_token(), Token::Semicolon);
-        assert_eq!(lexer.next_token(), Token::CloseBrace);
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("main"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::Void);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::Return);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(2, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Semicolon);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
     }
 
     #[test]
@@ -194,17 +941,23 @@ mod tests {
         let input = "int \n main ( void ) { \t return 2 ; }";
         let mut lexer = Lexer::new(input);
 
-        assert_eq!(lexer.next_token(), Token::Int);
-        assert_eq!(lexer.next_token(), Token::Identifier("main".to_string()));
-        assert_eq!(lexer.next_token(), Token::OpenParen);
-        assert_eq!(lexer.next_token(), Token::Void);
-        assert_eq!(lexer.next_token(), Token::CloseParen);
-        assert_eq!(lexer.next_token(), Token::OpenBrace);
-        assert_eq!(lexer.next_token(), Token::Return);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(2));
-        assert_eq!(lexer.next_token(), Token::Semicolon);
-        assert_eq!(lexer.next_token(), Token::CloseBrace);
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("main"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::Void);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::Return);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(2, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Semicolon);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
     }
 
     #[test]
@@ -212,20 +965,26 @@ mod tests {
         let input = "int $ main";
         let mut lexer = Lexer::new(input);
 
-        assert_eq!(lexer.next_token(), Token::Int);
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
         assert_eq!(
             lexer.next_token(),
-            Token::Error("Unexpected character: '$'".to_string())
+            Token::new(
+                TokenKind::Error(Interned::new("Unexpected character: '$'"), 0),
+                Span::new(4, 5)
+            )
         );
-        assert_eq!(lexer.next_token(), Token::Identifier("main".to_string()));
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("main"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
     }
 
     #[test]
     fn test_lexer_empty_input() {
         let input = "";
         let mut lexer = Lexer::new(input);
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
     }
 
     #[test]
@@ -233,17 +992,23 @@ mod tests {
         let input = "// A comment \n int main ( void ) { \t return 2 ; }";
         let mut lexer = Lexer::new(input);
 
-        assert_eq!(lexer.next_token(), Token::Int);
-        assert_eq!(lexer.next_token(), Token::Identifier("main".to_string()));
-        assert_eq!(lexer.next_token(), Token::OpenParen);
-        assert_eq!(lexer.next_token(), Token::Void);
-        assert_eq!(lexer.next_token(), Token::CloseParen);
-        assert_eq!(lexer.next_token(), Token::OpenBrace);
-        assert_eq!(lexer.next_token(), Token::Return);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(2));
-        assert_eq!(lexer.next_token(), Token::Semicolon);
-        assert_eq!(lexer.next_token(), Token::CloseBrace);
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("main"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::Void);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::Return);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(2, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Semicolon);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
     }
 
     #[test]
@@ -251,19 +1016,28 @@ mod tests {
         let input = "int main(void) { return 2 / 5; }";
         let mut lexer = Lexer::new(input);
 
-        assert_eq!(lexer.next_token(), Token::Int);
-        assert_eq!(lexer.next_token(), Token::Identifier("main".to_string()));
-        assert_eq!(lexer.next_token(), Token::OpenParen);
-        assert_eq!(lexer.next_token(), Token::Void);
-        assert_eq!(lexer.next_token(), Token::CloseParen);
-        assert_eq!(lexer.next_token(), Token::OpenBrace);
-        assert_eq!(lexer.next_token(), Token::Return);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(2));
-        assert_eq!(lexer.next_token(), Token::Slash);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(5));
-        assert_eq!(lexer.next_token(), Token::Semicolon);
-        assert_eq!(lexer.next_token(), Token::CloseBrace);
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("main"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::Void);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::Return);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(2, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Slash);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(5, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Semicolon);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
     }
 
     #[test]
@@ -271,17 +1045,23 @@ mod tests {
         let input = "/* This a a comment. Bla Bla \n Bla fwaiooja koawf \n lkdalw */ int main(void) { return 2; }";
         let mut lexer = Lexer::new(input);
 
-        assert_eq!(lexer.next_token(), Token::Int);
-        assert_eq!(lexer.next_token(), Token::Identifier("main".to_string()));
-        assert_eq!(lexer.next_token(), Token::OpenParen);
-        assert_eq!(lexer.next_token(), Token::Void);
-        assert_eq!(lexer.next_token(), Token::CloseParen);
-        assert_eq!(lexer.next_token(), Token::OpenBrace);
-        assert_eq!(lexer.next_token(), Token::Return);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(2));
-        assert_eq!(lexer.next_token(), Token::Semicolon);
-        assert_eq!(lexer.next_token(), Token::CloseBrace);
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("main"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::Void);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseParen);
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::Return);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(2, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Semicolon);
+        assert_eq!(lexer.next_token().kind, TokenKind::CloseBrace);
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
     }
 
     #[test]
@@ -289,13 +1069,687 @@ mod tests {
         let input = "int x = 5 == 5;";
         let mut lexer = Lexer::new(input);
 
-        assert_eq!(lexer.next_token(), Token::Int);
-        assert_eq!(lexer.next_token(), Token::Identifier("x".to_string()));
-        assert_eq!(lexer.next_token(), Token::Assignment);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(5));
-        assert_eq!(lexer.next_token(), Token::Equal);
-        assert_eq!(lexer.next_token(), Token::IntegerLiteral(5));
-        assert_eq!(lexer.next_token(), Token::Semicolon);
-        assert_eq!(lexer.next_token(), Token::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("x"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Assignment);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(5, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Equal);
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::IntegerLiteral(5, IntegerType::Int)
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Semicolon);
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_lexer_token_spans() {
+        let input = "int main(void) {\n    return 2 == 22; // done\n}";
+        let spans: Vec<Span> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.span)
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 3),   // int
+                Span::new(4, 8),   // main
+                Span::new(8, 9),   // (
+                Span::new(9, 13),  // void
+                Span::new(13, 14), // )
+                Span::new(15, 16), // {
+                Span::new(21, 27), // return
+                Span::new(28, 29), // 2
+                Span::new(30, 32), // ==
+                Span::new(33, 35), // 22
+                Span::new(35, 36), // ;
+                Span::new(45, 46), // }
+                Span::new(46, 46), // EOF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_unterminated_comment_span() {
+        let input = "int /* never closed";
+        let mut lexer = Lexer::new(input);
+
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+        assert_eq!(
+            lexer.next_token(),
+            Token::new(
+                TokenKind::Error(Interned::new("Unterminated multi-line comment"), 0),
+                Span::new(4, 19)
+            )
+        );
+    }
+
+    #[test]
+    fn test_lexer_punctuators_longest_match() {
+        let input = "a+++b ->x <<= >>= >> <= != && || ... .. %:%: <: :> <% %> ?:";
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier(Interned::new("a")),
+                TokenKind::Increment,
+                TokenKind::Plus,
+                TokenKind::Identifier(Interned::new("b")),
+                TokenKind::Arrow,
+                TokenKind::Identifier(Interned::new("x")),
+                TokenKind::ShiftLeftAssignment,
+                TokenKind::ShiftRightAssignment,
+                TokenKind::ShiftRight,
+                TokenKind::LessEqual,
+                TokenKind::NotEqual,
+                TokenKind::LogicalAnd,
+                TokenKind::LogicalOr,
+                TokenKind::Ellipsis,
+                TokenKind::Dot,
+                TokenKind::Dot,
+                TokenKind::HashHash,
+                TokenKind::OpenBracket,
+                TokenKind::CloseBracket,
+                TokenKind::OpenBrace,
+                TokenKind::CloseBrace,
+                TokenKind::Question,
+                TokenKind::Colon,
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_compound_assignments() {
+        let input = "+= -= *= /= %= &= |= ^= = ==";
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::PlusAssignment,
+                TokenKind::MinusAssignment,
+                TokenKind::AsterixAssignment,
+                TokenKind::SlashAssignment,
+                TokenKind::PercentAssignment,
+                TokenKind::AmpersandAssignment,
+                TokenKind::PipeAssignment,
+                TokenKind::CaretAssignment,
+                TokenKind::Assignment,
+                TokenKind::Equal,
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_integer_literal_bases() {
+        let input = "0x1F 017 0b101 0 0XfF";
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::IntegerLiteral(31, IntegerType::Int),
+                TokenKind::IntegerLiteral(15, IntegerType::Int),
+                TokenKind::IntegerLiteral(5, IntegerType::Int),
+                TokenKind::IntegerLiteral(0, IntegerType::Int),
+                TokenKind::IntegerLiteral(255, IntegerType::Int),
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_integer_literal_types() {
+        let input = "10u 42UL 123LL 7lu 2147483648 0x80000000 4294967296u 0xFFFFFFFFFFFFFFFF 9llU";
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::IntegerLiteral(10, IntegerType::UnsignedInt),
+                TokenKind::IntegerLiteral(42, IntegerType::UnsignedLong),
+                TokenKind::IntegerLiteral(123, IntegerType::LongLong),
+                TokenKind::IntegerLiteral(7, IntegerType::UnsignedLong),
+                TokenKind::IntegerLiteral(2147483648, IntegerType::Long),
+                TokenKind::IntegerLiteral(0x80000000, IntegerType::UnsignedInt),
+                TokenKind::IntegerLiteral(4294967296, IntegerType::UnsignedLong),
+                TokenKind::IntegerLiteral(u64::MAX, IntegerType::UnsignedLong),
+                TokenKind::IntegerLiteral(9, IntegerType::UnsignedLongLong),
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_invalid_integer_literals() {
+        let errors: Vec<TokenKind> = [
+            "09",
+            "0b2",
+            "0x",
+            "1lul",
+            "18446744073709551616",
+            "9223372036854775808",
+        ]
+        .into_iter()
+        .map(|input| Lexer::new(input).next_token().kind)
+        .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                TokenKind::Error(Interned::new("Invalid digit '9' in octal literal '09'"), 0),
+                TokenKind::Error(
+                    Interned::new("Invalid digit '2' in binary literal '0b2'"),
+                    0
+                ),
+                TokenKind::Error(Interned::new("Invalid integer literal: '0x'"), 0),
+                TokenKind::Error(
+                    Interned::new(
+                        "Invalid suffix 'lul' on integer literal '1': only 'u' and 'l' or 'll' \
+                         are allowed, at most once each"
+                    ),
+                    1
+                ),
+                TokenKind::Error(
+                    Interned::new(
+                        "Integer literal '18446744073709551616' is too large for any integer type"
+                    ),
+                    0
+                ),
+                TokenKind::Error(
+                    Interned::new(
+                        "Integer literal '9223372036854775808' is too large for any integer type"
+                    ),
+                    0
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_character_literals() {
+        let input = r"'a' '\n' '\x41' '\101' '\0' '\'' 'ab' '\xff' 'é'";
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::CharacterLiteral(97),
+                TokenKind::CharacterLiteral(10),
+                TokenKind::CharacterLiteral(65),
+                TokenKind::CharacterLiteral(65),
+                TokenKind::CharacterLiteral(0),
+                TokenKind::CharacterLiteral(39),
+                TokenKind::CharacterLiteral(0x6162),
+                TokenKind::CharacterLiteral(-1),
+                TokenKind::CharacterLiteral(0xc3a9),
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_string_literals() {
+        let input = r#""hello\tworld\n" "" "\"q\" \\ \x7f\1234 \u00e9""#;
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::StringLiteral(Interned::new(b"hello\tworld\n")),
+                TokenKind::StringLiteral(Interned::new(b"")),
+                TokenKind::StringLiteral(Interned::new(b"\"q\" \\ \x7f\x534 \xc3\xa9")),
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_invalid_quoted_literals() {
+        let mut lexer = Lexer::new("'' \"abc\n x");
+        assert_eq!(
+            lexer.next_token(),
+            Token::new(
+                TokenKind::Error(Interned::new("Empty character constant"), 0),
+                Span::new(0, 2)
+            )
+        );
+        assert_eq!(
+            lexer.next_token(),
+            Token::new(
+                TokenKind::Error(Interned::new("Missing terminating \" character"), 0),
+                Span::new(3, 7)
+            )
+        );
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("x"))
+        );
+
+        let errors = [
+            (r"'\q'", "Unknown escape sequence '\\q'"),
+            (r#""\x""#, "\\x used with no following hex digits"),
+            (r"'\x100'", "Hex escape sequence out of range"),
+            (r"'\777'", "Octal escape sequence out of range"),
+            (r#""\u12""#, "Incomplete universal character name \\u"),
+        ];
+        for (input, message) in errors {
+            let mut lexer = Lexer::new(input);
+            assert_eq!(
+                lexer.next_token(),
+                Token::new(
+                    TokenKind::Error(Interned::new(message), 0),
+                    Span::new(0, input.len())
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_lexer_float_literals() {
+        let input = "1.5 2e10 .5 3. 1e-3 09.5 0x1p-3 0x1.8P+1 0x.8p0 2.5f 1E2F 1.0L 4.e1";
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .lex_all()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::FloatLiteral(1.5, FloatType::Double),
+                TokenKind::FloatLiteral(2e10, FloatType::Double),
+                TokenKind::FloatLiteral(0.5, FloatType::Double),
+                TokenKind::FloatLiteral(3.0, FloatType::Double),
+                TokenKind::FloatLiteral(1e-3, FloatType::Double),
+                TokenKind::FloatLiteral(9.5, FloatType::Double),
+                TokenKind::FloatLiteral(0.125, FloatType::Double),
+                TokenKind::FloatLiteral(3.0, FloatType::Double),
+                TokenKind::FloatLiteral(0.5, FloatType::Double),
+                TokenKind::FloatLiteral(2.5, FloatType::Float),
+                TokenKind::FloatLiteral(100.0, FloatType::Float),
+                TokenKind::FloatLiteral(1.0, FloatType::LongDouble),
+                TokenKind::FloatLiteral(40.0, FloatType::Double),
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_invalid_float_literals() {
+        let errors = [
+            ("1e", "Exponent has no digits in floating literal '1e'"),
+            (
+                "1.5e+",
+                "Exponent has no digits in floating literal '1.5e+'",
+            ),
+            (
+                "0x1.8",
+                "Hexadecimal floating literal '0x1.8' requires an exponent",
+            ),
+            ("0x.p1", "Invalid floating literal: '0x.'"),
+            (
+                "1e999",
+                "Floating literal '1e999' is too large for its type",
+            ),
+            (
+                "1e39f",
+                "Floating literal '1e39f' is too large for its type",
+            ),
+        ];
+        for (input, message) in errors {
+            let mut lexer = Lexer::new(input);
+            assert_eq!(
+                lexer.next_token().kind,
+                TokenKind::Error(Interned::new(message), 0),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_lexer_non_ascii_source() {
+        let input = "int café = 1; /* ü */ \"π\" \u{a0}";
+        let tokens = Lexer::new(input).lex_all();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|token| token.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Int,
+                TokenKind::Identifier(Interned::new("café")),
+                TokenKind::Assignment,
+                TokenKind::IntegerLiteral(1, IntegerType::Int),
+                TokenKind::Semicolon,
+                TokenKind::StringLiteral(Interned::new("π")),
+                TokenKind::Error(Interned::new("Unexpected character: '\u{a0}'"), 0),
+                TokenKind::EOF,
+            ]
+        );
+        // Spans are byte offsets
+        assert_eq!(tokens[1].span, Span::new(4, 9));
+    }
+
+    #[test]
+    fn test_lexer_invalid_utf8() {
+        let mut lexer = Lexer::new(b"x \xff \"\xfe\"");
+
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::Identifier(Interned::new("x"))
+        );
+        assert_eq!(
+            lexer.next_token(),
+            Token::new(
+                TokenKind::Error(Interned::new("Unexpected character: '\u{fffd}'"), 0),
+                Span::new(2, 3)
+            )
+        );
+        // String literals are bytes, they don't have to be UTF-8
+        assert_eq!(
+            lexer.next_token().kind,
+            TokenKind::StringLiteral(Interned::new(b"\xfe"))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_lexer_iterator_ends_after_eof() {
+        let kinds: Vec<TokenKind> = Lexer::new("x;").map(|token| token.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier(Interned::new("x")),
+                TokenKind::Semicolon,
+                TokenKind::EOF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_peek_nth() {
+        let mut lexer = Lexer::new("(int) x");
+
+        assert_eq!(lexer.peek_nth(2).kind, TokenKind::CloseParen);
+        assert_eq!(lexer.peek_nth(0).kind, TokenKind::OpenParen);
+        assert_eq!(lexer.peek_nth(5).kind, TokenKind::EOF);
+        assert_eq!(lexer.next_token().kind, TokenKind::OpenParen);
+        assert_eq!(lexer.peek_nth(0).kind, TokenKind::Int);
+        assert_eq!(
+            lexer.peek_nth(2),
+            Token::new(TokenKind::Identifier(Interned::new("x")), Span::new(6, 7))
+        );
+        assert_eq!(lexer.next_token().kind, TokenKind::Int);
+    }
+
+    #[test]
+    fn test_lexer_checkpoint_restore() {
+        let mut lexer = Lexer::new("a /* c */ b c");
+        lexer.next_token();
+        lexer.peek_nth(1);
+
+        let checkpoint = lexer.checkpoint();
+        let rest: Vec<Token> = lexer.by_ref().collect();
+        assert_eq!(rest.len(), 3);
+        assert_eq!(lexer.next(), None);
+
+        lexer.restore(checkpoint);
+        assert_eq!(lexer.by_ref().collect::<Vec<_>>(), rest);
+    }
+
+    #[test]
+    fn test_lexer_pp_numbers() {
+        let mut lexer = Lexer::new("return 1foo;");
+        lexer.next_token();
+        let token = lexer.next_token();
+        assert_eq!(
+            token,
+            Token::new(
+                TokenKind::Error(
+                    Interned::new(
+                        "Invalid suffix 'foo' on integer literal '1': identifiers can't start \
+                         with a digit"
+                    ),
+                    1
+                ),
+                Span::new(7, 11)
+            )
+        );
+        assert_eq!(token.error_span(), Span::new(8, 11));
+        assert_eq!(lexer.next_token().kind, TokenKind::Semicolon);
+
+        let errors = [
+            (
+                "0x1e+1",
+                "Invalid suffix '+1' on integer literal '0x1e'",
+                4..6,
+            ),
+            (
+                "1ufoo",
+                "Invalid suffix 'ufoo' on integer literal '1'",
+                1..5,
+            ),
+            (
+                "1.5ff",
+                "Invalid suffix 'ff' on floating literal '1.5'",
+                3..5,
+            ),
+            ("1..2", "Invalid suffix '.2' on floating literal '1.'", 2..4),
+            (
+                "1.2.3",
+                "Invalid suffix '.3' on floating literal '1.2'",
+                3..5,
+            ),
+        ];
+        for (input, message, suffix) in errors {
+            let token = Lexer::new(input).next_token();
+            let TokenKind::Error(error, _) = token.kind else {
+                panic!("expected an error for {}", input);
+            };
+            assert!(error.as_str().starts_with(message), "{}", error);
+            // The token is the whole number, only the diagnostic points at the suffix
+            assert_eq!(token.span, Span::new(0, input.len()), "{}", input);
+            assert_eq!(
+                token.error_span(),
+                Span::new(suffix.start, suffix.end),
+                "{}",
+                input
+            );
+        }
+
+        // A sign is only part of a number after an exponent
+        let kinds: Vec<TokenKind> = Lexer::new("1+2 1e+2").map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::IntegerLiteral(1, IntegerType::Int),
+                TokenKind::Plus,
+                TokenKind::IntegerLiteral(2, IntegerType::Int),
+                TokenKind::FloatLiteral(100.0, FloatType::Double),
+                TokenKind::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_c17_keywords() {
+        let input = "auto break case char const continue default do double else enum extern float \
+                     for goto if inline int long register restrict return short signed sizeof \
+                     static struct switch typedef union unsigned void volatile while _Alignas \
+                     _Alignof _Atomic _Bool _Complex _Generic _Imaginary _Noreturn \
+                     _Static_assert _Thread_local";
+        let tokens = Lexer::new(input).lex_all();
+
+        assert_eq!(tokens.len(), 45);
+        for (token, name) in tokens.iter().zip(input.split_whitespace()) {
+            assert_eq!(token.kind.keyword(), Some(name));
+        }
+        // Keywords are case sensitive and must be the whole identifier
+        assert_eq!(
+            Lexer::new("_bool").next_token().kind,
+            TokenKind::Identifier(Interned::new("_bool"))
+        );
+        assert_eq!(
+            Lexer::new("sizeofx").next_token().kind,
+            TokenKind::Identifier(Interned::new("sizeofx"))
+        );
+    }
+
+    #[test]
+    fn test_lexer_unicode_identifiers() {
+        let input = r"café caf\u00e9 πx x\U0001F600 a·b ·";
+        let tokens = Lexer::new(input).lex_all();
+
+        // A universal character name and the character it names spell the same identifier
+        assert_eq!(tokens[0].kind, TokenKind::Identifier(Interned::new("café")));
+        assert_eq!(tokens[1].kind, TokenKind::Identifier(Interned::new("café")));
+        assert_eq!(tokens[1].span, Span::new(6, 15));
+        assert_eq!(tokens[2].kind, TokenKind::Identifier(Interned::new("πx")));
+        // An emoji is not XID_Continue
+        assert_eq!(
+            tokens[3],
+            Token::new(
+                error_at(
+                    r"Universal character name '\U0001F600' is not valid in an identifier"
+                        .to_string(),
+                    1
+                ),
+                Span::new(20, 31)
+            )
+        );
+        assert_eq!(tokens[3].error_span(), Span::new(21, 31));
+        // U+00B7 can continue an identifier, but not start one
+        assert_eq!(tokens[4].kind, TokenKind::Identifier(Interned::new("a·b")));
+        assert_eq!(
+            tokens[5].kind,
+            error("Unexpected character: '·'".to_string())
+        );
+
+        // Only `$`, `@` and `` ` `` may be named below U+00A0, and they aren't letters
+        assert_eq!(
+            Lexer::new(r"\u0041").next_token().kind,
+            error(r"Universal character name '\u0041' is not valid in an identifier".to_string())
+        );
+    }
+
+    #[test]
+    fn test_lexer_c89_ascii_identifiers() {
+        let input = r"ok café caf\U000000e9";
+        let tokens = Lexer::new(input).with_standard(Standard::C89).lex_all();
+
+        assert_eq!(tokens[0].kind, TokenKind::Identifier(Interned::new("ok")));
+        // The token is the whole identifier, the error points at the offending character
+        assert_eq!(
+            tokens[1],
+            Token::new(
+                error_at(
+                    "Character 'é' is not allowed in identifiers before C99".to_string(),
+                    3
+                ),
+                Span::new(3, 8)
+            )
+        );
+        assert_eq!(tokens[1].error_span(), Span::new(6, 8));
+        assert_eq!(
+            tokens[2],
+            Token::new(
+                error_at(
+                    r"Universal character name '\U000000e9' is not allowed in identifiers before C99"
+                        .to_string(),
+                    3
+                ),
+                Span::new(9, 22)
+            )
+        );
+        assert_eq!(tokens[2].error_span(), Span::new(12, 22));
+        assert_eq!(tokens[3].kind, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_lexer_spacing() {
+        let mut lexer = Lexer::new("#define X(a)\\\n (a)/**/+ /* \n */ y");
+        let spacings: Vec<(TokenKind, Spacing)> =
+            std::iter::repeat_with(|| lexer.next_token_spaced())
+                .take(11)
+                .map(|(token, spacing)| (token.kind, spacing))
+                .collect();
+        let spacing = |line_start, leading_space| Spacing {
+            line_start,
+            leading_space,
+        };
+
+        assert_eq!(
+            spacings,
+            vec![
+                (TokenKind::Hash, spacing(true, false)),
+                (
+                    TokenKind::Identifier(Interned::new("define")),
+                    spacing(false, false)
+                ),
+                (
+                    TokenKind::Identifier(Interned::new("X")),
+                    spacing(false, true)
+                ),
+                (TokenKind::OpenParen, spacing(false, false)),
+                (
+                    TokenKind::Identifier(Interned::new("a")),
+                    spacing(false, false)
+                ),
+                (TokenKind::CloseParen, spacing(false, false)),
+                // A spliced line is not a new line
+                (TokenKind::OpenParen, spacing(false, true)),
+                (
+                    TokenKind::Identifier(Interned::new("a")),
+                    spacing(false, false)
+                ),
+                (TokenKind::CloseParen, spacing(false, false)),
+                // A comment counts as a space
+                (TokenKind::Plus, spacing(false, true)),
+                // A newline in a comment doesn't start a line
+                (
+                    TokenKind::Identifier(Interned::new("y")),
+                    spacing(false, true)
+                ),
+            ]
+        );
     }
 }
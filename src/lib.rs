@@ -1,6 +1,15 @@
+pub mod codegen;
+pub mod driver;
+pub mod dump;
+pub mod intern;
 pub mod lexer;
 mod token;
-pub use token::Token;
+pub use token::{Checkpoint, FloatType, IntegerType, Token, TokenKind, TokenSource};
 #[allow(dead_code)]
 mod parser;
-pub use parser::parse;
+pub mod preprocessor;
+pub use parser::{parse, parse_tokens};
+pub mod semantic;
+pub mod span;
+pub mod standard;
+pub mod tacky;
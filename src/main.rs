@@ -1,7 +1,25 @@
-use std::{fs, path::PathBuf, process::ExitCode};
+use std::{
+    env,
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-use c_compiler::{Token, lexer::Lexer, parse};
-use clap::Parser;
+use c_compiler::{
+    TokenKind, codegen,
+    codegen::asm,
+    driver::{self, TempDir},
+    dump::{TokenJson, TokenTable},
+    lexer::Lexer,
+    parse_tokens,
+    preprocessor::{CommandLineMacro, IncludePaths, Preprocessor},
+    semantic,
+    span::{SourceMap, Span},
+    standard::Standard,
+    tacky,
+};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -9,52 +27,364 @@ struct Args {
     /// Run the lexer, but stop before parsing
     #[arg(long)]
     lex: bool,
+    /// How `--lex` prints the tokens
+    #[arg(long, value_enum, default_value_t = TokenFormat::Table)]
+    format: TokenFormat,
     /// Run the lexer and parser, but stop before assembly generation
     #[arg(long)]
     parse: bool,
+    /// Run the lexer, parser and semantic analysis, but stop before TACKY generation
+    #[arg(long)]
+    validate: bool,
+    /// Run the lexer, parser and TACKY generation, but stop before assembly generation
+    #[arg(long)]
+    tacky: bool,
     /// Perform lexing, parsing, and assembly generation, but stop before code emission
     #[arg(long)]
     codegen: bool,
 
+    /// Preprocess only, printing the result (or writing it to `-o`)
+    #[arg(short = 'E')]
+    e: bool,
     /// Emit assembly, but not assemble or link it
     #[arg(short = 'S')]
     s: bool,
+    /// Compile and assemble, but do not link
+    #[arg(short = 'c')]
+    c: bool,
+    /// Place the output into this file
+    #[arg(short = 'o')]
+    output: Option<PathBuf>,
+    /// Keep the intermediate `.s` files next to the sources, instead of in a temporary directory
+    #[arg(long = "save-temps")]
+    save_temps: bool,
+    /// Language standard: c89, c99, c11 or c17 (the GNU dialect names are accepted too)
+    #[arg(long = "std", default_value_t = Standard::C17)]
+    std: Standard,
+    /// Search this directory for headers, before the system directories
+    #[arg(short = 'I', value_name = "DIR")]
+    include_dirs: Vec<PathBuf>,
+    /// Search this directory for headers included with quotes only, before the `-I` directories
+    #[arg(long = "iquote", value_name = "DIR")]
+    quote_dirs: Vec<PathBuf>,
+    /// Search this directory for system headers, before the default ones
+    #[arg(long = "isystem", value_name = "DIR")]
+    system_dirs: Vec<PathBuf>,
+    /// Define a macro, as 1 if no value is given
+    #[arg(short = 'D', value_name = "NAME[=VALUE]")]
+    defines: Vec<String>,
+    /// Undefine a macro, predefined or from an earlier `-D`
+    #[arg(short = 'U', value_name = "NAME")]
+    undefines: Vec<String>,
+    /// `-D` and `-U` in the order they were given, which clap doesn't keep across options
+    #[arg(skip)]
+    command_line_macros: Vec<CommandLineMacro>,
 
-    src_path: PathBuf,
+    #[arg(required = true)]
+    src_paths: Vec<PathBuf>,
+}
+
+/// Output of `--lex`
+#[derive(Clone, Copy, ValueEnum)]
+enum TokenFormat {
+    /// One token per line: span, kind and text
+    Table,
+    /// A JSON array of tokens, for tools
+    Json,
+}
+
+impl Args {
+    /// Whether one of the stage flags stops compilation before any file is written
+    fn stops_early(&self) -> bool {
+        self.lex || self.e || self.parse || self.validate || self.tacky || self.codegen
+    }
+}
+
+/// gcc spells some long options with a single dash (e.g. `-save-temps`, `-std=c99`), which clap
+/// can't parse
+fn normalize_args(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    const SINGLE_DASH_LONG: &[&str] = &["-save-temps", "-iquote", "-isystem"];
+
+    args.map(|arg| match arg.to_str() {
+        Some(s) if SINGLE_DASH_LONG.contains(&s) || s.starts_with("-std=") => {
+            format!("-{}", s).into()
+        }
+        _ => arg,
+    })
+    .collect()
+}
+
+/// `-D` and `-U` options sorted by their position on the command line
+fn command_line_macros(matches: &ArgMatches) -> Vec<CommandLineMacro> {
+    let options = |id: &str, option: fn(String) -> CommandLineMacro| {
+        let values = matches.get_many::<String>(id).into_iter().flatten();
+        let indices = matches.indices_of(id).into_iter().flatten();
+        indices
+            .zip(values)
+            .map(move |(index, value)| (index, option(value.clone())))
+    };
+    let mut macros: Vec<_> = options("defines", CommandLineMacro::Define)
+        .chain(options("undefines", CommandLineMacro::Undefine))
+        .collect();
+    macros.sort_by_key(|(index, _)| *index);
+    macros.into_iter().map(|(_, option)| option).collect()
 }
 
 fn main() -> Result<(), ExitCode> {
-    let args = Args::parse();
-    let src = fs::read_to_string(args.src_path).expect("Failed to read the source file");
+    let matches = Args::command().get_matches_from(normalize_args(env::args_os()));
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    args.command_line_macros = command_line_macros(&matches);
+
+    if args.output.is_some() && args.src_paths.len() > 1 && (args.s || args.c || args.e) {
+        eprintln!("ERROR: Cannot specify '-o' with '-c', '-S' or '-E' with multiple files");
+        return Err(ExitCode::FAILURE);
+    }
+
+    run(&args)
+}
+
+/// Compiles every input and assembles or links the results
+fn run(args: &Args) -> Result<(), ExitCode> {
+    // Files handed to the system assembler/linker
+    let mut link_inputs = Vec::new();
+    // Holds the intermediate files unless `-save-temps` is given, created on first use
+    let mut temp_dir = None;
+
+    for (index, src_path) in args.src_paths.iter().enumerate() {
+        match input_kind(args, src_path) {
+            InputKind::C => {}
+            InputKind::Assembly if args.c => {
+                driver::assemble(src_path, &object_path(args, src_path)).map_err(report)?;
+                continue;
+            }
+            InputKind::Object if args.c => {
+                return Err(report(format_args!(
+                    "ERROR: '{}' is not a C or assembly file, it can't be compiled with '-c'",
+                    src_path.display()
+                )));
+            }
+            // Assembly and object files go straight to the linker
+            InputKind::Assembly | InputKind::Object => {
+                link_inputs.push(src_path.clone());
+                continue;
+            }
+        }
+
+        let Some(assembly) = compile(args, src_path)? else {
+            continue;
+        };
+
+        let asm_path = match &args.output {
+            Some(output) if args.s => output.clone(),
+            _ if args.s || args.save_temps => src_path.with_extension("s"),
+            // Numbered so that inputs with the same name in different directories don't clash
+            _ => intermediate_path(&mut temp_dir, index, src_path)?,
+        };
+        fs::write(&asm_path, assembly.to_string()).map_err(|e| {
+            report(format_args!(
+                "ERROR: Cannot write '{}': {}",
+                asm_path.display(),
+                e
+            ))
+        })?;
+        if args.s {
+            continue;
+        }
+
+        if args.c {
+            driver::assemble(&asm_path, &object_path(args, src_path)).map_err(report)?;
+        } else {
+            link_inputs.push(asm_path);
+        }
+    }
+
+    if args.stops_early() || args.s || args.c {
+        return Ok(());
+    }
+
+    let executable = match &args.output {
+        Some(output) => output.clone(),
+        None => args.src_paths[0].with_extension(""),
+    };
+    driver::link(&link_inputs, &executable).map_err(report)
+}
+
+/// Where the assembly of the `index`th input goes in the private temporary directory
+fn intermediate_path(
+    temp_dir: &mut Option<TempDir>,
+    index: usize,
+    src_path: &Path,
+) -> Result<PathBuf, ExitCode> {
+    let dir = match temp_dir {
+        Some(dir) => dir,
+        None => temp_dir.insert(TempDir::new().map_err(|e| {
+            report(format_args!(
+                "ERROR: Cannot create a temporary directory: {}",
+                e
+            ))
+        })?),
+    };
+    let stem = src_path.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(dir.path().join(format!("{}-{}.s", index, stem)))
+}
+
+/// Compiles one source file. Returns `None` if a stage flag stopped compilation early
+fn compile(args: &Args, path: &Path) -> Result<Option<asm::Program>, ExitCode> {
+    let src = fs::read_to_string(path).map_err(|e| {
+        report(format_args!(
+            "ERROR: Cannot read '{}': {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let mut sources = SourceMap::new();
+    let main = sources.add_file(path.to_path_buf(), src);
 
     if args.lex {
-        let lexer = Lexer::new(&src);
-        let tokens = lexer.lex_all();
+        let file = sources.file(main);
+        let tokens = Lexer::new(file.src()).with_standard(args.std).lex_all();
         let mut has_lexing_errors = false;
         for token in &tokens {
-            if let Token::Error(msg) = token {
-                eprintln!("Lexing Error: {}", msg);
+            if let TokenKind::Error(msg, _) = &token.kind {
+                report_at(
+                    &sources,
+                    token.error_span(),
+                    &format_args!("Lexing Error: {}", msg),
+                );
                 has_lexing_errors = true;
             }
         }
 
-        // Always print the tokens for debugging, regardless of errors
-        println!("Tokens: {:?}", tokens);
+        // Always print the tokens, errors are listed among them
+        match args.format {
+            TokenFormat::Table => print!("{}", TokenTable::new(file.lines(), &tokens)),
+            TokenFormat::Json => print!("{}", TokenJson::new(file.lines(), &tokens)),
+        }
 
         if has_lexing_errors {
             // Return a non-zero exit code to signal failure
             return Err(ExitCode::FAILURE);
         }
-    } else if args.parse {
-        let program = parse(&src);
-        match program {
-            Ok(a) => println!("{:?}", a),
-            Err(e) => {
-                eprintln!("{}", e);
-                return Err(ExitCode::FAILURE);
+        return Ok(None);
+    }
+
+    let include_paths = IncludePaths {
+        quote: args.quote_dirs.clone(),
+        user: args.include_dirs.clone(),
+        system: args.system_dirs.clone(),
+    };
+    let mut preprocessor = Preprocessor::new(&mut sources, main, args.std)
+        .with_include_paths(include_paths.with_default_system_dirs())
+        .with_command_line_macros(&args.command_line_macros);
+    if args.e {
+        let text = preprocessor.preprocessed_text();
+        let errors = preprocessor.into_errors();
+        for e in &errors {
+            report_at(&sources, e.span(), e);
+        }
+        match &args.output {
+            Some(output) => fs::write(output, text).map_err(|e| {
+                report(format_args!(
+                    "ERROR: Cannot write '{}': {}",
+                    output.display(),
+                    e
+                ))
+            })?,
+            None => print!("{}", text),
+        }
+        return if errors.iter().all(|e| e.is_warning()) {
+            Ok(None)
+        } else {
+            Err(ExitCode::FAILURE)
+        };
+    }
+
+    let parsed = parse_tokens(&mut preprocessor);
+    let preprocessor_errors = preprocessor.into_errors();
+    for e in &preprocessor_errors {
+        report_at(&sources, e.span(), e);
+    }
+    let mut program = match parsed {
+        Ok(program) if preprocessor_errors.iter().all(|e| e.is_warning()) => program,
+        Ok(_) => return Err(ExitCode::FAILURE),
+        Err(errors) => {
+            for e in &errors {
+                report_at(&sources, e.span(), e);
             }
+            return Err(ExitCode::FAILURE);
         }
+    };
+    if args.parse {
+        println!("{:?}", program);
+        return Ok(None);
+    }
+
+    let mut symbols = semantic::analyze(&mut program).map_err(|errors| {
+        for e in &errors {
+            report_at(&sources, e.span(), e);
+        }
+        ExitCode::FAILURE
+    })?;
+    if args.validate {
+        println!("{:?}", program);
+        return Ok(None);
+    }
+
+    let ir = tacky::generate(&program, &mut symbols);
+    if args.tacky {
+        print!("{}", ir);
+        return Ok(None);
+    }
+
+    let assembly = codegen::generate(&ir, &symbols);
+    if args.codegen {
+        println!("{:?}", assembly);
+        return Ok(None);
+    }
+
+    Ok(Some(assembly))
+}
+
+/// Prints a compilation error at its location and turns it into a failing exit code
+fn report_at(sources: &SourceMap, span: Span, e: &dyn fmt::Display) -> ExitCode {
+    eprintln!("{}: {}", sources.location(span.start), e);
+    ExitCode::FAILURE
+}
+
+/// Prints a compilation error and turns it into a failing exit code
+fn report(e: impl fmt::Display) -> ExitCode {
+    eprintln!("{}", e);
+    ExitCode::FAILURE
+}
+
+/// What the driver does with an input file
+#[derive(Clone, Copy)]
+enum InputKind {
+    /// Compiled by us
+    C,
+    /// Assembled by the system assembler
+    Assembly,
+    /// Handed to the linker as is
+    Object,
+}
+
+/// Sources are `.c` or preprocessed `.i` files. The stage flags compile any input as C, as they
+/// only make sense for sources
+fn input_kind(args: &Args, path: &Path) -> InputKind {
+    if args.stops_early() || args.s {
+        return InputKind::C;
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("c" | "i") => InputKind::C,
+        Some("s" | "S") => InputKind::Assembly,
+        _ => InputKind::Object,
     }
+}
 
-    Ok(())
+/// Where `-c` puts the object file of `src_path`
+fn object_path(args: &Args, src_path: &Path) -> PathBuf {
+    match &args.output {
+        Some(output) => output.clone(),
+        None => src_path.with_extension("o"),
+    }
 }
@@ -1,4 +1,8 @@
-use crate::{Token, parser::error::ParserError};
+use std::fmt;
+
+use crate::{
+    FloatType, IntegerType, Token, parser::error::ParserError, span::Span, token::TokenKind,
+};
 
 /// The top node of every AST. Represents each c file
 #[derive(Debug, PartialEq)]
@@ -7,37 +11,113 @@ pub struct TranslationUnit {
 }
 
 /// C data types
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum CType {
     Int,
+    Char,
+    Float,
+    Double,
     Void,
+    Pointer(Box<CType>),
 }
 
 impl TryFrom<&Token> for CType {
     type Error = ParserError;
 
     fn try_from(value: &Token) -> Result<Self, Self::Error> {
-        match value {
-            Token::Int => Ok(CType::Int),
-            Token::Void => Ok(CType::Void),
-            _ => Err(ParserError::Expected("type token", value.clone())),
+        match value.kind {
+            TokenKind::Int => Ok(CType::Int),
+            TokenKind::Float => Ok(CType::Float),
+            TokenKind::Double => Ok(CType::Double),
+            TokenKind::Void => Ok(CType::Void),
+            _ => Err(ParserError::expected("type token", *value)),
+        }
+    }
+}
+
+impl CType {
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            CType::Int | CType::Char | CType::Float | CType::Double
+        )
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self, CType::Float | CType::Double)
+    }
+}
+
+impl fmt::Display for CType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CType::Int => write!(f, "int"),
+            CType::Char => write!(f, "char"),
+            CType::Float => write!(f, "float"),
+            CType::Double => write!(f, "double"),
+            CType::Void => write!(f, "void"),
+            CType::Pointer(inner) => write!(f, "{} *", inner),
         }
     }
 }
 
 /// C expression
 #[derive(Debug, PartialEq)]
-pub enum Expression {
-    IntegerLiteral(i64),
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+    /// Filled in by the type checker
+    pub ctype: Option<CType>,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            ctype: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExpressionKind {
+    IntegerLiteral(u64, IntegerType),
+    FloatLiteral(f64, FloatType),
+    CharacterLiteral(i32),
+    /// Contents of the string, after concatenation of adjacent literals and without the
+    /// terminating null byte
+    StringLiteral(Vec<u8>),
     Identifier(String),
+    /// `(type) a`, also inserted by the type checker for implicit conversions
+    Cast(CType, Box<Expression>),
     BinaryOp(Box<BinaryOp>),
+    /// `a += b`, stored as the operation `a + b` whose left operand is also the target. The
+    /// operation is performed in the type of the right operand, converted as needed by the type
+    /// checker, so the target may have to be converted before and after it
+    CompoundAssign(Box<BinaryOp>),
     Unary(Box<Unary>),
+    FunctionCall(String, Vec<Expression>),
+    Conditional {
+        condition: Box<Expression>,
+        then: Box<Expression>,
+        otherwise: Box<Expression>,
+    }, // `condition ? then : otherwise`
+    Subscript(Box<Expression>, Box<Expression>), // `a[i]`
+    Member(Box<Expression>, String),             // `a.b`
+    PointerMember(Box<Expression>, String),      // `a->b`
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Unary {
     Positive(Expression),
     Negative(Expression),
+    Not(Expression),        // `!a`
+    Complement(Expression), // `~a`
+    PreIncrement(Expression),
+    PreDecrement(Expression),
+    PostIncrement(Expression),
+    PostDecrement(Expression),
 }
 
 #[derive(Debug, PartialEq)]
@@ -46,16 +126,216 @@ pub enum BinaryOp {
     Subtract(Expression, Expression),
     Multiply(Expression, Expression),
     Divide(Expression, Expression),
+    Remainder(Expression, Expression),
     Equals(Expression, Expression),
     NotEquals(Expression, Expression),
+    LessThan(Expression, Expression),
+    LessOrEqual(Expression, Expression),
+    GreaterThan(Expression, Expression),
+    GreaterOrEqual(Expression, Expression),
+    LogicalAnd(Expression, Expression),
+    LogicalOr(Expression, Expression),
+    BitwiseAnd(Expression, Expression),
+    BitwiseOr(Expression, Expression),
+    BitwiseXor(Expression, Expression),
+    ShiftLeft(Expression, Expression),
+    ShiftRight(Expression, Expression),
     Assign(Expression, Expression),
+    Comma(Expression, Expression),
+}
+
+impl BinaryOp {
+    /// The operator as written in the source
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add(..) => "+",
+            BinaryOp::Subtract(..) => "-",
+            BinaryOp::Multiply(..) => "*",
+            BinaryOp::Divide(..) => "/",
+            BinaryOp::Remainder(..) => "%",
+            BinaryOp::Equals(..) => "==",
+            BinaryOp::NotEquals(..) => "!=",
+            BinaryOp::LessThan(..) => "<",
+            BinaryOp::LessOrEqual(..) => "<=",
+            BinaryOp::GreaterThan(..) => ">",
+            BinaryOp::GreaterOrEqual(..) => ">=",
+            BinaryOp::LogicalAnd(..) => "&&",
+            BinaryOp::LogicalOr(..) => "||",
+            BinaryOp::BitwiseAnd(..) => "&",
+            BinaryOp::BitwiseOr(..) => "|",
+            BinaryOp::BitwiseXor(..) => "^",
+            BinaryOp::ShiftLeft(..) => "<<",
+            BinaryOp::ShiftRight(..) => ">>",
+            BinaryOp::Assign(..) => "=",
+            BinaryOp::Comma(..) => ",",
+        }
+    }
+
+    /// Operators only defined for integer operands
+    pub fn is_integer_only(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Remainder(..)
+                | BinaryOp::BitwiseAnd(..)
+                | BinaryOp::BitwiseOr(..)
+                | BinaryOp::BitwiseXor(..)
+                | BinaryOp::ShiftLeft(..)
+                | BinaryOp::ShiftRight(..)
+        )
+    }
+
+    /// Left and right operands
+    pub fn operands(&self) -> (&Expression, &Expression) {
+        match self {
+            BinaryOp::Add(left, right)
+            | BinaryOp::Subtract(left, right)
+            | BinaryOp::Multiply(left, right)
+            | BinaryOp::Divide(left, right)
+            | BinaryOp::Remainder(left, right)
+            | BinaryOp::Equals(left, right)
+            | BinaryOp::NotEquals(left, right)
+            | BinaryOp::LessThan(left, right)
+            | BinaryOp::LessOrEqual(left, right)
+            | BinaryOp::GreaterThan(left, right)
+            | BinaryOp::GreaterOrEqual(left, right)
+            | BinaryOp::LogicalAnd(left, right)
+            | BinaryOp::LogicalOr(left, right)
+            | BinaryOp::BitwiseAnd(left, right)
+            | BinaryOp::BitwiseOr(left, right)
+            | BinaryOp::BitwiseXor(left, right)
+            | BinaryOp::ShiftLeft(left, right)
+            | BinaryOp::ShiftRight(left, right)
+            | BinaryOp::Assign(left, right)
+            | BinaryOp::Comma(left, right) => (left, right),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> (&mut Expression, &mut Expression) {
+        match self {
+            BinaryOp::Add(left, right)
+            | BinaryOp::Subtract(left, right)
+            | BinaryOp::Multiply(left, right)
+            | BinaryOp::Divide(left, right)
+            | BinaryOp::Remainder(left, right)
+            | BinaryOp::Equals(left, right)
+            | BinaryOp::NotEquals(left, right)
+            | BinaryOp::LessThan(left, right)
+            | BinaryOp::LessOrEqual(left, right)
+            | BinaryOp::GreaterThan(left, right)
+            | BinaryOp::GreaterOrEqual(left, right)
+            | BinaryOp::LogicalAnd(left, right)
+            | BinaryOp::LogicalOr(left, right)
+            | BinaryOp::BitwiseAnd(left, right)
+            | BinaryOp::BitwiseOr(left, right)
+            | BinaryOp::BitwiseXor(left, right)
+            | BinaryOp::ShiftLeft(left, right)
+            | BinaryOp::ShiftRight(left, right)
+            | BinaryOp::Assign(left, right)
+            | BinaryOp::Comma(left, right) => (left, right),
+        }
+    }
+}
+
+impl Unary {
+    /// The operator as written in the source
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unary::Positive(_) => "+",
+            Unary::Negative(_) => "-",
+            Unary::Not(_) => "!",
+            Unary::Complement(_) => "~",
+            Unary::PreIncrement(_) | Unary::PostIncrement(_) => "++",
+            Unary::PreDecrement(_) | Unary::PostDecrement(_) => "--",
+        }
+    }
+
+    pub fn operand(&self) -> &Expression {
+        match self {
+            Unary::Positive(expr)
+            | Unary::Negative(expr)
+            | Unary::Not(expr)
+            | Unary::Complement(expr)
+            | Unary::PreIncrement(expr)
+            | Unary::PreDecrement(expr)
+            | Unary::PostIncrement(expr)
+            | Unary::PostDecrement(expr) => expr,
+        }
+    }
+
+    pub fn operand_mut(&mut self) -> &mut Expression {
+        match self {
+            Unary::Positive(expr)
+            | Unary::Negative(expr)
+            | Unary::Not(expr)
+            | Unary::Complement(expr)
+            | Unary::PreIncrement(expr)
+            | Unary::PreDecrement(expr)
+            | Unary::PostIncrement(expr)
+            | Unary::PostDecrement(expr) => expr,
+        }
+    }
+
+    /// Whether the operator modifies its operand, which must then be an lvalue
+    pub fn is_increment(&self) -> bool {
+        matches!(
+            self,
+            Unary::PreIncrement(_)
+                | Unary::PreDecrement(_)
+                | Unary::PostIncrement(_)
+                | Unary::PostDecrement(_)
+        )
+    }
 }
 
 #[derive(Debug, PartialEq)]
-pub enum Statement {
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StatementKind {
     Declaration(VariableDeclaration),
     ExpressionStatment(Expression), // e.g., `5 + 6;`, `my_func();`
-    Return(Expression),
+    Return(Option<Expression>),     // `return;` has no expression
+    Compound(Vec<Statement>),       // `{ ... }`
+    Null,                           // `;`
+    If {
+        condition: Expression,
+        then: Box<Statement>,
+        otherwise: Option<Box<Statement>>,
+    },
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+    },
+    DoWhile {
+        body: Box<Statement>,
+        condition: Expression,
+    },
+    For {
+        init: ForInit,
+        condition: Option<Expression>,
+        post: Option<Expression>,
+        body: Box<Statement>,
+    },
+    Break,
+    Continue,
+    Goto(String),
+    Labeled(String, Box<Statement>), // `label: statement`
+}
+
+/// First clause of a `for` statement
+#[derive(Debug, PartialEq)]
+pub enum ForInit {
+    Declaration(VariableDeclaration),
+    Expression(Option<Expression>),
 }
 
 #[derive(Debug, PartialEq)]
@@ -64,56 +344,73 @@ pub enum Declaration {
     Function(FunctionDeclaration),
 }
 
+impl Declaration {
+    pub fn span(&self) -> Span {
+        match self {
+            Declaration::Variable(variable) => variable.span,
+            Declaration::Function(function) => function.span,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct VariableDeclaration {
-    ctype: CType,
-    name: String,
-    initilizer: Option<Expression>,
+    pub ctype: CType,
+    pub name: String,
+    pub initilizer: Option<Expression>,
+    pub span: Span,
 }
 
 impl VariableDeclaration {
-    pub fn new(ctype: CType, name: String, initilizer: Option<Expression>) -> Self {
+    pub fn new(ctype: CType, name: String, initilizer: Option<Expression>, span: Span) -> Self {
         Self {
             ctype,
             name,
             initilizer,
+            span,
         }
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct FunctionDeclaration {
-    return_type: CType,
-    name: String,
-    parameters: Vec<FnParameter>,
-    body: Option<Vec<Statement>>,
+    pub return_type: CType,
+    pub name: String,
+    /// `None` for an empty list `()`, which declares a function without a prototype
+    pub parameters: Option<Vec<FnParameter>>,
+    /// `None` for declarations without a body (prototypes)
+    pub body: Option<Vec<Statement>>,
+    pub span: Span,
 }
 
 impl FunctionDeclaration {
     pub fn new(
         return_type: CType,
         name: String,
-        parameters: Vec<FnParameter>,
+        parameters: Option<Vec<FnParameter>>,
         body: Option<Vec<Statement>>,
+        span: Span,
     ) -> Self {
         Self {
             return_type,
             name,
             parameters,
             body,
+            span,
         }
     }
 }
 
-/// `ctype` is void and `name` is empty if function doesn't take any parameters
+/// The name is optional in declarations without a body (e.g. `int f(int);`)
 #[derive(Debug, PartialEq)]
 pub struct FnParameter {
-    ctype: CType,
-    name: String,
+    pub ctype: CType,
+    pub name: Option<String>,
+    pub span: Span,
 }
 
 impl FnParameter {
-    pub fn new(ctype: CType, name: String) -> Self {
-        Self { ctype, name }
+    pub fn new(ctype: CType, name: Option<String>, span: Span) -> Self {
+        Self { ctype, name, span }
     }
 }
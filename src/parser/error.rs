@@ -1,24 +1,76 @@
 use std::fmt;
 
-use crate::Token;
+use crate::{Token, TokenKind, span::Span};
 
 pub type ParserResult<T> = Result<T, ParserError>;
 
 #[derive(Debug)]
 pub enum ParserError {
     Expected(&'static str, Token),
-    Other(&'static str),
+    /// A C keyword the parser doesn't handle yet
+    UnsupportedKeyword(Token),
+    Other(&'static str, Span),
+}
+
+impl ParserError {
+    /// `got` was found where `expected` should be. A keyword the parser doesn't handle yet is
+    /// valid C, so it is reported as unsupported instead
+    pub fn expected(expected: &'static str, got: Token) -> Self {
+        if got.kind.keyword().is_some() && !is_supported_keyword(&got.kind) {
+            ParserError::UnsupportedKeyword(got)
+        } else {
+            ParserError::Expected(expected, got)
+        }
+    }
+
+    /// Location in the source the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::Expected(_, got) => got.error_span(),
+            ParserError::UnsupportedKeyword(got) => got.span,
+            ParserError::Other(_, span) => *span,
+        }
+    }
 }
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParserError::Other(msg) => write!(f, "ERROR: {}", msg),
+            ParserError::Other(msg, _) => write!(f, "ERROR: {}", msg),
+            // The lexer already explained what is wrong with the token
+            ParserError::Expected(_, got) if let TokenKind::Error(msg, _) = got.kind => {
+                write!(f, "ERROR: {}", msg)
+            }
+            ParserError::UnsupportedKeyword(got) => write!(
+                f,
+                "ERROR: Keyword '{}' is not supported yet",
+                got.kind.keyword().unwrap_or_default()
+            ),
             ParserError::Expected(exp, got) => write!(
                 f,
                 "ERROR: Unexpected error: expected '{}', but got '{:?}'",
-                exp, got
+                exp, got.kind
             ),
         }
     }
 }
+
+/// Keywords the parser knows how to handle
+fn is_supported_keyword(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Int
+            | TokenKind::Float
+            | TokenKind::Double
+            | TokenKind::Void
+            | TokenKind::Return
+            | TokenKind::If
+            | TokenKind::Else
+            | TokenKind::While
+            | TokenKind::Do
+            | TokenKind::For
+            | TokenKind::Break
+            | TokenKind::Continue
+            | TokenKind::Goto
+    )
+}
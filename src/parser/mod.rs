@@ -1,115 +1,268 @@
-mod ast;
+pub mod ast;
 mod error;
-mod precedence;
+pub(crate) mod precedence;
 
+pub use crate::parser::error::{ParserError, ParserResult};
 use crate::{
-    Token,
+    Token, TokenSource,
     lexer::Lexer,
     parser::{
         ast::{
-            BinaryOp, CType, Declaration, Expression, FnParameter, FunctionDeclaration, Statement,
-            TranslationUnit, Unary, VariableDeclaration,
+            BinaryOp, CType, Declaration, Expression, ExpressionKind, FnParameter, ForInit,
+            FunctionDeclaration, Statement, StatementKind, TranslationUnit, Unary,
+            VariableDeclaration,
         },
-        error::{ParserError, ParserResult},
         precedence::Precedence,
     },
+    span::Span,
+    token::TokenKind,
 };
 
-/// Public API for parsing source to `TranslationUnit`
-pub fn parse(src: &str) -> ParserResult<TranslationUnit> {
-    let mut lexer = Lexer::new(src);
-    let mut parser = Parser::new(&mut lexer);
+/// Public API for parsing source to `TranslationUnit`. Returns every syntax error found
+pub fn parse(src: &str) -> Result<TranslationUnit, Vec<ParserError>> {
+    parse_tokens(Lexer::new(src))
+}
+
+/// Parses the tokens of a configured lexer or of the preprocessor
+pub fn parse_tokens(tokens: impl TokenSource) -> Result<TranslationUnit, Vec<ParserError>> {
+    let mut parser = Parser::new(tokens);
 
     parser.parse()
 }
 
-struct Parser<'a> {
-    lexer: &'a mut Lexer<'a>,
+struct Parser<S> {
+    tokens: S,
+    /// The current token, tokens after it are looked at with `tokens.peek_nth`
     token: Token,
-    peek_token: Token,
+    /// Span of the last consumed token
+    prev_span: Span,
+    /// Errors recovered from so far
+    errors: Vec<ParserError>,
 }
 
-impl<'a> Parser<'a> {
-    fn new(lexer: &'a mut Lexer<'a>) -> Self {
-        let token = lexer.next_token();
-        let peek_token = lexer.next_token();
+impl<S: TokenSource> Parser<S> {
+    fn new(mut tokens: S) -> Self {
+        let token = tokens.next_token();
 
         Self {
-            lexer,
+            tokens,
             token,
-            peek_token,
+            prev_span: Span::default(),
+            errors: Vec::new(),
         }
     }
 
     fn next_token(&mut self) {
-        // TODO: Can this be done without the `.clone()`?
-        self.token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+        self.prev_span = self.token.span;
+        self.token = self.tokens.next_token();
+    }
+
+    /// Span from `start` to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev_span)
     }
 
-    fn parse(&mut self) -> ParserResult<TranslationUnit> {
+    /// Consumes the current token if it is `kind`, errors otherwise
+    fn expect(&mut self, kind: TokenKind, expected: &'static str) -> ParserResult<()> {
+        if self.token.kind != kind {
+            return Err(ParserError::expected(expected, self.token));
+        }
+        self.next_token();
+        Ok(())
+    }
+
+    fn parse(&mut self) -> Result<TranslationUnit, Vec<ParserError>> {
         let mut declarations = Vec::new();
 
-        while self.token != Token::EOF {
-            declarations.push(self.parse_declaration()?);
+        while self.token.kind != TokenKind::EOF {
+            match self.parse_declaration() {
+                Ok(declaration) => declarations.push(declaration),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize_declaration();
+                }
+            }
         }
 
-        Ok(TranslationUnit { declarations })
+        if self.errors.is_empty() {
+            Ok(TranslationUnit { declarations })
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Skips tokens until the end of the broken declaration: after a top-level `;` or `}`,
+    /// or right before a type that starts the next declaration
+    fn synchronize_declaration(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.token.kind {
+                TokenKind::EOF => return,
+                ref kind if depth == 0 && kind.is_type_specifier() => return,
+                TokenKind::OpenParen | TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseParen | TokenKind::CloseBrace => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 && self.token.kind == TokenKind::CloseBrace {
+                        self.next_token();
+                        return;
+                    }
+                }
+                TokenKind::Semicolon if depth == 0 => {
+                    self.next_token();
+                    return;
+                }
+                _ => {}
+            }
+            self.next_token();
+        }
+    }
+
+    /// Skips tokens until after the `;` ending the broken statement, or right before the `}`
+    /// closing the enclosing block
+    fn synchronize_statement(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.token.kind {
+                TokenKind::EOF => return,
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => return,
+                TokenKind::CloseBrace => depth -= 1,
+                TokenKind::Semicolon if depth == 0 => {
+                    self.next_token();
+                    return;
+                }
+                _ => {}
+            }
+            self.next_token();
+        }
     }
 
     fn parse_declaration(&mut self) -> ParserResult<Declaration> {
+        let (ctype, identifier, start) = self.parse_declaration_head()?;
+
+        match self.token.kind {
+            TokenKind::Assignment | TokenKind::Semicolon => Ok(Declaration::Variable(
+                self.parse_variable_declaration(ctype, identifier, start)?,
+            )),
+            TokenKind::OpenParen => self.parse_function_declaration(ctype, identifier, start),
+            _ => Err(ParserError::expected("declaration", self.token)),
+        }
+    }
+
+    /// Parses the type and name every declaration starts with. Also returns the span of the type
+    fn parse_declaration_head(&mut self) -> ParserResult<(CType, String, Span)> {
+        let start = self.token.span;
         // TODO: This code expects type to be only one token, but in c there can be multi token types (e.g. `unsigned int x = 5;`)
         // Convert the current token to a `CType`
         let ctype = CType::try_from(&self.token)?;
         self.next_token(); // Consume the type
-        let identifier = match &self.token {
-            Token::Identifier(id) => id.clone(),
-            _ => return Err(ParserError::Expected("identifier", self.token.clone())),
+        let identifier = match &self.token.kind {
+            TokenKind::Identifier(id) => id.to_string(),
+            _ => return Err(ParserError::expected("identifier", self.token)),
         };
         self.next_token(); // Consume the identifier
 
-        match self.token {
-            Token::Assignment | Token::Semicolon => {
-                self.parse_variable_declaration(ctype, identifier)
-            }
-            Token::OpenParen => self.parse_function_declaration(ctype, identifier),
-            _ => Err(ParserError::Expected("declaration", self.token.clone())),
-        }
+        Ok((ctype, identifier, start))
     }
 
     fn parse_function_declaration(
         &mut self,
         ctype: CType,
         name: String,
+        start: Span,
     ) -> ParserResult<Declaration> {
         let parameters = self.parse_function_args()?;
-        let body = self.parse_body()?;
+        let body = if self.token.kind == TokenKind::Semicolon {
+            self.next_token(); // Consume the `;`
+            None
+        } else {
+            Some(self.parse_body()?)
+        };
 
         Ok(Declaration::Function(FunctionDeclaration::new(
             ctype,
             name,
             parameters,
-            Some(body),
+            body,
+            self.span_from(start),
         )))
     }
 
-    fn parse_function_args(&mut self) -> ParserResult<Vec<FnParameter>> {
-        self.next_token(); // Consume the '('
-        // Consume the args
-        while self.token != Token::CloseParen {
-            self.next_token();
+    /// Parses `(...)` after a function name. Returns `None` for `()`, and an empty list for `(void)`
+    fn parse_function_args(&mut self) -> ParserResult<Option<Vec<FnParameter>>> {
+        self.expect(TokenKind::OpenParen, "(")?;
+
+        if self.token.kind == TokenKind::CloseParen {
+            self.next_token(); // Consume the ')'
+            return Ok(None);
+        }
+        if self.token.kind == TokenKind::Void
+            && self.tokens.peek_nth(0).kind == TokenKind::CloseParen
+        {
+            self.next_token(); // Consume the `void`
+            self.next_token(); // Consume the ')'
+            return Ok(Some(Vec::new()));
+        }
+
+        let mut parameters = vec![self.parse_parameter()?];
+        while self.token.kind == TokenKind::Comma {
+            self.next_token(); // Consume the ','
+            parameters.push(self.parse_parameter()?);
         }
-        self.next_token(); // Consume the ')'
+        self.expect(TokenKind::CloseParen, ")")?;
 
-        // TODO: Parse function parameters
-        Ok(vec![FnParameter::new(CType::Void, String::new())])
+        Ok(Some(parameters))
+    }
+
+    fn parse_parameter(&mut self) -> ParserResult<FnParameter> {
+        let start = self.token.span;
+        let ctype = CType::try_from(&self.token)?;
+        self.next_token(); // Consume the type
+
+        let name = match &self.token.kind {
+            TokenKind::Identifier(name) => {
+                let name = name.to_string();
+                self.next_token(); // Consume the identifier
+                Some(name)
+            }
+            _ => None,
+        };
+
+        Ok(FnParameter::new(ctype, name, self.span_from(start)))
+    }
+
+    /// Parses the arguments of a call, after the opening '('
+    fn parse_call_args(&mut self) -> ParserResult<Vec<Expression>> {
+        let mut args = Vec::new();
+        if self.token.kind != TokenKind::CloseParen {
+            // Commas separate the arguments, so the comma operator isn't allowed unparenthesized
+            args.push(self.parse_expresssion(Precedence::Comma)?);
+            while self.token.kind == TokenKind::Comma {
+                self.next_token(); // Consume the ','
+                args.push(self.parse_expresssion(Precedence::Comma)?);
+            }
+        }
+        self.expect(TokenKind::CloseParen, ")")?;
+
+        Ok(args)
     }
 
     fn parse_body(&mut self) -> ParserResult<Vec<Statement>> {
-        self.next_token(); // Consume the '{'
+        self.expect(TokenKind::OpenBrace, "{")?;
         let mut statements = Vec::new();
-        while self.token != Token::CloseBrace {
-            statements.push(self.parse_statement()?);
+        while self.token.kind != TokenKind::CloseBrace {
+            if self.token.kind == TokenKind::EOF {
+                return Err(ParserError::expected("}", self.token));
+            }
+
+            match self.parse_block_item() {
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    // Keep going, so every broken statement in the body gets reported
+                    self.errors.push(e);
+                    self.synchronize_statement();
+                }
+            }
         }
         self.next_token(); // Consume the '}'
 
@@ -120,23 +273,30 @@ impl<'a> Parser<'a> {
         &mut self,
         ctype: CType,
         name: String,
-    ) -> ParserResult<Declaration> {
-        match self.token {
-            Token::Semicolon => Ok(Declaration::Variable(VariableDeclaration::new(
-                ctype, name, None,
-            ))),
-            Token::Assignment => {
+        start: Span,
+    ) -> ParserResult<VariableDeclaration> {
+        match self.token.kind {
+            TokenKind::Semicolon => {
+                self.next_token(); // Consume the `;`
+                Ok(VariableDeclaration::new(
+                    ctype,
+                    name,
+                    None,
+                    self.span_from(start),
+                ))
+            }
+            TokenKind::Assignment => {
                 self.next_token(); // Consume the `=`
-                Ok(Declaration::Variable(VariableDeclaration::new(
+                let initilizer = self.parse_expresssion(Precedence::Comma)?;
+                self.expect(TokenKind::Semicolon, ";")?;
+                Ok(VariableDeclaration::new(
                     ctype,
                     name,
-                    Some(self.parse_expresssion(Precedence::Lowest)?),
-                )))
+                    Some(initilizer),
+                    self.span_from(start),
+                ))
             }
-            _ => Err(ParserError::Expected(
-                "assignmen | semicolon",
-                self.token.clone(),
-            )),
+            _ => Err(ParserError::expected("assignmen | semicolon", self.token)),
         }
     }
 
@@ -144,107 +304,424 @@ impl<'a> Parser<'a> {
         let mut left = self.parse_prefix_expression()?;
 
         // Parse infix operators while they have higher precedence
-        while self.token != Token::Semicolon
-            && self.token != Token::CloseParen
-            && self.token != Token::CloseBrace
-            && precedence < Precedence::from(&self.token)
+        while self.token.kind != TokenKind::Semicolon
+            && self.token.kind != TokenKind::CloseParen
+            && self.token.kind != TokenKind::CloseBrace
+            && precedence < Precedence::from(&self.token.kind)
         {
             left = self.parse_infix_expression(left)?;
         }
 
-        dbg!(&self.token);
-        if self.token == Token::Semicolon {
-            self.next_token();
-        }
-
         Ok(left)
     }
 
     fn parse_prefix_expression(&mut self) -> ParserResult<Expression> {
-        match &self.token {
-            Token::IntegerLiteral(value) => {
+        let start = self.token.span;
+        match &self.token.kind {
+            TokenKind::IntegerLiteral(value, ty) => {
+                let (value, ty) = (*value, *ty);
+                self.next_token();
+                Ok(Expression::new(
+                    ExpressionKind::IntegerLiteral(value, ty),
+                    start,
+                ))
+            }
+            TokenKind::FloatLiteral(value, ty) => {
+                let (value, ty) = (*value, *ty);
+                self.next_token();
+                Ok(Expression::new(
+                    ExpressionKind::FloatLiteral(value, ty),
+                    start,
+                ))
+            }
+            TokenKind::CharacterLiteral(value) => {
                 let value = *value;
                 self.next_token();
-                Ok(Expression::IntegerLiteral(value))
+                Ok(Expression::new(
+                    ExpressionKind::CharacterLiteral(value),
+                    start,
+                ))
             }
-            Token::Identifier(name) => {
-                let name = name.clone();
+            TokenKind::StringLiteral(_) => {
+                // Adjacent string literals are concatenated into one
+                let mut bytes = Vec::new();
+                while let TokenKind::StringLiteral(part) = &self.token.kind {
+                    bytes.extend_from_slice(part.as_bytes());
+                    self.next_token();
+                }
+                Ok(Expression::new(
+                    ExpressionKind::StringLiteral(bytes),
+                    self.span_from(start),
+                ))
+            }
+            TokenKind::Identifier(name) => {
+                let name = name.to_string();
                 self.next_token();
-                Ok(Expression::Identifier(name))
+
+                if self.token.kind == TokenKind::OpenParen {
+                    self.next_token(); // Consume '('
+                    let args = self.parse_call_args()?;
+                    return Ok(Expression::new(
+                        ExpressionKind::FunctionCall(name, args),
+                        self.span_from(start),
+                    ));
+                }
+                Ok(Expression::new(ExpressionKind::Identifier(name), start))
             }
-            Token::Plus => {
-                self.next_token(); // Consume '+'
+            TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Bang
+            | TokenKind::Tilde
+            | TokenKind::Increment
+            | TokenKind::Decrement => {
+                let operator = self.token.kind;
+                self.next_token(); // Consume the operator
                 let expr = self.parse_expresssion(Precedence::Unary)?;
-                Ok(Expression::Unary(Box::new(Unary::Positive(expr))))
+                let span = start.to(expr.span);
+                let unary = match operator {
+                    TokenKind::Plus => Unary::Positive(expr),
+                    TokenKind::Minus => Unary::Negative(expr),
+                    TokenKind::Bang => Unary::Not(expr),
+                    TokenKind::Tilde => Unary::Complement(expr),
+                    TokenKind::Increment => Unary::PreIncrement(expr),
+                    _ => Unary::PreDecrement(expr),
+                };
+                Ok(Expression::new(
+                    ExpressionKind::Unary(Box::new(unary)),
+                    span,
+                ))
             }
-            Token::Minus => {
-                self.next_token(); // Consume '-'
+            TokenKind::OpenParen if self.tokens.peek_nth(0).kind.is_type_specifier() => {
+                self.next_token(); // Consume '('
+                let ctype = CType::try_from(&self.token)?;
+                self.next_token();
+                self.expect(TokenKind::CloseParen, "closing parenthesis")?;
                 let expr = self.parse_expresssion(Precedence::Unary)?;
-                Ok(Expression::Unary(Box::new(Unary::Negative(expr))))
+                let span = start.to(expr.span);
+                Ok(Expression::new(
+                    ExpressionKind::Cast(ctype, Box::new(expr)),
+                    span,
+                ))
             }
-            Token::OpenParen => {
+            TokenKind::OpenParen => {
                 self.next_token(); // Consume '('
-                let expr = self.parse_expresssion(Precedence::Lowest)?;
-                if self.token != Token::CloseParen {
-                    return Err(ParserError::Expected(
-                        "closing parenthesis",
-                        self.token.clone(),
-                    ));
+                let mut expr = self.parse_expresssion(Precedence::Lowest)?;
+                if self.token.kind != TokenKind::CloseParen {
+                    return Err(ParserError::expected("closing parenthesis", self.token));
                 }
                 self.next_token(); // Consume ')'
+                expr.span = self.span_from(start);
                 Ok(expr)
             }
-            _ => Err(ParserError::Expected("expression", self.token.clone())),
+            _ => Err(ParserError::expected("expression", self.token)),
         }
     }
 
     fn parse_infix_expression(&mut self, left: Expression) -> ParserResult<Expression> {
-        let operator = self.token.clone();
-        let precedence = Precedence::from(&operator);
+        let operator = self.token;
+        let precedence = Precedence::from(&operator.kind);
         self.next_token(); // Consume the operator
 
-        let right = self.parse_expresssion(precedence)?;
+        match operator.kind {
+            TokenKind::Increment | TokenKind::Decrement => {
+                let span = left.span.to(operator.span);
+                let unary = if operator.kind == TokenKind::Increment {
+                    Unary::PostIncrement(left)
+                } else {
+                    Unary::PostDecrement(left)
+                };
+                return Ok(Expression::new(
+                    ExpressionKind::Unary(Box::new(unary)),
+                    span,
+                ));
+            }
+            TokenKind::OpenBracket => {
+                let index = self.parse_expresssion(Precedence::Lowest)?;
+                self.expect(TokenKind::CloseBracket, "]")?;
+                let span = left.span.to(self.prev_span);
+                return Ok(Expression::new(
+                    ExpressionKind::Subscript(Box::new(left), Box::new(index)),
+                    span,
+                ));
+            }
+            TokenKind::Dot | TokenKind::Arrow => {
+                let TokenKind::Identifier(member) = &self.token.kind else {
+                    return Err(ParserError::expected("member name", self.token));
+                };
+                let member = member.to_string();
+                self.next_token(); // Consume the member name
+                let span = left.span.to(self.prev_span);
+                let kind = if operator.kind == TokenKind::Dot {
+                    ExpressionKind::Member(Box::new(left), member)
+                } else {
+                    ExpressionKind::PointerMember(Box::new(left), member)
+                };
+                return Ok(Expression::new(kind, span));
+            }
+            TokenKind::Question => {
+                // Anything can appear between `?` and `:`, like inside parentheses
+                let then = self.parse_expresssion(Precedence::Lowest)?;
+                self.expect(TokenKind::Colon, ":")?;
+                let otherwise = self.parse_expresssion(precedence.lower())?;
+                let span = left.span.to(otherwise.span);
+                return Ok(Expression::new(
+                    ExpressionKind::Conditional {
+                        condition: Box::new(left),
+                        then: Box::new(then),
+                        otherwise: Box::new(otherwise),
+                    },
+                    span,
+                ));
+            }
+            _ => {}
+        }
+
+        // Right associative operators accept an operator of the same precedence on their right
+        let right_precedence = if Precedence::is_right_associative(&operator.kind) {
+            precedence.lower()
+        } else {
+            precedence
+        };
+        let right = self.parse_expresssion(right_precedence)?;
+        let span = left.span.to(right.span);
 
-        let binary_op = match operator {
-            Token::Plus => BinaryOp::Add(left, right),
-            Token::Minus => BinaryOp::Subtract(left, right),
-            Token::Asterix => BinaryOp::Multiply(left, right),
-            Token::Slash => BinaryOp::Divide(left, right),
-            Token::Equal => BinaryOp::Equals(left, right),
-            Token::Assignment => BinaryOp::Assign(left, right),
-            _ => return Err(ParserError::Expected("binary operator", operator)),
+        let (binary_op, compound) = match operator.kind {
+            TokenKind::Plus => (BinaryOp::Add(left, right), false),
+            TokenKind::Minus => (BinaryOp::Subtract(left, right), false),
+            TokenKind::Asterix => (BinaryOp::Multiply(left, right), false),
+            TokenKind::Slash => (BinaryOp::Divide(left, right), false),
+            TokenKind::Percent => (BinaryOp::Remainder(left, right), false),
+            TokenKind::Equal => (BinaryOp::Equals(left, right), false),
+            TokenKind::NotEqual => (BinaryOp::NotEquals(left, right), false),
+            TokenKind::Less => (BinaryOp::LessThan(left, right), false),
+            TokenKind::LessEqual => (BinaryOp::LessOrEqual(left, right), false),
+            TokenKind::Greater => (BinaryOp::GreaterThan(left, right), false),
+            TokenKind::GreaterEqual => (BinaryOp::GreaterOrEqual(left, right), false),
+            TokenKind::LogicalAnd => (BinaryOp::LogicalAnd(left, right), false),
+            TokenKind::LogicalOr => (BinaryOp::LogicalOr(left, right), false),
+            TokenKind::Ampersand => (BinaryOp::BitwiseAnd(left, right), false),
+            TokenKind::Pipe => (BinaryOp::BitwiseOr(left, right), false),
+            TokenKind::Caret => (BinaryOp::BitwiseXor(left, right), false),
+            TokenKind::ShiftLeft => (BinaryOp::ShiftLeft(left, right), false),
+            TokenKind::ShiftRight => (BinaryOp::ShiftRight(left, right), false),
+            TokenKind::Assignment => (BinaryOp::Assign(left, right), false),
+            TokenKind::Comma => (BinaryOp::Comma(left, right), false),
+            TokenKind::PlusAssignment => (BinaryOp::Add(left, right), true),
+            TokenKind::MinusAssignment => (BinaryOp::Subtract(left, right), true),
+            TokenKind::AsterixAssignment => (BinaryOp::Multiply(left, right), true),
+            TokenKind::SlashAssignment => (BinaryOp::Divide(left, right), true),
+            TokenKind::PercentAssignment => (BinaryOp::Remainder(left, right), true),
+            TokenKind::AmpersandAssignment => (BinaryOp::BitwiseAnd(left, right), true),
+            TokenKind::PipeAssignment => (BinaryOp::BitwiseOr(left, right), true),
+            TokenKind::CaretAssignment => (BinaryOp::BitwiseXor(left, right), true),
+            TokenKind::ShiftLeftAssignment => (BinaryOp::ShiftLeft(left, right), true),
+            TokenKind::ShiftRightAssignment => (BinaryOp::ShiftRight(left, right), true),
+            _ => return Err(ParserError::expected("binary operator", operator)),
         };
 
-        Ok(Expression::BinaryOp(Box::new(binary_op)))
+        let kind = if compound {
+            ExpressionKind::CompoundAssign(Box::new(binary_op))
+        } else {
+            ExpressionKind::BinaryOp(Box::new(binary_op))
+        };
+        Ok(Expression::new(kind, span))
+    }
+
+    /// Parses a declaration or a statement, the items of a compound statement
+    fn parse_block_item(&mut self) -> ParserResult<Statement> {
+        if !self.token.kind.is_type_specifier() {
+            return self.parse_statement();
+        }
+        let (ctype, name, start) = self.parse_declaration_head()?;
+        let variable = self.parse_variable_declaration(ctype, name, start)?;
+
+        Ok(Statement::new(
+            StatementKind::Declaration(variable),
+            self.span_from(start),
+        ))
     }
 
     fn parse_statement(&mut self) -> ParserResult<Statement> {
-        let statement = match self.token {
-            Token::Return => self.parse_return_statement()?,
-            Token::Int => unimplemented!("Var statement"),
-            _ => unimplemented!("Statement"),
+        let start = self.token.span;
+        let kind = match self.token.kind {
+            TokenKind::Return => return self.parse_return_statement(),
+            // Only a compound statement can contain declarations, e.g. `if (x) int y;` is invalid
+            ref kind if kind.is_type_specifier() => {
+                return Err(ParserError::Other(
+                    "Expected a statement, but got a declaration",
+                    self.token.span,
+                ));
+            }
+            TokenKind::Semicolon => {
+                self.next_token(); // Consume the `;`
+                StatementKind::Null
+            }
+            TokenKind::OpenBrace => StatementKind::Compound(self.parse_body()?),
+            TokenKind::If => self.parse_if_statement()?,
+            TokenKind::While => {
+                self.next_token(); // Consume the `while` keyword
+                let condition = self.parse_condition()?;
+                let body = Box::new(self.parse_statement()?);
+                StatementKind::While { condition, body }
+            }
+            TokenKind::Do => {
+                self.next_token(); // Consume the `do` keyword
+                let body = Box::new(self.parse_statement()?);
+                self.expect(TokenKind::While, "while")?;
+                let condition = self.parse_condition()?;
+                self.expect(TokenKind::Semicolon, ";")?;
+                StatementKind::DoWhile { body, condition }
+            }
+            TokenKind::For => self.parse_for_statement()?,
+            TokenKind::Break => {
+                self.next_token(); // Consume the `break` keyword
+                self.expect(TokenKind::Semicolon, ";")?;
+                StatementKind::Break
+            }
+            TokenKind::Continue => {
+                self.next_token(); // Consume the `continue` keyword
+                self.expect(TokenKind::Semicolon, ";")?;
+                StatementKind::Continue
+            }
+            TokenKind::Goto => {
+                self.next_token(); // Consume the `goto` keyword
+                let TokenKind::Identifier(label) = &self.token.kind else {
+                    return Err(ParserError::expected("label", self.token));
+                };
+                let label = label.to_string();
+                self.next_token(); // Consume the label
+                self.expect(TokenKind::Semicolon, ";")?;
+                StatementKind::Goto(label)
+            }
+            TokenKind::Identifier(ref label)
+                if self.tokens.peek_nth(0).kind == TokenKind::Colon =>
+            {
+                let label = label.to_string();
+                self.next_token(); // Consume the label
+                self.next_token(); // Consume the ':'
+                StatementKind::Labeled(label, Box::new(self.parse_statement()?))
+            }
+            _ => {
+                let expression = self.parse_expresssion(Precedence::Lowest)?;
+                self.expect(TokenKind::Semicolon, ";")?;
+                StatementKind::ExpressionStatment(expression)
+            }
+        };
+
+        Ok(Statement::new(kind, self.span_from(start)))
+    }
+
+    /// Parses the parenthesized condition of `if`, `while` and `do`
+    fn parse_condition(&mut self) -> ParserResult<Expression> {
+        self.expect(TokenKind::OpenParen, "(")?;
+        let condition = self.parse_expresssion(Precedence::Lowest)?;
+        self.expect(TokenKind::CloseParen, ")")?;
+
+        Ok(condition)
+    }
+
+    fn parse_if_statement(&mut self) -> ParserResult<StatementKind> {
+        self.next_token(); // Consume the `if` keyword
+        let condition = self.parse_condition()?;
+        let then = Box::new(self.parse_statement()?);
+
+        // An `else` always belongs to the innermost `if`, which is the one parsed last
+        let otherwise = if self.token.kind == TokenKind::Else {
+            self.next_token(); // Consume the `else` keyword
+            Some(Box::new(self.parse_statement()?))
+        } else {
+            None
+        };
+
+        Ok(StatementKind::If {
+            condition,
+            then,
+            otherwise,
+        })
+    }
+
+    fn parse_for_statement(&mut self) -> ParserResult<StatementKind> {
+        self.next_token(); // Consume the `for` keyword
+        self.expect(TokenKind::OpenParen, "(")?;
+
+        let init = match self.token.kind {
+            ref kind if kind.is_type_specifier() => {
+                let (ctype, name, start) = self.parse_declaration_head()?;
+                ForInit::Declaration(self.parse_variable_declaration(ctype, name, start)?)
+            }
+            _ => {
+                let init = self.parse_optional_expression(TokenKind::Semicolon)?;
+                self.expect(TokenKind::Semicolon, ";")?;
+                ForInit::Expression(init)
+            }
         };
+        let condition = self.parse_optional_expression(TokenKind::Semicolon)?;
+        self.expect(TokenKind::Semicolon, ";")?;
+        let post = self.parse_optional_expression(TokenKind::CloseParen)?;
+        self.expect(TokenKind::CloseParen, ")")?;
+        let body = Box::new(self.parse_statement()?);
 
-        Ok(statement)
+        Ok(StatementKind::For {
+            init,
+            condition,
+            post,
+            body,
+        })
+    }
+
+    /// Parses an expression, unless the current token is `terminator` (which isn't consumed)
+    fn parse_optional_expression(
+        &mut self,
+        terminator: TokenKind,
+    ) -> ParserResult<Option<Expression>> {
+        if self.token.kind == terminator {
+            Ok(None)
+        } else {
+            self.parse_expresssion(Precedence::Lowest).map(Some)
+        }
     }
 
     fn parse_return_statement(&mut self) -> ParserResult<Statement> {
+        let start = self.token.span;
         self.next_token(); // Consume the `return` keyword
-        let expression = self.parse_expresssion(Precedence::Lowest)?;
+        let expression = self.parse_optional_expression(TokenKind::Semicolon)?;
+        self.expect(TokenKind::Semicolon, ";")?;
 
-        Ok(Statement::Return(expression))
+        Ok(Statement::new(
+            StatementKind::Return(expression),
+            self.span_from(start),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::IntegerType;
     use crate::parser::ast::{
         CType, Declaration, Expression, FnParameter, FunctionDeclaration, Statement,
         VariableDeclaration,
     };
     use pretty_assertions::assert_eq;
 
+    fn int(value: u64, start: usize, end: usize) -> Expression {
+        Expression::new(
+            ExpressionKind::IntegerLiteral(value, IntegerType::Int),
+            Span::new(start, end),
+        )
+    }
+
+    fn binary(op: BinaryOp, start: usize, end: usize) -> Expression {
+        Expression::new(
+            ExpressionKind::BinaryOp(Box::new(op)),
+            Span::new(start, end),
+        )
+    }
+
+    fn unary(op: Unary, start: usize, end: usize) -> Expression {
+        Expression::new(ExpressionKind::Unary(Box::new(op)), Span::new(start, end))
+    }
+
     #[test]
     fn test_parser_parse_simple_main_function() {
         let src = "int main(void) { return 0; }";
@@ -254,8 +731,12 @@ mod tests {
             declarations: vec![Declaration::Function(FunctionDeclaration::new(
                 CType::Int,
                 "main".to_string(),
-                vec![FnParameter::new(CType::Void, "".to_string())],
-                Some(vec![Statement::Return(Expression::IntegerLiteral(0))]),
+                Some(vec![]),
+                Some(vec![Statement::new(
+                    StatementKind::Return(Some(int(0, 24, 25))),
+                    Span::new(17, 26),
+                )]),
+                Span::new(0, 28),
             ))],
         };
 
@@ -273,13 +754,18 @@ mod tests {
                 Declaration::Variable(VariableDeclaration::new(
                     CType::Int,
                     "a".to_string(),
-                    Some(Expression::IntegerLiteral(5)),
+                    Some(int(5, 8, 9)),
+                    Span::new(0, 10),
                 )),
                 Declaration::Function(FunctionDeclaration::new(
                     CType::Void,
                     "main".to_string(),
-                    vec![FnParameter::new(CType::Void, "".to_string())],
-                    Some(vec![Statement::Return(Expression::IntegerLiteral(0))]),
+                    Some(vec![]),
+                    Some(vec![Statement::new(
+                        StatementKind::Return(Some(int(0, 35, 36))),
+                        Span::new(28, 37),
+                    )]),
+                    Span::new(11, 38),
                 )),
             ],
         };
@@ -298,16 +784,14 @@ mod tests {
                 Declaration::Variable(VariableDeclaration::new(
                     CType::Int,
                     "a".to_string(),
-                    Some(Expression::Unary(Box::new(Unary::Positive(
-                        Expression::IntegerLiteral(5),
-                    )))),
+                    Some(unary(Unary::Positive(int(5, 9, 10)), 8, 10)),
+                    Span::new(0, 11),
                 )),
                 Declaration::Variable(VariableDeclaration::new(
                     CType::Int,
                     "b".to_string(),
-                    Some(Expression::Unary(Box::new(Unary::Negative(
-                        Expression::IntegerLiteral(6),
-                    )))),
+                    Some(unary(Unary::Negative(int(6, 21, 22)), 20, 22)),
+                    Span::new(12, 23),
                 )),
             ],
         };
@@ -325,13 +809,15 @@ mod tests {
             declarations: vec![Declaration::Variable(VariableDeclaration::new(
                 CType::Int,
                 "a".to_string(),
-                Some(Expression::BinaryOp(Box::new(BinaryOp::Add(
-                    Expression::IntegerLiteral(2),
-                    Expression::BinaryOp(Box::new(BinaryOp::Multiply(
-                        Expression::IntegerLiteral(3),
-                        Expression::IntegerLiteral(4),
-                    ))),
-                )))),
+                Some(binary(
+                    BinaryOp::Add(
+                        int(2, 8, 9),
+                        binary(BinaryOp::Multiply(int(3, 12, 13), int(4, 16, 17)), 12, 17),
+                    ),
+                    8,
+                    17,
+                )),
+                Span::new(0, 18),
             ))],
         };
 
@@ -348,13 +834,15 @@ mod tests {
             declarations: vec![Declaration::Variable(VariableDeclaration::new(
                 CType::Int,
                 "a".to_string(),
-                Some(Expression::BinaryOp(Box::new(BinaryOp::Multiply(
-                    Expression::BinaryOp(Box::new(BinaryOp::Add(
-                        Expression::IntegerLiteral(2),
-                        Expression::IntegerLiteral(3),
-                    ))),
-                    Expression::IntegerLiteral(4),
-                )))),
+                Some(binary(
+                    BinaryOp::Multiply(
+                        binary(BinaryOp::Add(int(2, 9, 10), int(3, 13, 14)), 8, 15),
+                        int(4, 18, 19),
+                    ),
+                    8,
+                    19,
+                )),
+                Span::new(0, 20),
             ))],
         };
 
@@ -371,19 +859,403 @@ mod tests {
             declarations: vec![Declaration::Variable(VariableDeclaration::new(
                 CType::Int,
                 "result".to_string(),
-                Some(Expression::BinaryOp(Box::new(BinaryOp::Subtract(
-                    Expression::BinaryOp(Box::new(BinaryOp::Add(
-                        Expression::Unary(Box::new(Unary::Negative(Expression::IntegerLiteral(5)))),
-                        Expression::BinaryOp(Box::new(BinaryOp::Multiply(
-                            Expression::IntegerLiteral(3),
-                            Expression::IntegerLiteral(2),
-                        ))),
-                    ))),
-                    Expression::IntegerLiteral(1),
-                )))),
+                Some(binary(
+                    BinaryOp::Subtract(
+                        binary(
+                            BinaryOp::Add(
+                                unary(Unary::Negative(int(5, 14, 15)), 13, 15),
+                                binary(BinaryOp::Multiply(int(3, 18, 19), int(2, 22, 23)), 18, 23),
+                            ),
+                            13,
+                            23,
+                        ),
+                        int(1, 26, 27),
+                    ),
+                    13,
+                    27,
+                )),
+                Span::new(0, 28),
             ))],
         };
 
         assert_eq!(expected, ast);
     }
+
+    #[test]
+    fn test_parser_block_statements() {
+        let src = "int main(void) { int x = 1; int y; ; { x = 2; } x + y; return x; }";
+        let ast = parse(src).unwrap();
+
+        let Declaration::Function(main) = &ast.declarations[0] else {
+            panic!("expected a function");
+        };
+        let body = main.body.as_ref().unwrap();
+        assert_eq!(body.len(), 6);
+        assert!(
+            matches!(&body[0].kind, StatementKind::Declaration(x) if x.name == "x" && x.initilizer.is_some())
+        );
+        assert!(
+            matches!(&body[1].kind, StatementKind::Declaration(y) if y.name == "y" && y.initilizer.is_none())
+        );
+        assert_eq!(body[2].kind, StatementKind::Null);
+        assert!(matches!(&body[3].kind, StatementKind::Compound(inner) if inner.len() == 1));
+        assert!(matches!(
+            &body[4].kind,
+            StatementKind::ExpressionStatment(_)
+        ));
+        assert!(matches!(&body[5].kind, StatementKind::Return(_)));
+
+        assert_eq!(body[0].span, Span::new(17, 27));
+        assert_eq!(body[3].span, Span::new(37, 47));
+    }
+
+    #[test]
+    fn test_parser_assignment_is_right_associative() {
+        let src = "int main(void) { a = b = 5; }";
+        let ast = parse(src).unwrap();
+
+        let Declaration::Function(main) = &ast.declarations[0] else {
+            panic!("expected a function");
+        };
+        let StatementKind::ExpressionStatment(expr) = &main.body.as_ref().unwrap()[0].kind else {
+            panic!("expected an expression statement");
+        };
+        let ExpressionKind::BinaryOp(assign) = &expr.kind else {
+            panic!("expected an assignment");
+        };
+        // Should parse as a = (b = 5)
+        assert!(matches!(
+            assign.as_ref(),
+            BinaryOp::Assign(left, right)
+                if left.kind == ExpressionKind::Identifier("a".to_string())
+                    && matches!(&right.kind, ExpressionKind::BinaryOp(inner) if matches!(inner.as_ref(), BinaryOp::Assign(..)))
+        ));
+    }
+
+    #[test]
+    fn test_parser_function_parameters_and_prototypes() {
+        let src = "int f(int a, int);\nint g();\nint h(void) { return f(1, g()); }";
+        let ast = parse(src).unwrap();
+
+        let expected = TranslationUnit {
+            declarations: vec![
+                Declaration::Function(FunctionDeclaration::new(
+                    CType::Int,
+                    "f".to_string(),
+                    Some(vec![
+                        FnParameter::new(CType::Int, Some("a".to_string()), Span::new(6, 11)),
+                        FnParameter::new(CType::Int, None, Span::new(13, 16)),
+                    ]),
+                    None,
+                    Span::new(0, 18),
+                )),
+                Declaration::Function(FunctionDeclaration::new(
+                    CType::Int,
+                    "g".to_string(),
+                    None,
+                    None,
+                    Span::new(19, 27),
+                )),
+                Declaration::Function(FunctionDeclaration::new(
+                    CType::Int,
+                    "h".to_string(),
+                    Some(vec![]),
+                    Some(vec![Statement::new(
+                        StatementKind::Return(Some(Expression::new(
+                            ExpressionKind::FunctionCall(
+                                "f".to_string(),
+                                vec![
+                                    int(1, 51, 52),
+                                    Expression::new(
+                                        ExpressionKind::FunctionCall("g".to_string(), vec![]),
+                                        Span::new(54, 57),
+                                    ),
+                                ],
+                            ),
+                            Span::new(49, 58),
+                        ))),
+                        Span::new(42, 59),
+                    )]),
+                    Span::new(28, 61),
+                )),
+            ],
+        };
+
+        assert_eq!(expected, ast);
+    }
+
+    /// Fully parenthesized form of an expression, to check how operators were grouped
+    fn render(expr: &Expression) -> String {
+        match &expr.kind {
+            ExpressionKind::IntegerLiteral(value, _) => value.to_string(),
+            ExpressionKind::FloatLiteral(value, _) => format!("{:?}", value),
+            ExpressionKind::CharacterLiteral(value) => format!("'{}'", value),
+            ExpressionKind::Cast(ctype, expr) => format!("(({}) {})", ctype, render(expr)),
+            ExpressionKind::StringLiteral(bytes) => {
+                format!("{:?}", bytes.escape_ascii().to_string())
+            }
+            ExpressionKind::Identifier(name) => name.clone(),
+            ExpressionKind::Unary(unary) => {
+                let operand = render(unary.operand());
+                match unary.as_ref() {
+                    Unary::Positive(_) => format!("(+{})", operand),
+                    Unary::Negative(_) => format!("(-{})", operand),
+                    Unary::Not(_) => format!("(!{})", operand),
+                    Unary::Complement(_) => format!("(~{})", operand),
+                    Unary::PreIncrement(_) => format!("(++{})", operand),
+                    Unary::PreDecrement(_) => format!("(--{})", operand),
+                    Unary::PostIncrement(_) => format!("({}++)", operand),
+                    Unary::PostDecrement(_) => format!("({}--)", operand),
+                }
+            }
+            ExpressionKind::BinaryOp(binary) | ExpressionKind::CompoundAssign(binary) => {
+                let operator = binary.symbol();
+                let assign = if matches!(expr.kind, ExpressionKind::CompoundAssign(_)) {
+                    "="
+                } else {
+                    ""
+                };
+                let (left, right) = binary.operands();
+                format!(
+                    "({} {}{} {})",
+                    render(left),
+                    operator,
+                    assign,
+                    render(right)
+                )
+            }
+            ExpressionKind::FunctionCall(name, args) => {
+                let args: Vec<_> = args.iter().map(render).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => format!(
+                "({} ? {} : {})",
+                render(condition),
+                render(then),
+                render(otherwise)
+            ),
+            ExpressionKind::Subscript(array, index) => {
+                format!("{}[{}]", render(array), render(index))
+            }
+            ExpressionKind::Member(expr, member) => format!("{}.{}", render(expr), member),
+            ExpressionKind::PointerMember(expr, member) => format!("{}->{}", render(expr), member),
+        }
+    }
+
+    /// Parses `src` as the expression of a return statement
+    fn render_expression(src: &str) -> String {
+        let src = format!("int main(void) {{ return {}; }}", src);
+        let [StatementKind::Return(Some(expr))] = &body_of(&src)[..] else {
+            panic!("expected a return statement");
+        };
+        render(expr)
+    }
+
+    /// Kinds of the statements in the body of the first function in `src`
+    fn body_of(src: &str) -> Vec<StatementKind> {
+        let ast = parse(src).unwrap();
+        let Some(Declaration::Function(function)) = ast.declarations.into_iter().next() else {
+            panic!("expected a function");
+        };
+        function.body.unwrap().into_iter().map(|s| s.kind).collect()
+    }
+
+    #[test]
+    fn test_parser_dangling_else() {
+        let body = body_of("int main(void) { if (1) if (2) return 2; else return 3; }");
+
+        // The `else` belongs to the inner `if`
+        let [
+            StatementKind::If {
+                then,
+                otherwise: None,
+                ..
+            },
+        ] = &body[..]
+        else {
+            panic!("expected an `if` without `else`, got {:?}", body);
+        };
+        assert!(matches!(
+            &then.kind,
+            StatementKind::If {
+                otherwise: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parser_loops() {
+        let body = body_of(
+            "int main(void) { for (int i = 0; i; i = i - 1) continue; for (;;) break; \
+             while (1) ; do { } while (0); }",
+        );
+
+        assert!(matches!(
+            &body[0],
+            StatementKind::For { init: ForInit::Declaration(i), condition: Some(_), post: Some(_), body }
+                if i.name == "i" && body.kind == StatementKind::Continue
+        ));
+        assert!(matches!(
+            &body[1],
+            StatementKind::For { init: ForInit::Expression(None), condition: None, post: None, body }
+                if body.kind == StatementKind::Break
+        ));
+        assert!(
+            matches!(&body[2], StatementKind::While { body, .. } if body.kind == StatementKind::Null)
+        );
+        assert!(matches!(&body[3], StatementKind::DoWhile { .. }));
+    }
+
+    #[test]
+    fn test_parser_labels_and_goto() {
+        let body = body_of("int main(void) { goto end; end: return 0; }");
+
+        assert_eq!(body[0], StatementKind::Goto("end".to_string()));
+        assert!(matches!(
+            &body[1],
+            StatementKind::Labeled(label, statement)
+                if label == "end" && matches!(statement.kind, StatementKind::Return(_))
+        ));
+    }
+
+    #[test]
+    fn test_parser_declarations_are_not_statements() {
+        let src = "int main(void) {\n    if (1) int j = 1;\n    for (int i = 0; i < 3; i++) int k = i;\n    \
+                   while (1) l: int m;\n    { int n = 0; }\n    return 0;\n}";
+        let errors = parse(src).unwrap_err();
+
+        let spans: Vec<Span> = errors.iter().map(ParserError::span).collect();
+        assert_eq!(
+            spans,
+            vec![Span::new(28, 31), Span::new(71, 74), Span::new(99, 102)]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "ERROR: Expected a statement, but got a declaration"
+        );
+    }
+
+    #[test]
+    fn test_parser_error_span() {
+        let src = "int main(void) {\n    return 1 +;\n}";
+        let errors = parse(src).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span(), Span::new(31, 32));
+    }
+
+    #[test]
+    fn test_parser_lexing_error() {
+        let errors = parse("int main(void) { return 1foo; }").unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span(), Span::new(25, 28));
+        assert_eq!(
+            errors[0].to_string(),
+            "ERROR: Invalid suffix 'foo' on integer literal '1': identifiers can't start with a digit"
+        );
+    }
+
+    #[test]
+    fn test_parser_unsupported_keywords() {
+        let src = "static int a;\nint main(void) { return sizeof(int); }";
+        let errors = parse(src).unwrap_err();
+
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "ERROR: Keyword 'static' is not supported yet",
+                "ERROR: Keyword 'sizeof' is not supported yet",
+            ]
+        );
+        assert_eq!(errors[1].span(), Span::new(38, 44));
+    }
+
+    #[test]
+    fn test_parser_reports_every_error() {
+        let src = "int a = ;\nint main(void) {\n    return (1;\n    return 2 +;\n    return 3;\n}\nint = 4;\nint b = 5;";
+        let errors = parse(src).unwrap_err();
+
+        let spans: Vec<Span> = errors.iter().map(ParserError::span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(8, 9),   // `int a = ;`
+                Span::new(40, 41), // `return (1;`
+                Span::new(56, 57), // `return 2 +;`
+                Span::new(78, 79), // `int = 4;`
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_unterminated_body() {
+        let src = "int main(void) { return 0;";
+        let errors = parse(src).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(
+            matches!(&errors[0], ParserError::Expected("}", token) if token.kind == TokenKind::EOF)
+        );
+    }
+
+    #[test]
+    fn test_parser_operator_precedence() {
+        assert_eq!(
+            render_expression("a || b && c | d ^ e & f == g < h << i + j * k"),
+            "(a || (b && (c | (d ^ (e & (f == (g < (h << (i + (j * k))))))))))"
+        );
+        assert_eq!(
+            render_expression("a * b % c - d >> e >= f != g"),
+            "((((((a * b) % c) - d) >> e) >= f) != g)"
+        );
+        assert_eq!(render_expression("!-~a"), "(!(-(~a)))");
+        assert_eq!(render_expression("-a++ + --b"), "((-(a++)) + (--b))");
+    }
+
+    #[test]
+    fn test_parser_assignment_conditional_and_comma() {
+        assert_eq!(
+            render_expression("a = b += c <<= 2, d"),
+            "((a = (b += (c <<= 2))) , d)"
+        );
+        assert_eq!(
+            render_expression("a ? b, c : d ? e : f"),
+            "(a ? (b , c) : (d ? e : f))"
+        );
+        assert_eq!(render_expression("a || b ? c : d"), "((a || b) ? c : d)");
+        assert_eq!(render_expression("f(a, (b, c))"), "f(a, (b , c))");
+    }
+
+    #[test]
+    fn test_parser_postfix_operators() {
+        assert_eq!(render_expression("a[i + 1].b->c--"), "(a[(i + 1)].b->c--)");
+
+        let src = "int main(void) { return a[1]; }";
+        let [StatementKind::Return(Some(expr))] = &body_of(src)[..] else {
+            panic!("expected a return statement");
+        };
+        assert_eq!(expr.span, Span::new(24, 28));
+    }
+
+    #[test]
+    fn test_parser_string_concatenation() {
+        assert_eq!(
+            render_expression(r#"f("a\n" "b", 'c')"#),
+            r#"f("a\\nb", '99')"#
+        );
+
+        let src = r#"int main(void) { return "ab" "c"; }"#;
+        let [StatementKind::Return(Some(expr))] = &body_of(src)[..] else {
+            panic!("expected a return statement");
+        };
+        assert_eq!(expr.kind, ExpressionKind::StringLiteral(b"abc".to_vec()));
+        assert_eq!(expr.span, Span::new(24, 32));
+    }
 }
@@ -1,4 +1,4 @@
-use crate::Token;
+use crate::token::TokenKind;
 
 /// REF: https://cppreference.com/w/c/language/operator_precedence.html
 // Higher number is beater
@@ -6,30 +6,91 @@ use crate::Token;
 #[repr(u8)]
 pub enum Precedence {
     Lowest = 0,
-    Assignment = 1,     // = += -= etc.
-    Equality = 2,       // == !=
-    Relational = 3,     // < > <= >=
-    Additive = 4,       // + -
-    Multiplicative = 5, // * / %
-    Unary = 6,          // + - ! ~ (prefix)
-    Postfix = 7,        // [] () -> . (postfix)
+    Comma = 1,           // ,
+    Assignment = 2,      // = += -= etc.
+    Conditional = 3,     // ?:
+    LogicalOr = 4,       // ||
+    LogicalAnd = 5,      // &&
+    BitwiseOr = 6,       // |
+    BitwiseXor = 7,      // ^
+    BitwiseAnd = 8,      // &
+    Equality = 9,        // == !=
+    Relational = 10,     // < > <= >=
+    Shift = 11,          // << >>
+    Additive = 12,       // + -
+    Multiplicative = 13, // * / %
+    Unary = 14,          // + - ! ~ ++ -- (prefix)
+    Postfix = 15,        // [] () -> . ++ -- (postfix)
 }
 
-impl From<&Token> for Precedence {
+impl From<&TokenKind> for Precedence {
     /// Convert a token to its precedence level
-    fn from(value: &Token) -> Self {
+    fn from(value: &TokenKind) -> Self {
         match value {
-            Token::Assignment => Precedence::Assignment,
-            Token::Equal => Precedence::Equality,
-            Token::Plus | Token::Minus => Precedence::Additive,
-            Token::Asterix | Token::Slash => Precedence::Multiplicative,
+            TokenKind::Comma => Precedence::Comma,
+            TokenKind::Assignment
+            | TokenKind::PlusAssignment
+            | TokenKind::MinusAssignment
+            | TokenKind::AsterixAssignment
+            | TokenKind::SlashAssignment
+            | TokenKind::PercentAssignment
+            | TokenKind::AmpersandAssignment
+            | TokenKind::PipeAssignment
+            | TokenKind::CaretAssignment
+            | TokenKind::ShiftLeftAssignment
+            | TokenKind::ShiftRightAssignment => Precedence::Assignment,
+            TokenKind::Question => Precedence::Conditional,
+            TokenKind::LogicalOr => Precedence::LogicalOr,
+            TokenKind::LogicalAnd => Precedence::LogicalAnd,
+            TokenKind::Pipe => Precedence::BitwiseOr,
+            TokenKind::Caret => Precedence::BitwiseXor,
+            TokenKind::Ampersand => Precedence::BitwiseAnd,
+            TokenKind::Equal | TokenKind::NotEqual => Precedence::Equality,
+            TokenKind::Less
+            | TokenKind::Greater
+            | TokenKind::LessEqual
+            | TokenKind::GreaterEqual => Precedence::Relational,
+            TokenKind::ShiftLeft | TokenKind::ShiftRight => Precedence::Shift,
+            TokenKind::Plus | TokenKind::Minus => Precedence::Additive,
+            TokenKind::Asterix | TokenKind::Slash | TokenKind::Percent => {
+                Precedence::Multiplicative
+            }
+            TokenKind::Increment
+            | TokenKind::Decrement
+            | TokenKind::OpenBracket
+            | TokenKind::Dot
+            | TokenKind::Arrow => Precedence::Postfix,
             _ => Precedence::Lowest,
         }
     }
 }
 
 impl Precedence {
-    pub fn is_right_associative(token: &Token) -> bool {
-        matches!(token, Token::Assignment)
+    /// The next lower precedence level
+    pub fn lower(self) -> Precedence {
+        match self {
+            Precedence::Lowest | Precedence::Comma => Precedence::Lowest,
+            Precedence::Assignment => Precedence::Comma,
+            Precedence::Conditional => Precedence::Assignment,
+            Precedence::LogicalOr => Precedence::Conditional,
+            Precedence::LogicalAnd => Precedence::LogicalOr,
+            Precedence::BitwiseOr => Precedence::LogicalAnd,
+            Precedence::BitwiseXor => Precedence::BitwiseOr,
+            Precedence::BitwiseAnd => Precedence::BitwiseXor,
+            Precedence::Equality => Precedence::BitwiseAnd,
+            Precedence::Relational => Precedence::Equality,
+            Precedence::Shift => Precedence::Relational,
+            Precedence::Additive => Precedence::Shift,
+            Precedence::Multiplicative => Precedence::Additive,
+            Precedence::Unary => Precedence::Multiplicative,
+            Precedence::Postfix => Precedence::Unary,
+        }
+    }
+
+    pub fn is_right_associative(token: &TokenKind) -> bool {
+        matches!(
+            Precedence::from(token),
+            Precedence::Assignment | Precedence::Conditional
+        )
     }
 }
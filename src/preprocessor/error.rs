@@ -0,0 +1,204 @@
+use std::{fmt, io};
+
+use crate::span::Span;
+
+pub type PreprocessorResult<T> = Result<T, PreprocessorError>;
+
+#[derive(Debug)]
+pub enum PreprocessorError {
+    /// A `#` at the start of a line followed by something that isn't a directive
+    InvalidDirective(String, Span),
+    UnsupportedDirective(String, Span),
+    ExpectedHeaderName(Span),
+    /// Header name as written and why it couldn't be read
+    IncludeFailed(String, io::Error, Span),
+    IncludeTooDeep(Span),
+    ExpectedMacroName(Span),
+    /// A `(` after a function-like macro name without a matching parameter list
+    InvalidParameterList(Span),
+    DuplicateParameter(String, Span),
+    /// A `#` in a function-like macro that isn't followed by a parameter
+    StringizeNotParameter(Span),
+    PasteAtEdge(Span),
+    /// `__VA_ARGS__` or `__VA_OPT__` outside a variadic macro
+    VaArgsNotAllowed(String, Span),
+    InvalidVaOpt(Span),
+    UnterminatedArguments(String, Span),
+    /// Macro name, number of named parameters, whether it is variadic and number of arguments
+    ArgumentCount(String, usize, bool, usize, Span),
+    /// Spellings of the two operands of `##`
+    InvalidPaste(String, String, Span),
+    /// Name of the directive
+    MissingExpression(&'static str, Span),
+    /// What was expected, e.g. `')'`
+    ExpectedInExpression(&'static str, Span),
+    /// A token that has no meaning in `#if`, like a string or `=`
+    UnexpectedInExpression(Span),
+    /// A token the lexer rejected, with its message, e.g. an integer constant out of range
+    InvalidToken(String, Span),
+    DivisionByZero(Span),
+    /// `#elif`, `#else` or `#endif` with no open `#if`
+    UnmatchedConditional(&'static str, Span),
+    /// `#elif` or `#else` after the `#else` of the same `#if`
+    AfterElse(&'static str, Span),
+    /// An `#if` still open at the end of its file
+    UnterminatedConditional(Span),
+    /// `#error` and its message
+    ErrorDirective(String, Span),
+    /// `#warning` and its message, the only diagnostic that doesn't stop compilation
+    WarningDirective(String, Span),
+    /// A macro defined again with a different replacement list
+    MacroRedefined(String, Span),
+    /// Name of the directive
+    ExtraTokens(&'static str, Span),
+}
+
+impl PreprocessorError {
+    /// Location in the source the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            PreprocessorError::InvalidDirective(_, span)
+            | PreprocessorError::UnsupportedDirective(_, span)
+            | PreprocessorError::ExpectedHeaderName(span)
+            | PreprocessorError::IncludeFailed(_, _, span)
+            | PreprocessorError::IncludeTooDeep(span)
+            | PreprocessorError::ExpectedMacroName(span)
+            | PreprocessorError::InvalidParameterList(span)
+            | PreprocessorError::DuplicateParameter(_, span)
+            | PreprocessorError::StringizeNotParameter(span)
+            | PreprocessorError::PasteAtEdge(span)
+            | PreprocessorError::VaArgsNotAllowed(_, span)
+            | PreprocessorError::InvalidVaOpt(span)
+            | PreprocessorError::UnterminatedArguments(_, span)
+            | PreprocessorError::ArgumentCount(_, _, _, _, span)
+            | PreprocessorError::InvalidPaste(_, _, span)
+            | PreprocessorError::MissingExpression(_, span)
+            | PreprocessorError::ExpectedInExpression(_, span)
+            | PreprocessorError::UnexpectedInExpression(span)
+            | PreprocessorError::InvalidToken(_, span)
+            | PreprocessorError::DivisionByZero(span)
+            | PreprocessorError::UnmatchedConditional(_, span)
+            | PreprocessorError::AfterElse(_, span)
+            | PreprocessorError::UnterminatedConditional(span)
+            | PreprocessorError::ErrorDirective(_, span)
+            | PreprocessorError::WarningDirective(_, span)
+            | PreprocessorError::MacroRedefined(_, span)
+            | PreprocessorError::ExtraTokens(_, span) => *span,
+        }
+    }
+
+    /// Whether this is only a warning, which doesn't fail compilation
+    pub fn is_warning(&self) -> bool {
+        matches!(self, PreprocessorError::WarningDirective(..))
+    }
+}
+
+impl fmt::Display for PreprocessorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessorError::InvalidDirective(name, _) => {
+                write!(f, "ERROR: Invalid preprocessing directive '#{}'", name)
+            }
+            PreprocessorError::UnsupportedDirective(name, _) => {
+                write!(f, "ERROR: Directive '#{}' is not supported yet", name)
+            }
+            PreprocessorError::ExpectedHeaderName(_) => {
+                write!(f, "ERROR: #include expects \"FILENAME\" or <FILENAME>")
+            }
+            PreprocessorError::IncludeFailed(name, e, _) if e.kind() == io::ErrorKind::NotFound => {
+                write!(f, "ERROR: '{}' file not found", name)
+            }
+            PreprocessorError::IncludeFailed(name, e, _) => {
+                write!(f, "ERROR: Cannot include '{}': {}", name, e)
+            }
+            PreprocessorError::IncludeTooDeep(_) => write!(f, "ERROR: #include nested too deeply"),
+            PreprocessorError::ExpectedMacroName(_) => {
+                write!(f, "ERROR: Macro names must be identifiers")
+            }
+            PreprocessorError::InvalidParameterList(_) => {
+                write!(f, "ERROR: Invalid macro parameter list")
+            }
+            PreprocessorError::DuplicateParameter(name, _) => {
+                write!(f, "ERROR: Duplicate macro parameter '{}'", name)
+            }
+            PreprocessorError::StringizeNotParameter(_) => {
+                write!(f, "ERROR: '#' is not followed by a macro parameter")
+            }
+            PreprocessorError::PasteAtEdge(_) => {
+                write!(
+                    f,
+                    "ERROR: '##' cannot appear at either end of a macro expansion"
+                )
+            }
+            PreprocessorError::VaArgsNotAllowed(name, _) => {
+                write!(
+                    f,
+                    "ERROR: '{}' can only appear in the expansion of a variadic macro",
+                    name
+                )
+            }
+            PreprocessorError::InvalidVaOpt(_) => {
+                write!(
+                    f,
+                    "ERROR: __VA_OPT__ must be followed by a parenthesized list"
+                )
+            }
+            PreprocessorError::UnterminatedArguments(name, _) => {
+                write!(
+                    f,
+                    "ERROR: Unterminated argument list invoking macro '{}'",
+                    name
+                )
+            }
+            PreprocessorError::ArgumentCount(name, params, variadic, args, _) => {
+                write!(
+                    f,
+                    "ERROR: Macro '{}' requires {}{} arguments, but {} given",
+                    name,
+                    if *variadic { "at least " } else { "" },
+                    params,
+                    args
+                )
+            }
+            PreprocessorError::InvalidPaste(lhs, rhs, _) => {
+                write!(
+                    f,
+                    "ERROR: Pasting '{}' and '{}' does not give a valid preprocessing token",
+                    lhs, rhs
+                )
+            }
+            PreprocessorError::MacroRedefined(name, _) => {
+                write!(f, "ERROR: Macro '{}' redefined differently", name)
+            }
+            PreprocessorError::MissingExpression(directive, _) => {
+                write!(f, "ERROR: #{} with no expression", directive)
+            }
+            PreprocessorError::ExpectedInExpression(expected, _) => {
+                write!(f, "ERROR: Expected {} in #if", expected)
+            }
+            PreprocessorError::UnexpectedInExpression(_) => {
+                write!(f, "ERROR: Token is not valid in #if expressions")
+            }
+            PreprocessorError::InvalidToken(message, _) => write!(f, "ERROR: {}", message),
+            PreprocessorError::DivisionByZero(_) => write!(f, "ERROR: Division by zero in #if"),
+            PreprocessorError::UnmatchedConditional(directive, _) => {
+                write!(f, "ERROR: #{} without #if", directive)
+            }
+            PreprocessorError::AfterElse(directive, _) => {
+                write!(f, "ERROR: #{} after #else", directive)
+            }
+            PreprocessorError::UnterminatedConditional(_) => {
+                write!(f, "ERROR: Unterminated conditional directive")
+            }
+            PreprocessorError::ErrorDirective(message, _) => {
+                write!(f, "ERROR: #error {}", message)
+            }
+            PreprocessorError::WarningDirective(message, _) => {
+                write!(f, "WARNING: #warning {}", message)
+            }
+            PreprocessorError::ExtraTokens(directive, _) => {
+                write!(f, "ERROR: Extra tokens at end of #{} directive", directive)
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{
+    IntegerType, Token, TokenKind,
+    parser::precedence::Precedence,
+    preprocessor::{PreprocessorError, PreprocessorResult, macro_name, macros::PpToken},
+    span::Span,
+};
+
+/// Value of an `#if` expression, where every integer acts as `intmax_t` or `uintmax_t`
+/// (C17 6.10.1p4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Value {
+    bits: u64,
+    unsigned: bool,
+}
+
+impl Value {
+    fn signed(value: i64) -> Self {
+        Self {
+            bits: value as u64,
+            unsigned: false,
+        }
+    }
+
+    /// 1 or 0, as an `int`
+    fn truth(value: bool) -> Self {
+        Self::signed(value as i64)
+    }
+
+    pub fn is_true(self) -> bool {
+        self.bits != 0
+    }
+}
+
+/// Evaluates the macro expanded tokens of an `#if` or `#elif`. `end` is where the line ends, for
+/// errors about missing tokens
+pub(super) fn evaluate(tokens: &[PpToken], end: Span) -> PreprocessorResult<Value> {
+    let mut evaluator = Evaluator {
+        tokens,
+        pos: 0,
+        end,
+    };
+    let value = evaluator.expression(Precedence::Lowest, true)?;
+    match evaluator.peek() {
+        Some(extra) => Err(PreprocessorError::UnexpectedInExpression(extra.span)),
+        None => Ok(value),
+    }
+}
+
+struct Evaluator<'t> {
+    tokens: &'t [PpToken],
+    pos: usize,
+    end: Span,
+}
+
+impl Evaluator<'_> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).map(|token| token.token)
+    }
+
+    fn next(&mut self) -> PreprocessorResult<Token> {
+        let token = self.peek().ok_or(PreprocessorError::ExpectedInExpression(
+            "an expression",
+            self.end,
+        ))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &'static str) -> PreprocessorResult<()> {
+        match self.peek() {
+            Some(token) if token.kind == kind => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => Err(PreprocessorError::ExpectedInExpression(
+                expected, token.span,
+            )),
+            None => Err(PreprocessorError::ExpectedInExpression(expected, self.end)),
+        }
+    }
+
+    /// `evaluated` is false in the operands `&&`, `||` and `?:` skip, where dividing by zero is
+    /// not an error
+    fn expression(&mut self, precedence: Precedence, evaluated: bool) -> PreprocessorResult<Value> {
+        let mut left = self.prefix(evaluated)?;
+        while let Some(operator) = self.peek()
+            && precedence < Precedence::from(&operator.kind)
+        {
+            left = self.infix(left, operator, evaluated)?;
+        }
+        Ok(left)
+    }
+
+    fn prefix(&mut self, evaluated: bool) -> PreprocessorResult<Value> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::IntegerLiteral(value, ty) => Ok(Value {
+                bits: value,
+                unsigned: matches!(
+                    ty,
+                    IntegerType::UnsignedInt
+                        | IntegerType::UnsignedLong
+                        | IntegerType::UnsignedLongLong
+                ),
+            }),
+            TokenKind::CharacterLiteral(value) => Ok(Value::signed(value as i64)),
+            // Identifiers left after macro expansion are 0, keywords included
+            kind if macro_name(kind).is_some() => Ok(Value::signed(0)),
+            TokenKind::OpenParen => {
+                let value = self.expression(Precedence::Lowest, evaluated)?;
+                self.expect(TokenKind::CloseParen, "')'")?;
+                Ok(value)
+            }
+            TokenKind::Plus => self.expression(Precedence::Unary, evaluated),
+            TokenKind::Minus => {
+                let value = self.expression(Precedence::Unary, evaluated)?;
+                Ok(Value {
+                    bits: value.bits.wrapping_neg(),
+                    ..value
+                })
+            }
+            TokenKind::Tilde => {
+                let value = self.expression(Precedence::Unary, evaluated)?;
+                Ok(Value {
+                    bits: !value.bits,
+                    ..value
+                })
+            }
+            TokenKind::Bang => {
+                let value = self.expression(Precedence::Unary, evaluated)?;
+                Ok(Value::truth(!value.is_true()))
+            }
+            // Reported with the lexer's message, e.g. for a constant too large for `uintmax_t`
+            TokenKind::Error(message, _) => Err(PreprocessorError::InvalidToken(
+                message.to_string(),
+                token.error_span(),
+            )),
+            _ => Err(PreprocessorError::UnexpectedInExpression(token.span)),
+        }
+    }
+
+    fn infix(
+        &mut self,
+        left: Value,
+        operator: Token,
+        evaluated: bool,
+    ) -> PreprocessorResult<Value> {
+        self.pos += 1;
+        let precedence = Precedence::from(&operator.kind);
+        match operator.kind {
+            TokenKind::LogicalAnd => {
+                let right = self.expression(precedence, evaluated && left.is_true())?;
+                return Ok(Value::truth(left.is_true() && right.is_true()));
+            }
+            TokenKind::LogicalOr => {
+                let right = self.expression(precedence, evaluated && !left.is_true())?;
+                return Ok(Value::truth(left.is_true() || right.is_true()));
+            }
+            TokenKind::Question => {
+                let then = self.expression(Precedence::Lowest, evaluated && left.is_true())?;
+                self.expect(TokenKind::Colon, "':'")?;
+                let otherwise =
+                    self.expression(precedence.lower(), evaluated && !left.is_true())?;
+                let chosen = if left.is_true() { then } else { otherwise };
+                // Both operands convert to their common type
+                return Ok(Value {
+                    bits: chosen.bits,
+                    unsigned: then.unsigned || otherwise.unsigned,
+                });
+            }
+            TokenKind::Comma => return self.expression(precedence, evaluated),
+            _ => {}
+        }
+
+        let right = self.expression(precedence, evaluated)?;
+        // The usual arithmetic conversions: unsigned if either operand is
+        let unsigned = left.unsigned || right.unsigned;
+        let (l, r) = (left.bits, right.bits);
+        let arithmetic = |bits| Value { bits, unsigned };
+        let compare = |ordering: std::cmp::Ordering| {
+            if unsigned {
+                l.cmp(&r) == ordering
+            } else {
+                (l as i64).cmp(&(r as i64)) == ordering
+            }
+        };
+
+        let value = match operator.kind {
+            TokenKind::Plus => arithmetic(l.wrapping_add(r)),
+            TokenKind::Minus => arithmetic(l.wrapping_sub(r)),
+            TokenKind::Asterix => arithmetic(l.wrapping_mul(r)),
+            TokenKind::Slash | TokenKind::Percent if r == 0 => {
+                if evaluated {
+                    return Err(PreprocessorError::DivisionByZero(operator.span));
+                }
+                arithmetic(0)
+            }
+            TokenKind::Slash if unsigned => arithmetic(l / r),
+            TokenKind::Slash => arithmetic((l as i64).wrapping_div(r as i64) as u64),
+            TokenKind::Percent if unsigned => arithmetic(l % r),
+            TokenKind::Percent => arithmetic((l as i64).wrapping_rem(r as i64) as u64),
+            // Shifts have the type of their left operand, shifting everything out gives 0 (or -1)
+            TokenKind::ShiftLeft => Value {
+                bits: shift_amount(right).map_or(0, |amount| l << amount),
+                unsigned: left.unsigned,
+            },
+            TokenKind::ShiftRight => {
+                let bits = match shift_amount(right) {
+                    Some(amount) if left.unsigned => l >> amount,
+                    Some(amount) => ((l as i64) >> amount) as u64,
+                    None if left.unsigned || (l as i64) >= 0 => 0,
+                    None => u64::MAX,
+                };
+                Value {
+                    bits,
+                    unsigned: left.unsigned,
+                }
+            }
+            TokenKind::Less => Value::truth(compare(std::cmp::Ordering::Less)),
+            TokenKind::Greater => Value::truth(compare(std::cmp::Ordering::Greater)),
+            TokenKind::LessEqual => Value::truth(!compare(std::cmp::Ordering::Greater)),
+            TokenKind::GreaterEqual => Value::truth(!compare(std::cmp::Ordering::Less)),
+            TokenKind::Equal => Value::truth(l == r),
+            TokenKind::NotEqual => Value::truth(l != r),
+            TokenKind::Ampersand => arithmetic(l & r),
+            TokenKind::Pipe => arithmetic(l | r),
+            TokenKind::Caret => arithmetic(l ^ r),
+            // Assignments, `++`, `[` and the like
+            _ => return Err(PreprocessorError::UnexpectedInExpression(operator.span)),
+        };
+        Ok(value)
+    }
+}
+
+/// The amount a shift by `right` moves bits, `None` if it moves all of them out
+fn shift_amount(right: Value) -> Option<u32> {
+    if !right.unsigned && (right.bits as i64) < 0 {
+        return None;
+    }
+    u32::try_from(right.bits).ok().filter(|&amount| amount < 64)
+}
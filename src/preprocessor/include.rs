@@ -0,0 +1,82 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Where gcc installs its own headers (`stddef.h`, `stdarg.h`...), one directory per version
+const GCC_LIB_DIR: &str = "/usr/lib/gcc/x86_64-linux-gnu";
+
+/// The system directories gcc searches after its own on x86-64 Linux
+const SYSTEM_INCLUDE_DIRS: &[&str] = &[
+    "/usr/local/include",
+    "/usr/include/x86_64-linux-gnu",
+    "/usr/include",
+];
+
+/// Directories `#include` searches, in gcc's order: `#include "..."` looks next to the including
+/// file, then in `quote`, `user` and `system`. `#include <...>` starts at `user`
+#[derive(Debug, Clone, Default)]
+pub struct IncludePaths {
+    /// `-iquote` directories
+    pub quote: Vec<PathBuf>,
+    /// `-I` directories
+    pub user: Vec<PathBuf>,
+    /// `-isystem` directories then the default ones. Headers found there are system headers
+    pub system: Vec<PathBuf>,
+}
+
+/// A directory of the search list
+#[derive(Debug)]
+pub(super) struct SearchDir {
+    pub path: PathBuf,
+    pub system: bool,
+}
+
+impl IncludePaths {
+    /// Adds the directories gcc searches by default, those of the newest gcc installed first
+    pub fn with_default_system_dirs(mut self) -> Self {
+        self.system.extend(gcc_include_dir());
+        self.system
+            .extend(SYSTEM_INCLUDE_DIRS.iter().map(PathBuf::from));
+        self
+    }
+
+    /// The whole search list, and the index where `#include <...>` starts in it
+    pub(super) fn search_dirs(self) -> (Vec<SearchDir>, usize) {
+        let bracket_start = self.quote.len();
+        let dir = |system| move |path| SearchDir { path, system };
+        let dirs = self
+            .quote
+            .into_iter()
+            .map(dir(false))
+            .chain(self.user.into_iter().map(dir(false)))
+            .chain(self.system.into_iter().map(dir(true)))
+            .collect();
+        (dirs, bracket_start)
+    }
+}
+
+/// `include` in the directory of the newest gcc version, e.g.
+/// `/usr/lib/gcc/x86_64-linux-gnu/12/include`
+fn gcc_include_dir() -> Option<PathBuf> {
+    let versions = fs::read_dir(GCC_LIB_DIR).ok()?;
+    versions
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let version = entry.file_name().to_str()?.to_string();
+            let numbers = version
+                .split('.')
+                .map(|number| number.parse::<u32>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            let include = entry.path().join("include");
+            include.is_dir().then_some((numbers, include))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, include)| include)
+}
+
+/// The path a file is known by for `#pragma once` and include guards, the same through any
+/// symbolic link or `..`
+pub(super) fn file_identity(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,713 @@
+use std::{mem, path::PathBuf, rc::Rc};
+
+use crate::{
+    Token, TokenKind,
+    intern::{FxHashMap, Interned},
+    lexer::{Lexer, Spacing},
+    preprocessor::{
+        Preprocessor, PreprocessorError, PreprocessorResult, expect_end, macro_name,
+        predefined::Builtin,
+    },
+    span::Span,
+};
+
+/// A token inside the preprocessor: what the parser gets, plus what macro expansion needs
+#[derive(Debug, Clone, Copy)]
+pub(super) struct PpToken {
+    pub token: Token,
+    pub spacing: Spacing,
+    /// Macros this token came out of, which it must not expand again
+    pub hideset: HideSet,
+}
+
+impl PpToken {
+    pub fn new(token: Token, spacing: Spacing) -> Self {
+        Self {
+            token,
+            spacing,
+            hideset: HideSet::default(),
+        }
+    }
+}
+
+/// An object-like or function-like macro (C17 6.10.3)
+pub(super) struct Macro {
+    /// Parameters of a function-like macro, ending with `__VA_ARGS__` if it is variadic
+    params: Option<Vec<Interned>>,
+    variadic: bool,
+    /// The replacement list as written, to compare redefinitions
+    tokens: Vec<PpToken>,
+    body: Vec<Replacement>,
+    /// Set for `__LINE__` and the like, which have no replacement list
+    builtin: Option<Builtin>,
+}
+
+impl Macro {
+    pub fn builtin(builtin: Builtin) -> Self {
+        Self {
+            params: None,
+            variadic: false,
+            tokens: Vec::new(),
+            body: Vec::new(),
+            builtin: Some(builtin),
+        }
+    }
+}
+
+/// An element of a replacement list, with the operators and parameters found at definition
+#[derive(Debug, Clone)]
+enum Replacement {
+    Token(PpToken),
+    /// A parameter, by index, and the spacing before it
+    Param(usize, Spacing),
+    /// `#` and a parameter
+    Stringize(usize, Spacing),
+    /// `##` between the items around it
+    Paste,
+    /// `__VA_OPT__(...)`, kept only if the variable arguments are not empty (C23 6.10.5.2)
+    VaOpt(Vec<Replacement>, Spacing),
+    /// `# __VA_OPT__(...)`
+    StringizeVaOpt(Vec<Replacement>, Spacing),
+}
+
+/// Arguments of a function-like macro invocation
+#[derive(Default)]
+struct Arguments {
+    raw: Vec<Vec<PpToken>>,
+    /// Fully macro expanded arguments, computed the first time they are needed
+    expanded: Vec<Option<Vec<PpToken>>>,
+}
+
+/// Set of macro names, interned in `HideSets`. A macro adds its name to the tokens it expands to,
+/// so that it is not expanded again inside its own expansion: those tokens are "painted blue"
+/// for good (C17 6.10.3.4p2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub(super) struct HideSet(u32);
+
+pub(super) struct HideSets {
+    /// Names in each set, the empty set is the first
+    sets: Vec<Vec<Interned>>,
+    /// Already built results of `with`
+    unions: FxHashMap<(HideSet, Interned), HideSet>,
+}
+
+impl HideSets {
+    pub fn new() -> Self {
+        Self {
+            sets: vec![Vec::new()],
+            unions: FxHashMap::default(),
+        }
+    }
+
+    pub fn contains(&self, set: HideSet, name: Interned) -> bool {
+        self.sets[set.0 as usize].contains(&name)
+    }
+
+    /// `set` with `name` added
+    pub fn with(&mut self, set: HideSet, name: Interned) -> HideSet {
+        if self.contains(set, name) {
+            return set;
+        }
+        if let Some(&union) = self.unions.get(&(set, name)) {
+            return union;
+        }
+
+        let mut names = self.sets[set.0 as usize].clone();
+        names.push(name);
+        let union = HideSet(self.sets.len() as u32);
+        self.sets.push(names);
+        self.unions.insert((set, name), union);
+        union
+    }
+
+    pub fn union(&mut self, a: HideSet, b: HideSet) -> HideSet {
+        let names = self.sets[b.0 as usize].clone();
+        names.into_iter().fold(a, |set, name| self.with(set, name))
+    }
+
+    pub fn intersection(&mut self, a: HideSet, b: HideSet) -> HideSet {
+        let names: Vec<Interned> = self.sets[a.0 as usize]
+            .iter()
+            .copied()
+            .filter(|&name| self.contains(b, name))
+            .collect();
+        names
+            .into_iter()
+            .fold(HideSet::default(), |set, name| self.with(set, name))
+    }
+}
+
+impl Preprocessor<'_> {
+    /// `#define NAME replacement-list` or `#define NAME(params) replacement-list`
+    pub(super) fn define(&mut self, args: &[PpToken], directive: Span) -> PreprocessorResult<()> {
+        let (name, span) = macro_name_arg(args, directive)?;
+
+        // A function-like macro has its `(` right after the name
+        let (params, variadic, tokens) = match args.get(1) {
+            Some(paren)
+                if paren.token.kind == TokenKind::OpenParen && !paren.spacing.leading_space =>
+            {
+                let (params, variadic, len) = parse_params(&args[2..], paren.token.span)?;
+                (Some(params), variadic, &args[2 + len..])
+            }
+            _ => (None, false, &args[1..]),
+        };
+        let body = replacement_list(tokens, params.as_deref(), variadic)?;
+
+        // A redefinition must be the same, down to the whitespace between tokens (C17 6.10.3p2)
+        if let Some(previous) = self.macros.get(&name) {
+            let same = previous.params == params
+                && previous.tokens.len() == tokens.len()
+                && previous
+                    .tokens
+                    .iter()
+                    .zip(tokens)
+                    .enumerate()
+                    .all(|(i, (a, b))| {
+                        self.sources.text(a.token.span) == self.sources.text(b.token.span)
+                            && (i == 0 || a.spacing.leading_space == b.spacing.leading_space)
+                    });
+            if !same {
+                return Err(PreprocessorError::MacroRedefined(name.to_string(), span));
+            }
+        }
+
+        let definition = Macro {
+            params,
+            variadic,
+            tokens: tokens.to_vec(),
+            body,
+            builtin: None,
+        };
+        self.macros.insert(name, Rc::new(definition));
+        Ok(())
+    }
+
+    /// `#undef NAME`
+    pub(super) fn undef(&mut self, args: &[PpToken], directive: Span) -> PreprocessorResult<()> {
+        let (name, _) = macro_name_arg(args, directive)?;
+        expect_end(&args[1..], "undef")?;
+        self.macros.remove(&name);
+        Ok(())
+    }
+
+    /// If `token` names a macro it can expand, queues the expansion to be rescanned. A
+    /// function-like macro name that isn't followed by `(` is left alone
+    pub(super) fn expand(&mut self, token: PpToken) -> bool {
+        let Some(name) = macro_name(token.token.kind) else {
+            return false;
+        };
+        if self.hidesets.contains(token.hideset, name) {
+            return false;
+        }
+        let Some(definition) = self.macros.get(&name) else {
+            return false;
+        };
+        let definition = Rc::clone(definition);
+
+        if let Some(builtin) = definition.builtin {
+            let text = self.builtin_expansion(builtin);
+            let (expanded, _) = self.scratch_token(text);
+            let mut expanded = PpToken::new(expanded, token.spacing);
+            expanded.hideset = self.hidesets.with(token.hideset, name);
+            self.pending.push_front(expanded);
+            return true;
+        }
+
+        let (hideset, mut args) = match &definition.params {
+            None => (
+                self.hidesets.with(token.hideset, name),
+                Arguments::default(),
+            ),
+            Some(params) => {
+                let paren = self.read_token();
+                if paren.token.kind != TokenKind::OpenParen {
+                    self.pending.push_front(paren);
+                    return false;
+                }
+                let (raw, close) = match self.collect_arguments(
+                    name,
+                    params.len(),
+                    definition.variadic,
+                    token.token.span,
+                ) {
+                    Ok(args) => args,
+                    Err(e) => {
+                        // The invocation is dropped
+                        self.errors.push(e);
+                        return true;
+                    }
+                };
+                // Only names hidden for both the macro name and the `)` stay hidden, the other
+                // tokens of the invocation could come from another expansion (Prosser's algorithm)
+                let hideset = self.hidesets.intersection(token.hideset, close.hideset);
+                let expanded = vec![None; raw.len()];
+                (
+                    self.hidesets.with(hideset, name),
+                    Arguments { raw, expanded },
+                )
+            }
+        };
+
+        let mut expansion: Vec<PpToken> = self
+            .substitute(&definition.body, &mut args)
+            .into_iter()
+            .flatten()
+            .collect();
+        for (i, expanded) in expansion.iter_mut().enumerate() {
+            expanded.hideset = self.hidesets.union(expanded.hideset, hideset);
+            // The expansion takes the place of the name, and is on its line
+            expanded.spacing = if i == 0 {
+                token.spacing
+            } else {
+                Spacing {
+                    line_start: false,
+                    leading_space: expanded.spacing.leading_space || expanded.spacing.line_start,
+                }
+            };
+        }
+        for expanded in expansion.into_iter().rev() {
+            self.pending.push_front(expanded);
+        }
+        true
+    }
+
+    /// The spelling `__LINE__` and the like expand to, for where the preprocessor is in the files
+    fn builtin_expansion(&mut self, builtin: Builtin) -> String {
+        let location = self.sources.location(self.position.start);
+        match builtin {
+            Builtin::Line => location.line.to_string(),
+            Builtin::File => {
+                let mut text = String::from("\"");
+                for ch in location.path.display().to_string().chars() {
+                    if ch == '"' || ch == '\\' {
+                        text.push('\\');
+                    }
+                    text.push(ch);
+                }
+                text.push('"');
+                text
+            }
+            Builtin::Date => self.date.clone(),
+            Builtin::Time => self.time.clone(),
+            Builtin::Counter => {
+                self.counter += 1;
+                (self.counter - 1).to_string()
+            }
+        }
+    }
+
+    /// Macro expands `tokens` on their own, as the arguments of a macro or a directive line
+    pub(super) fn fully_expand(&mut self, tokens: &[PpToken]) -> Vec<PpToken> {
+        // An `EOF` at the end keeps function-like macros from reading past the tokens
+        let end = tokens.last().map_or(0, |token| token.token.span.end);
+        let eof = Token::new(TokenKind::EOF, Span::new(end, end));
+        let outer = mem::replace(&mut self.pending, tokens.iter().copied().collect());
+        self.pending
+            .push_back(PpToken::new(eof, Spacing::default()));
+
+        let mut expanded = Vec::new();
+        loop {
+            let token = self.expanded_token();
+            if token.token.kind == TokenKind::EOF {
+                break;
+            }
+            expanded.push(token);
+        }
+        self.pending = outer;
+        expanded
+    }
+
+    /// Reads the arguments of an invocation after its `(`. Returns them and the closing `)`
+    fn collect_arguments(
+        &mut self,
+        name: Interned,
+        params: usize,
+        variadic: bool,
+        span: Span,
+    ) -> PreprocessorResult<(Vec<Vec<PpToken>>, PpToken)> {
+        let mut args = vec![Vec::new()];
+        let mut depth = 0;
+        let close = loop {
+            let token = self.read_token();
+            match token.token.kind {
+                TokenKind::EOF => {
+                    self.pending.push_front(token);
+                    return Err(PreprocessorError::UnterminatedArguments(
+                        name.to_string(),
+                        span,
+                    ));
+                }
+                TokenKind::OpenParen => depth += 1,
+                TokenKind::CloseParen if depth == 0 => break token,
+                TokenKind::CloseParen => depth -= 1,
+                // The variable arguments keep their commas
+                TokenKind::Comma if depth == 0 && !(variadic && args.len() == params) => {
+                    args.push(Vec::new());
+                    continue;
+                }
+                _ => {}
+            }
+            args.last_mut().unwrap().push(token);
+        };
+
+        // `f()` passes no argument to a macro without parameters
+        if params == 0 && args.len() == 1 && args[0].is_empty() {
+            args.clear();
+        }
+        // The variable arguments can be left out, commas included (C23 6.10.5p12)
+        if variadic && args.len() + 1 == params {
+            args.push(Vec::new());
+        }
+        if args.len() != params {
+            let named = params - variadic as usize;
+            return Err(PreprocessorError::ArgumentCount(
+                name.to_string(),
+                named,
+                variadic,
+                args.len(),
+                span,
+            ));
+        }
+        Ok((args, close))
+    }
+
+    /// Replaces the parameters of a replacement list and applies `#` and `##` (C17 6.10.3.1 to
+    /// 6.10.3.3). `None` is a placemarker, which `##` with an empty argument leaves
+    fn substitute(&mut self, items: &[Replacement], args: &mut Arguments) -> Vec<Option<PpToken>> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < items.len() {
+            match &items[i] {
+                Replacement::Paste => {
+                    i += 1;
+                    let mut rhs = self.operand(&items[i], args).into_iter();
+                    let lhs = output.pop().expect("`##` is never first");
+                    let pasted = self.paste(lhs, rhs.next().flatten());
+                    output.extend(pasted);
+                    output.extend(rhs);
+                }
+                // The operands of `##` are not macro expanded
+                item if matches!(items.get(i + 1), Some(Replacement::Paste)) => {
+                    let operand = self.operand(item, args);
+                    output.extend(operand);
+                }
+                Replacement::Param(index, spacing) => {
+                    let expanded = self.expanded_argument(args, *index);
+                    output.extend(with_spacing(expanded, *spacing).into_iter().map(Some));
+                }
+                item => {
+                    let operand = self.operand(item, args);
+                    output.extend(operand);
+                }
+            }
+            i += 1;
+        }
+        output
+    }
+
+    /// An item of a replacement list without macro expanding arguments. Never empty, nothing
+    /// is a placemarker
+    fn operand(&mut self, item: &Replacement, args: &mut Arguments) -> Vec<Option<PpToken>> {
+        match item {
+            Replacement::Token(token) => vec![Some(*token)],
+            Replacement::Param(index, _) if args.raw[*index].is_empty() => vec![None],
+            Replacement::Param(index, spacing) => {
+                let raw = with_spacing(args.raw[*index].clone(), *spacing);
+                raw.into_iter().map(Some).collect()
+            }
+            Replacement::Stringize(index, spacing) => {
+                let raw = args.raw[*index].clone();
+                vec![Some(self.stringize(&raw, *spacing))]
+            }
+            Replacement::VaOpt(content, spacing) => {
+                let mut output = self.va_opt(content, args, *spacing);
+                if output.is_empty() {
+                    output.push(None);
+                }
+                output
+            }
+            Replacement::StringizeVaOpt(content, spacing) => {
+                let tokens: Vec<PpToken> = self
+                    .va_opt(content, args, Spacing::default())
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                vec![Some(self.stringize(&tokens, *spacing))]
+            }
+            Replacement::Paste => unreachable!("`##` is never an operand of `##`"),
+        }
+    }
+
+    /// The content of `__VA_OPT__(...)` if the variable arguments expand to something
+    fn va_opt(
+        &mut self,
+        content: &[Replacement],
+        args: &mut Arguments,
+        spacing: Spacing,
+    ) -> Vec<Option<PpToken>> {
+        let va_args = args.raw.len() - 1;
+        if self.expanded_argument(args, va_args).is_empty() {
+            return Vec::new();
+        }
+
+        let mut output = self.substitute(content, args);
+        if let Some(Some(first)) = output.iter_mut().find(|token| token.is_some()) {
+            first.spacing = spacing;
+        }
+        output
+    }
+
+    fn expanded_argument(&mut self, args: &mut Arguments, index: usize) -> Vec<PpToken> {
+        if args.expanded[index].is_none() {
+            let raw = args.raw[index].clone();
+            args.expanded[index] = Some(self.fully_expand(&raw));
+        }
+        args.expanded[index].clone().unwrap()
+    }
+
+    /// `#`: the spelling of `tokens` as a string literal, with the whitespace between them
+    /// turned into single spaces (C17 6.10.3.2)
+    fn stringize(&mut self, tokens: &[PpToken], spacing: Spacing) -> PpToken {
+        let mut text = String::from("\"");
+        for (i, token) in tokens.iter().enumerate() {
+            if i > 0 && (token.spacing.leading_space || token.spacing.line_start) {
+                text.push(' ');
+            }
+            let spelling = self.sources.text(token.token.span);
+            if matches!(
+                token.token.kind,
+                TokenKind::StringLiteral(_) | TokenKind::CharacterLiteral(_)
+            ) {
+                for ch in spelling.chars() {
+                    if ch == '"' || ch == '\\' {
+                        text.push('\\');
+                    }
+                    text.push(ch);
+                }
+            } else {
+                text.push_str(spelling);
+            }
+        }
+        text.push('"');
+
+        let (token, _) = self.scratch_token(text);
+        PpToken::new(token, spacing)
+    }
+
+    /// `##`: the token spelled by `lhs` and `rhs` written together (C17 6.10.3.3). If they
+    /// don't spell a single token, the error is reported and both are given back
+    fn paste(&mut self, lhs: Option<PpToken>, rhs: Option<PpToken>) -> Vec<Option<PpToken>> {
+        let (lhs, mut rhs) = match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            (lhs, None) => return vec![lhs],
+            (None, rhs) => return vec![rhs],
+        };
+
+        let left = self.sources.text(lhs.token.span);
+        let right = self.sources.text(rhs.token.span);
+        let (token, single) = self.scratch_token(format!("{}{}", left, right));
+        if !single {
+            self.errors.push(PreprocessorError::InvalidPaste(
+                left.to_string(),
+                right.to_string(),
+                lhs.token.span,
+            ));
+            // Both operands are kept, apart so that they aren't read back as one token
+            rhs.spacing.leading_space = true;
+            return vec![Some(lhs), Some(rhs)];
+        }
+        vec![Some(PpToken::new(token, lhs.spacing))]
+    }
+
+    /// Lexes text made by `#` or `##`, which is added to the source map so that the token has a
+    /// spelling. Returns the first token and whether the text is exactly one valid preprocessing
+    /// token
+    fn scratch_token(&mut self, text: String) -> (Token, bool) {
+        let id = self
+            .sources
+            .add_file(PathBuf::from("<scratch space>"), text);
+        let file = self.sources.file(id);
+        let mut lexer = Lexer::new(file.src()).with_standard(self.standard);
+        let (mut token, spacing) = lexer.next_token_spaced();
+        // A pp-number like `1e` is a valid preprocessing token even if it is no valid constant.
+        // It stays an error token, which is only reported if it reaches the parser
+        let valid = match token.kind {
+            TokenKind::Error(..) => starts_pp_number(file.src()),
+            kind => kind != TokenKind::EOF,
+        };
+        let single = !spacing.leading_space && valid && lexer.next_token().kind == TokenKind::EOF;
+
+        token.span = Span::new(
+            token.span.start + file.start(),
+            token.span.end + file.start(),
+        );
+        (token, single)
+    }
+}
+
+/// Whether `text` starts with a preprocessing number: a digit, or a `.` and a digit (C17 6.4.8).
+/// The lexer reads the rest of the number into the same token
+fn starts_pp_number(text: &str) -> bool {
+    matches!(text.as_bytes(), [b'0'..=b'9', ..] | [b'.', b'0'..=b'9', ..])
+}
+
+/// The macro name a `#define` or `#undef` starts with
+pub(super) fn macro_name_arg(
+    args: &[PpToken],
+    directive: Span,
+) -> PreprocessorResult<(Interned, Span)> {
+    let first = args
+        .first()
+        .ok_or(PreprocessorError::ExpectedMacroName(directive))?;
+    match macro_name(first.token.kind) {
+        Some(name) => Ok((name, first.token.span)),
+        None => Err(PreprocessorError::ExpectedMacroName(first.token.span)),
+    }
+}
+
+/// Parses the parameters after the `(` of a function-like macro. Returns them, whether the macro
+/// is variadic and the number of tokens up to and including the `)`
+fn parse_params(
+    tokens: &[PpToken],
+    paren: Span,
+) -> PreprocessorResult<(Vec<Interned>, bool, usize)> {
+    let va_args = Interned::new("__VA_ARGS__");
+    let mut params = Vec::new();
+    let mut i = 0;
+    loop {
+        let Some(token) = tokens.get(i) else {
+            return Err(PreprocessorError::InvalidParameterList(paren));
+        };
+        match token.token.kind {
+            TokenKind::CloseParen if i == 0 => return Ok((params, false, 1)),
+            TokenKind::Ellipsis => {
+                return match tokens.get(i + 1) {
+                    Some(close) if close.token.kind == TokenKind::CloseParen => {
+                        params.push(va_args);
+                        Ok((params, true, i + 2))
+                    }
+                    _ => Err(PreprocessorError::InvalidParameterList(token.token.span)),
+                };
+            }
+            kind => {
+                let name = macro_name(kind)
+                    .filter(|&name| name != va_args)
+                    .ok_or(PreprocessorError::InvalidParameterList(token.token.span))?;
+                if params.contains(&name) {
+                    return Err(PreprocessorError::DuplicateParameter(
+                        name.to_string(),
+                        token.token.span,
+                    ));
+                }
+                params.push(name);
+
+                match tokens.get(i + 1).map(|next| next.token.kind) {
+                    Some(TokenKind::Comma) => i += 2,
+                    Some(TokenKind::CloseParen) => return Ok((params, false, i + 2)),
+                    _ => return Err(PreprocessorError::InvalidParameterList(token.token.span)),
+                }
+            }
+        }
+    }
+}
+
+/// Finds the parameters and operators of a replacement list. `params` is `None` for an
+/// object-like macro, in which `#` is an ordinary token
+fn replacement_list(
+    tokens: &[PpToken],
+    params: Option<&[Interned]>,
+    variadic: bool,
+) -> PreprocessorResult<Vec<Replacement>> {
+    let va_args = Interned::new("__VA_ARGS__");
+    let va_opt = Interned::new("__VA_OPT__");
+    let param = |token: &PpToken| {
+        let name = macro_name(token.token.kind)?;
+        params?.iter().position(|&param| param == name)
+    };
+
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        let spacing = token.spacing;
+        let name = macro_name(token.token.kind);
+        let item = match token.token.kind {
+            // The right operand of `##` can be `##` itself
+            TokenKind::HashHash if matches!(items.last(), Some(Replacement::Paste)) => {
+                Replacement::Token(token)
+            }
+            TokenKind::HashHash if items.is_empty() || i + 1 == tokens.len() => {
+                return Err(PreprocessorError::PasteAtEdge(token.token.span));
+            }
+            TokenKind::HashHash => Replacement::Paste,
+            TokenKind::Hash if params.is_some() => {
+                let next = tokens.get(i + 1);
+                if let Some(index) = next.and_then(param) {
+                    i += 1;
+                    Replacement::Stringize(index, spacing)
+                } else if variadic
+                    && next.and_then(|next| macro_name(next.token.kind)) == Some(va_opt)
+                {
+                    let (content, end) = va_opt_content(tokens, i + 1, params, variadic)?;
+                    i = end;
+                    Replacement::StringizeVaOpt(content, spacing)
+                } else {
+                    return Err(PreprocessorError::StringizeNotParameter(token.token.span));
+                }
+            }
+            _ if let Some(index) = param(&token) => Replacement::Param(index, spacing),
+            _ if variadic && name == Some(va_opt) => {
+                let (content, end) = va_opt_content(tokens, i, params, variadic)?;
+                i = end;
+                Replacement::VaOpt(content, spacing)
+            }
+            // Only allowed in variadic macros, where they are parameters
+            _ if name == Some(va_args) || name == Some(va_opt) => {
+                return Err(PreprocessorError::VaArgsNotAllowed(
+                    name.unwrap().to_string(),
+                    token.token.span,
+                ));
+            }
+            _ => Replacement::Token(token),
+        };
+        items.push(item);
+        i += 1;
+    }
+    Ok(items)
+}
+
+/// The replacement list in `__VA_OPT__(...)` at `start`. Returns it and the index of the `)`
+fn va_opt_content(
+    tokens: &[PpToken],
+    start: usize,
+    params: Option<&[Interned]>,
+    variadic: bool,
+) -> PreprocessorResult<(Vec<Replacement>, usize)> {
+    let span = tokens[start].token.span;
+    if tokens.get(start + 1).map(|token| token.token.kind) != Some(TokenKind::OpenParen) {
+        return Err(PreprocessorError::InvalidVaOpt(span));
+    }
+
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start + 2) {
+        match token.token.kind {
+            TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen if depth == 0 => {
+                let content = replacement_list(&tokens[start + 2..i], params, variadic)?;
+                return Ok((content, i));
+            }
+            TokenKind::CloseParen => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(PreprocessorError::InvalidVaOpt(span))
+}
+
+/// `tokens` with the first one taking the place of a parameter
+fn with_spacing(mut tokens: Vec<PpToken>, spacing: Spacing) -> Vec<PpToken> {
+    if let Some(first) = tokens.first_mut() {
+        first.spacing = spacing;
+    }
+    tokens
+}
@@ -0,0 +1,1683 @@
+mod error;
+mod expr;
+mod include;
+mod macros;
+mod predefined;
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+pub use crate::preprocessor::{
+    error::{PreprocessorError, PreprocessorResult},
+    include::IncludePaths,
+};
+use crate::{
+    Checkpoint, IntegerType, Token, TokenKind, TokenSource,
+    intern::{FxHashMap, Interned},
+    lexer::{Lexer, Spacing},
+    preprocessor::{
+        include::{SearchDir, file_identity},
+        macros::{HideSets, Macro, PpToken, macro_name_arg},
+        predefined::{BUILTINS, build_date_time, predefined_macros},
+    },
+    span::{FileId, SourceMap, Span},
+    standard::Standard,
+};
+
+/// Files nested deeper than this are most likely including themselves
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// Translation phase 4 (C17 5.1.1.2): executes directives and expands macros in the tokens of the
+/// main file and the files it includes. The parser reads the result as a `TokenSource`
+pub struct Preprocessor<'m> {
+    sources: &'m mut SourceMap,
+    standard: Standard,
+    /// Files being read, the innermost include last. The main file is never closed
+    files: Vec<OpenFile>,
+    macros: FxHashMap<Interned, Rc<Macro>>,
+    hidesets: HideSets,
+    /// Tokens of macro expansions, rescanned before reading on in the files
+    pending: VecDeque<PpToken>,
+    /// Output tokens already produced by `peek_nth`, or given back by `restore`
+    lookahead: VecDeque<Token>,
+    /// Output tokens consumed since the outermost live checkpoint, which `restore` can give back.
+    /// Macros and directives can't be run again, so going back replays their output instead
+    consumed: Vec<Token>,
+    /// Checkpoints not yet restored or released, `consumed` is only kept while there are some
+    checkpoints: usize,
+    errors: Vec<PreprocessorError>,
+    /// The `#include` search list, and where `#include <...>` starts in it
+    search_dirs: Vec<SearchDir>,
+    bracket_start: usize,
+    /// Files with `#pragma once`, by identity
+    once: HashSet<PathBuf>,
+    /// Files wrapped in an include guard, by identity, and the macro of the guard
+    guards: HashMap<PathBuf, Interned>,
+    /// The last token read from a file, for `__LINE__` and `__FILE__`
+    position: Span,
+    /// `__DATE__` and `__TIME__`, fixed when preprocessing starts
+    date: String,
+    time: String,
+    /// Next value of `__COUNTER__`
+    counter: u64,
+}
+
+/// A `-D` or `-U` option, applied in command line order after the predefined macros
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandLineMacro {
+    /// `NAME` (defined as 1) or `NAME=VALUE`
+    Define(String),
+    Undefine(String),
+}
+
+struct OpenFile {
+    id: FileId,
+    /// The path of the file through links and `..`
+    identity: PathBuf,
+    /// Where in the search list the file was found, for `#include_next`
+    dir: Option<usize>,
+    /// Whether the file is a system header, which doesn't give warnings
+    system: bool,
+    guard: Guard,
+    lexer: Lexer<'static>,
+    /// The token after a directive, read to find the end of its line
+    peeked: Option<(Token, Spacing)>,
+    /// `#if`s whose `#endif` hasn't been read yet, the innermost last. They don't cross files
+    conditionals: Vec<Conditional>,
+}
+
+/// An open `#if`, `#ifdef` or `#ifndef` (C17 6.10.1)
+struct Conditional {
+    /// The directive that opened it, for errors
+    span: Span,
+    /// Whether one of its groups was kept already, so that the other ones are skipped
+    taken: bool,
+    seen_else: bool,
+}
+
+/// How far a file matches `#ifndef NAME` ... `#endif` with nothing outside, an include guard. A
+/// guarded file isn't read again while `NAME` is defined (the multiple-include optimization)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Guard {
+    /// Nothing read yet
+    Start,
+    /// In the `#ifndef` of the guard
+    Inside(Interned),
+    /// After its `#endif`
+    After(Interned),
+    Unguarded,
+}
+
+impl<'m> Preprocessor<'m> {
+    pub fn new(sources: &'m mut SourceMap, main: FileId, standard: Standard) -> Self {
+        let (date, time) = build_date_time();
+        let mut preprocessor = Self {
+            sources,
+            standard,
+            files: Vec::new(),
+            macros: FxHashMap::default(),
+            hidesets: HideSets::new(),
+            pending: VecDeque::new(),
+            lookahead: VecDeque::new(),
+            consumed: Vec::new(),
+            checkpoints: 0,
+            errors: Vec::new(),
+            search_dirs: Vec::new(),
+            bracket_start: 0,
+            once: HashSet::new(),
+            guards: HashMap::new(),
+            position: Span::default(),
+            date,
+            time,
+            counter: 0,
+        };
+        for &(name, builtin) in BUILTINS {
+            let definition = Rc::new(Macro::builtin(builtin));
+            preprocessor.macros.insert(Interned::new(name), definition);
+        }
+        preprocessor.open(main, None, false);
+
+        // The predefined macros are read as a file before the main one
+        let predefined = predefined_macros(standard);
+        let id = preprocessor
+            .sources
+            .add_file(PathBuf::from("<built-in>"), predefined);
+        preprocessor.open(id, None, true);
+        preprocessor
+    }
+
+    /// Defines and undefines `-D` and `-U` macros, before the main file is read
+    pub fn with_command_line_macros(mut self, macros: &[CommandLineMacro]) -> Self {
+        let mut text = String::new();
+        for command_line_macro in macros {
+            let line = match command_line_macro {
+                CommandLineMacro::Define(definition) => match definition.split_once('=') {
+                    Some((name, value)) => format!("#define {} {}\n", name, value),
+                    None => format!("#define {} 1\n", definition),
+                },
+                CommandLineMacro::Undefine(name) => format!("#undef {}\n", name),
+            };
+            text.push_str(&line);
+        }
+
+        // Read after the predefined macros, which are the last file opened
+        let id = self.sources.add_file(PathBuf::from("<command line>"), text);
+        self.open(id, None, false);
+        let command_line = self.files.pop().unwrap();
+        self.files.insert(1, command_line);
+        self
+    }
+
+    /// Directories to search for headers. Only the directory of the including file is searched
+    /// without them
+    pub fn with_include_paths(mut self, paths: IncludePaths) -> Self {
+        (self.search_dirs, self.bracket_start) = paths.search_dirs();
+        self
+    }
+
+    /// Errors in directives, and `#warning`s. The directive is skipped, the tokens around it still
+    /// reach the parser
+    pub fn into_errors(self) -> Vec<PreprocessorError> {
+        self.errors
+    }
+
+    /// The preprocessed source, as printed by `-E`. Tokens keep their line breaks and spacing,
+    /// blank lines and comments are dropped
+    pub fn preprocessed_text(&mut self) -> String {
+        let mut text = String::new();
+        let mut prev: Option<&str> = None;
+        loop {
+            let token = self.expanded_token();
+            if token.token.kind == TokenKind::EOF {
+                break;
+            }
+
+            let spelling = self.sources.text(token.token.span);
+            if let Some(prev) = prev {
+                if token.spacing.line_start {
+                    text.push('\n');
+                } else if token.spacing.leading_space || would_paste(prev, spelling) {
+                    text.push(' ');
+                }
+            }
+            text.push_str(spelling);
+            prev = Some(spelling);
+        }
+        if prev.is_some() {
+            text.push('\n');
+        }
+        text
+    }
+
+    fn open(&mut self, id: FileId, dir: Option<usize>, system: bool) {
+        let file = self.sources.file(id);
+        let src = file.src();
+        self.files.push(OpenFile {
+            id,
+            identity: file_identity(file.path()),
+            dir,
+            system,
+            guard: Guard::Start,
+            lexer: Lexer::new(src).with_standard(self.standard),
+            peeked: None,
+            conditionals: Vec::new(),
+        });
+    }
+
+    /// Next token with macros expanded
+    fn expanded_token(&mut self) -> PpToken {
+        loop {
+            let token = self.read_token();
+            if !self.expand(token) {
+                return token;
+            }
+        }
+    }
+
+    /// Next token to be expanded, executing the directives before it
+    fn read_token(&mut self) -> PpToken {
+        if let Some(token) = self.pending.pop_front() {
+            return token;
+        }
+
+        loop {
+            let (token, spacing) = self.file_token();
+            match token.kind {
+                TokenKind::Hash if spacing.line_start => self.directive(),
+                TokenKind::EOF => {
+                    let file = self.files.last_mut().unwrap();
+                    for conditional in file.conditionals.drain(..) {
+                        let e = PreprocessorError::UnterminatedConditional(conditional.span);
+                        self.errors.push(e);
+                    }
+                    if let Guard::After(name) = file.guard {
+                        self.guards.insert(file.identity.clone(), name);
+                    }
+                    if self.files.len() == 1 {
+                        return PpToken::new(token, spacing);
+                    }
+                    self.files.pop();
+                }
+                _ => {
+                    let file = self.files.last_mut().unwrap();
+                    if file.conditionals.is_empty() {
+                        file.guard = Guard::Unguarded;
+                    }
+                    self.position = token.span;
+                    return PpToken::new(token, spacing);
+                }
+            }
+        }
+    }
+
+    /// Next token of the innermost file, its span moved into the source map
+    fn file_token(&mut self) -> (Token, Spacing) {
+        let file = self
+            .files
+            .last_mut()
+            .expect("the main file is never closed");
+        if let Some(peeked) = file.peeked.take() {
+            return peeked;
+        }
+
+        let (mut token, spacing) = file.lexer.next_token_spaced();
+        let start = self.sources.file(file.id).start();
+        token.span = Span::new(token.span.start + start, token.span.end + start);
+        (token, spacing)
+    }
+
+    /// Tokens after the directive name up to the end of the line
+    fn directive_line(&mut self) -> Vec<PpToken> {
+        let mut line = Vec::new();
+        loop {
+            let (token, spacing) = self.file_token();
+            if spacing.line_start || token.kind == TokenKind::EOF {
+                self.files.last_mut().unwrap().peeked = Some((token, spacing));
+                return line;
+            }
+            line.push(PpToken::new(token, spacing));
+        }
+    }
+
+    /// Executes the directive whose `#` was just read
+    fn directive(&mut self) {
+        let line = self.directive_line();
+        // A `#` alone is the null directive
+        let Some((name, args)) = line.split_first() else {
+            return;
+        };
+
+        let span = name.token.span;
+        let directive = self.sources.text(span);
+        self.position = span;
+        let file = self.files.last_mut().unwrap();
+        if file.conditionals.is_empty() {
+            let guard = args.first().and_then(|arg| macro_name(arg.token.kind));
+            file.guard = match (file.guard, directive, guard) {
+                (Guard::Start, "ifndef", Some(guard)) => Guard::Inside(guard),
+                _ => Guard::Unguarded,
+            };
+        }
+
+        let result = match macro_name(name.token.kind).map(|_| directive) {
+            Some("include") => self.include(args, span, false),
+            Some("include_next") => self.include(args, span, true),
+            Some("define") => self.define(args, span),
+            Some("undef") => self.undef(args, span),
+            Some("if") => {
+                let taken = self.condition(args, "if", span);
+                self.begin_conditional(taken, span);
+                Ok(())
+            }
+            Some("ifdef") => {
+                let taken = self.is_defined_arg(args, "ifdef", span);
+                self.begin_conditional(taken, span);
+                Ok(())
+            }
+            Some("ifndef") => {
+                let taken = self.is_defined_arg(args, "ifndef", span);
+                self.begin_conditional(taken.map(|defined| !defined), span);
+                Ok(())
+            }
+            Some("elif") => self.end_group("elif", args, span),
+            Some("else") => self.end_group("else", args, span),
+            Some("endif") => self.endif(args, span),
+            Some("error") => Err(PreprocessorError::ErrorDirective(self.message(args), span)),
+            Some("warning") => Err(PreprocessorError::WarningDirective(
+                self.message(args),
+                span,
+            )),
+            Some("pragma") => {
+                self.pragma(args);
+                Ok(())
+            }
+            Some("line") => Err(PreprocessorError::UnsupportedDirective(
+                directive.to_string(),
+                span,
+            )),
+            _ => Err(PreprocessorError::InvalidDirective(
+                directive.to_string(),
+                span,
+            )),
+        };
+        if let Err(e) = result {
+            // Warnings in system headers are noise that can't be fixed where they are reported
+            if !(e.is_warning() && self.files.last().unwrap().system) {
+                self.errors.push(e);
+            }
+        }
+    }
+
+    /// `#pragma once` and `#pragma GCC system_header`. Pragmas we don't know are ignored
+    /// (C17 6.10.6p1)
+    fn pragma(&mut self, args: &[PpToken]) {
+        let words: Vec<&str> = args
+            .iter()
+            .map(|arg| self.sources.text(arg.token.span))
+            .collect();
+        let file = self.files.last_mut().unwrap();
+        match words[..] {
+            ["once"] => {
+                self.once.insert(file.identity.clone());
+            }
+            ["GCC", "system_header"] => file.system = true,
+            _ => {}
+        }
+    }
+
+    /// The text of an `#error` or `#warning` line as written
+    fn message(&self, args: &[PpToken]) -> String {
+        match (args.first(), args.last()) {
+            (Some(first), Some(last)) => {
+                let span = first.token.span.to(last.token.span);
+                self.sources.text(span).to_string()
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Opens a conditional, skipping its first group unless it is `taken`. A condition with an
+    /// error counts as false
+    fn begin_conditional(&mut self, taken: PreprocessorResult<bool>, span: Span) {
+        let taken = taken.unwrap_or_else(|e| {
+            self.errors.push(e);
+            false
+        });
+        let file = self.files.last_mut().unwrap();
+        file.conditionals.push(Conditional {
+            span,
+            taken,
+            seen_else: false,
+        });
+        if !taken {
+            self.skip_group();
+        }
+    }
+
+    /// `#elif` or `#else` read at the end of a kept group: the rest of the conditional is skipped
+    fn end_group(
+        &mut self,
+        directive: &'static str,
+        args: &[PpToken],
+        span: Span,
+    ) -> PreprocessorResult<()> {
+        let conditional = self.innermost_conditional(directive, span)?;
+        conditional.seen_else = directive == "else";
+        if directive == "else" {
+            expect_end(args, directive)?;
+        }
+        self.skip_group();
+        Ok(())
+    }
+
+    fn endif(&mut self, args: &[PpToken], span: Span) -> PreprocessorResult<()> {
+        self.innermost_conditional("endif", span)?;
+        let file = self.files.last_mut().unwrap();
+        file.conditionals.pop();
+        if let (true, Guard::Inside(name)) = (file.conditionals.is_empty(), file.guard) {
+            file.guard = Guard::After(name);
+        }
+        expect_end(args, "endif")
+    }
+
+    /// The conditional an `#elif`, `#else` or `#endif` belongs to
+    fn innermost_conditional(
+        &mut self,
+        directive: &'static str,
+        span: Span,
+    ) -> PreprocessorResult<&mut Conditional> {
+        let file = self.files.last_mut().unwrap();
+        // A guard has no `#elif` or `#else`
+        if file.conditionals.len() == 1 && directive != "endif" {
+            file.guard = Guard::Unguarded;
+        }
+        let conditional = file
+            .conditionals
+            .last_mut()
+            .ok_or(PreprocessorError::UnmatchedConditional(directive, span))?;
+        if conditional.seen_else && directive != "endif" {
+            return Err(PreprocessorError::AfterElse(directive, span));
+        }
+        Ok(conditional)
+    }
+
+    /// Skips the lines of a group up to the `#elif`, `#else` or `#endif` that ends it, and on to
+    /// the group that is kept if there is one. Only the nesting of directives matters in there
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        loop {
+            let (token, spacing) = self.file_token();
+            match token.kind {
+                TokenKind::Hash if spacing.line_start => {}
+                TokenKind::EOF => {
+                    self.files.last_mut().unwrap().peeked = Some((token, spacing));
+                    return;
+                }
+                _ => continue,
+            }
+
+            let line = self.directive_line();
+            let Some((name, args)) = line.split_first() else {
+                continue;
+            };
+            let span = name.token.span;
+            let directive = macro_name(name.token.kind).map(|_| self.sources.text(span));
+            let result = match directive {
+                Some("if" | "ifdef" | "ifndef") => {
+                    depth += 1;
+                    Ok(false)
+                }
+                Some("endif") if depth > 0 => {
+                    depth -= 1;
+                    Ok(false)
+                }
+                Some("endif") if depth == 0 => self.endif(args, span).map(|_| true),
+                Some("elif") if depth == 0 => self.skipped_elif(args, span),
+                Some("else") if depth == 0 => self.skipped_else(args, span),
+                _ => Ok(false),
+            };
+            match result {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(e) => self.errors.push(e),
+            }
+        }
+    }
+
+    /// `#elif` after skipped groups. Returns whether its group is kept
+    fn skipped_elif(&mut self, args: &[PpToken], span: Span) -> PreprocessorResult<bool> {
+        // The condition is only evaluated if no group was kept before
+        if self.innermost_conditional("elif", span)?.taken {
+            return Ok(false);
+        }
+        let taken = self.condition(args, "elif", span)?;
+        self.innermost_conditional("elif", span)?.taken = taken;
+        Ok(taken)
+    }
+
+    /// `#else` after skipped groups. Returns whether its group is kept
+    fn skipped_else(&mut self, args: &[PpToken], span: Span) -> PreprocessorResult<bool> {
+        let conditional = self.innermost_conditional("else", span)?;
+        let taken = !conditional.taken;
+        conditional.taken = true;
+        conditional.seen_else = true;
+        expect_end(args, "else")?;
+        Ok(taken)
+    }
+
+    /// Evaluates the expression of an `#if` or `#elif`
+    fn condition(
+        &mut self,
+        args: &[PpToken],
+        directive: &'static str,
+        span: Span,
+    ) -> PreprocessorResult<bool> {
+        let defined = Interned::new("defined");
+        let has_include = Interned::new("__has_include");
+
+        // `defined` and `__has_include` apply to their operands as written, before the rest of
+        // the line is macro expanded
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            let token = args[i];
+            let name = macro_name(token.token.kind);
+            let (value, len) = if name == Some(defined) {
+                self.defined_operator(&args[i + 1..], token.token.span)?
+            } else if name == Some(has_include) {
+                self.has_include_operator(&args[i + 1..], token.token.span)?
+            } else {
+                tokens.push(token);
+                i += 1;
+                continue;
+            };
+            let kind = TokenKind::IntegerLiteral(value as u64, IntegerType::Int);
+            tokens.push(PpToken::new(
+                Token::new(kind, token.token.span),
+                token.spacing,
+            ));
+            i += 1 + len;
+        }
+
+        let expanded = self.fully_expand(&tokens);
+        if expanded.is_empty() {
+            return Err(PreprocessorError::MissingExpression(directive, span));
+        }
+        let end = args.last().map_or(span, |last| last.token.span);
+        let end = Span::new(end.end, end.end);
+        Ok(expr::evaluate(&expanded, end)?.is_true())
+    }
+
+    /// `defined NAME` or `defined(NAME)`, given the tokens after `defined`. Returns whether the
+    /// macro is defined and the number of tokens used
+    fn defined_operator(&self, rest: &[PpToken], span: Span) -> PreprocessorResult<(bool, usize)> {
+        let parenthesized = rest
+            .first()
+            .is_some_and(|first| first.token.kind == TokenKind::OpenParen);
+        let operand = &rest[parenthesized as usize..];
+        let (name, name_span) = macro_name_arg(operand, span)?;
+        if !parenthesized {
+            return Ok((self.is_defined(name), 1));
+        }
+        match operand.get(1) {
+            Some(close) if close.token.kind == TokenKind::CloseParen => {
+                Ok((self.is_defined(name), 3))
+            }
+            Some(other) => Err(PreprocessorError::ExpectedInExpression(
+                "')'",
+                other.token.span,
+            )),
+            None => Err(PreprocessorError::ExpectedInExpression("')'", name_span)),
+        }
+    }
+
+    /// `__has_include("name")` or `__has_include(<name>)`, given the tokens after
+    /// `__has_include`. Returns whether the header can be included and the number of tokens used
+    fn has_include_operator(
+        &mut self,
+        rest: &[PpToken],
+        span: Span,
+    ) -> PreprocessorResult<(bool, usize)> {
+        if rest.first().map(|first| first.token.kind) != Some(TokenKind::OpenParen) {
+            return Err(PreprocessorError::ExpectedInExpression("'('", span));
+        }
+        let close = rest
+            .iter()
+            .position(|token| token.token.kind == TokenKind::CloseParen)
+            .ok_or(PreprocessorError::ExpectedInExpression("')'", span))?;
+        let (name, quoted, _) = self.header_name(&rest[1..close], span)?;
+        Ok((self.find_include(&name, quoted, false).is_ok(), close + 1))
+    }
+
+    /// The macro name of an `#ifdef` or `#ifndef` and whether it is defined
+    fn is_defined_arg(
+        &self,
+        args: &[PpToken],
+        directive: &'static str,
+        span: Span,
+    ) -> PreprocessorResult<bool> {
+        let (name, _) = macro_name_arg(args, span)?;
+        expect_end(&args[1..], directive)?;
+        Ok(self.is_defined(name))
+    }
+
+    /// Whether `defined` is true for `name`. `__has_include` counts as a macro, so that code can
+    /// check for it
+    fn is_defined(&self, name: Interned) -> bool {
+        self.macros.contains_key(&name) || name == Interned::new("__has_include")
+    }
+
+    /// `#include "name"` or `#include <name>` (C17 6.10.2). `#include_next` searches the
+    /// directories after the one the current file was found in, for headers that wrap another
+    /// one of the same name
+    fn include(&mut self, args: &[PpToken], directive: Span, next: bool) -> PreprocessorResult<()> {
+        let (name, quoted, span) = self.header_name(args, directive)?;
+        if self.files.len() >= MAX_INCLUDE_DEPTH {
+            return Err(PreprocessorError::IncludeTooDeep(span));
+        }
+
+        let failed = |e| PreprocessorError::IncludeFailed(name.clone(), e, span);
+        let found = self.find_include(&name, quoted, next).map_err(failed)?;
+        let identity = file_identity(&found.path);
+        if self.once.contains(&identity) {
+            return Ok(());
+        }
+        if let Some(guard) = self.guards.get(&identity)
+            && self.macros.contains_key(guard)
+        {
+            return Ok(());
+        }
+
+        let src = fs::read_to_string(&found.path).map_err(failed)?;
+        let id = self.sources.add_file(found.path, src);
+        self.open(id, found.dir, found.system);
+        Ok(())
+    }
+
+    /// The header name of an `#include` and whether it is quoted. If the line isn't a header name
+    /// as written, it is macro expanded first
+    fn header_name(
+        &mut self,
+        args: &[PpToken],
+        directive: Span,
+    ) -> PreprocessorResult<(String, bool, Span)> {
+        let first = args
+            .first()
+            .ok_or(PreprocessorError::ExpectedHeaderName(directive))?;
+        match first.token.kind {
+            TokenKind::StringLiteral(_) => {
+                expect_end(&args[1..], "include")?;
+                let text = self.sources.text(first.token.span);
+                Ok((text[1..text.len() - 1].to_string(), true, first.token.span))
+            }
+            // The name is taken from the source as is, `<sys/types.h>` is several tokens
+            TokenKind::Less => {
+                let close = args
+                    .iter()
+                    .position(|arg| arg.token.kind == TokenKind::Greater)
+                    .ok_or(PreprocessorError::ExpectedHeaderName(first.token.span))?;
+                expect_end(&args[close + 1..], "include")?;
+                let span = first.token.span.to(args[close].token.span);
+                let name = Span::new(first.token.span.end, args[close].token.span.start);
+                Ok((self.sources.text(name).to_string(), false, span))
+            }
+            _ => {
+                let expanded = self.fully_expand(args);
+                if expanded.is_empty() {
+                    return Err(PreprocessorError::ExpectedHeaderName(first.token.span));
+                }
+                self.expanded_header_name(&expanded, first.token.span)
+            }
+        }
+    }
+
+    /// Header name spelled by the macro expanded tokens of an `#include` line
+    fn expanded_header_name(
+        &mut self,
+        tokens: &[PpToken],
+        span: Span,
+    ) -> PreprocessorResult<(String, bool, Span)> {
+        let first = tokens[0];
+        match first.token.kind {
+            TokenKind::StringLiteral(_) => {
+                expect_end(&tokens[1..], "include")?;
+                let text = self.sources.text(first.token.span);
+                Ok((text[1..text.len() - 1].to_string(), true, span))
+            }
+            TokenKind::Less => {
+                let mut name = String::new();
+                for (i, token) in tokens.iter().enumerate().skip(1) {
+                    if token.token.kind == TokenKind::Greater {
+                        expect_end(&tokens[i + 1..], "include")?;
+                        return Ok((name, false, span));
+                    }
+                    if token.spacing.leading_space && i > 1 {
+                        name.push(' ');
+                    }
+                    name.push_str(self.sources.text(token.token.span));
+                }
+                Err(PreprocessorError::ExpectedHeaderName(span))
+            }
+            _ => Err(PreprocessorError::ExpectedHeaderName(span)),
+        }
+    }
+
+    /// The file `#include` names. A quoted name is first looked for next to the including file,
+    /// where headers of a system header are system headers too
+    fn find_include(&self, name: &str, quoted: bool, next: bool) -> io::Result<FoundHeader> {
+        let current = self.files.last().expect("an include is always in a file");
+        if Path::new(name).is_absolute() {
+            if !Path::new(name).is_file() {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            return Ok(FoundHeader {
+                path: PathBuf::from(name),
+                dir: None,
+                system: false,
+            });
+        }
+
+        let next_dir = current.dir.filter(|_| next).map(|dir| dir + 1);
+        if quoted
+            && next_dir.is_none()
+            && let Some(dir) = self.sources.file(current.id).path().parent()
+        {
+            let path = dir.join(name);
+            if path.is_file() {
+                return Ok(FoundHeader {
+                    path,
+                    dir: None,
+                    system: current.system,
+                });
+            }
+        }
+
+        let start = next_dir.unwrap_or(if quoted { 0 } else { self.bracket_start });
+        for (i, dir) in self.search_dirs.iter().enumerate().skip(start) {
+            let path = dir.path.join(name);
+            if path.is_file() {
+                return Ok(FoundHeader {
+                    path,
+                    dir: Some(i),
+                    system: dir.system,
+                });
+            }
+        }
+        Err(io::ErrorKind::NotFound.into())
+    }
+}
+
+/// A header `#include` found in the search list
+struct FoundHeader {
+    path: PathBuf,
+    /// Index of its directory in the search list, `None` if it was found some other way
+    dir: Option<usize>,
+    system: bool,
+}
+
+impl TokenSource for Preprocessor<'_> {
+    fn next_token(&mut self) -> Token {
+        let token = match self.lookahead.pop_front() {
+            Some(token) => token,
+            None => self.expanded_token().token,
+        };
+        if self.checkpoints > 0 {
+            self.consumed.push(token);
+        }
+        token
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Token {
+        while self.lookahead.len() <= n {
+            let token = self.expanded_token().token;
+            self.lookahead.push_back(token);
+        }
+        self.lookahead[n]
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints += 1;
+        Checkpoint {
+            pos: self.consumed.len(),
+        }
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        for token in self.consumed.drain(checkpoint.pos..).rev() {
+            self.lookahead.push_front(token);
+        }
+        self.release(checkpoint);
+    }
+
+    fn release(&mut self, _checkpoint: Checkpoint) {
+        self.checkpoints = self
+            .checkpoints
+            .checked_sub(1)
+            .expect("releasing a checkpoint of another token source");
+        if self.checkpoints == 0 {
+            // Free the buffer, a long lookahead shouldn't stay allocated for the whole file
+            self.consumed = Vec::new();
+        }
+    }
+}
+
+/// The name a token has as a macro or parameter. Keywords are identifiers to the preprocessor
+fn macro_name(kind: TokenKind) -> Option<Interned> {
+    match kind {
+        TokenKind::Identifier(name) => Some(name),
+        kind => kind.keyword().map(Interned::new),
+    }
+}
+
+/// Errors if a directive goes on after its arguments
+fn expect_end(rest: &[PpToken], directive: &'static str) -> PreprocessorResult<()> {
+    match rest.first() {
+        Some(extra) => Err(PreprocessorError::ExtraTokens(directive, extra.token.span)),
+        None => Ok(()),
+    }
+}
+
+/// Whether two tokens written next to each other would lex as something else, e.g. `+` and `+`
+/// coming out of different macros
+fn would_paste(prev: &str, next: &str) -> bool {
+    let joined = format!("{}{}", prev, next);
+    Lexer::new(&joined).next_token().span.end != prev.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// The `-E` output and the errors for `src`, read as `path`
+    fn preprocess_file(path: &Path, src: &str) -> (String, Vec<String>) {
+        preprocess_with_paths(path, src, IncludePaths::default())
+    }
+
+    fn preprocess_with_paths(path: &Path, src: &str, paths: IncludePaths) -> (String, Vec<String>) {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file(path.to_path_buf(), src.to_string());
+        let mut preprocessor =
+            Preprocessor::new(&mut sources, main, Standard::default()).with_include_paths(paths);
+        let text = preprocessor.preprocessed_text();
+        let errors = preprocessor.into_errors();
+        let errors = errors
+            .iter()
+            .map(|e| format!("{}: {}", sources.location(e.span().start), e))
+            .collect();
+        (text, errors)
+    }
+
+    fn preprocess(src: &str) -> (String, Vec<String>) {
+        preprocess_file(Path::new("main.c"), src)
+    }
+
+    /// Checks that `src` preprocesses to the tokens of `expected`, whatever the whitespace
+    fn assert_expands_to(src: &str, expected: &str) {
+        let (text, errors) = preprocess(src);
+        let spellings = |text: &str| {
+            let mut lexer = Lexer::new(text);
+            let mut spellings = Vec::new();
+            loop {
+                let token = lexer.next_token();
+                if token.kind == TokenKind::EOF {
+                    break spellings;
+                }
+                spellings.push(text[token.span.start..token.span.end].to_string());
+            }
+        };
+
+        assert_eq!(spellings(&text), spellings(expected), "{}", text);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_preprocessor_object_like_macros() {
+        let src = "#define N 10\n\
+                   #define TWICE_N N * 2\n\
+                   int a = TWICE_N;\n\
+                   #undef N\n\
+                   int b = N;\n\
+                   #define EMPTY\n\
+                   int EMPTY c;";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "int a = 10 * 2;\nint b = N;\nint c;\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_preprocessor_no_recursive_expansion() {
+        let src = "#define foo foo + 1\n\
+                   #define a b\n\
+                   #define b a\n\
+                   foo a b";
+        let (text, _) = preprocess(src);
+
+        assert_eq!(text, "foo + 1 a b\n");
+    }
+
+    #[test]
+    fn test_preprocessor_directive_lines() {
+        // Directives end with their line, a spliced line or a comment doesn't end them
+        let src = "# define X 1 \\\n + 2 /* a\n b */ + 3\n\
+                   X\n\
+                   #\n\
+                   a # define Y\n\
+                   #pragma anything\n\
+                   /**/ # define Z 4\n\
+                   Z";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "1 + 2 + 3\na # define Y\n4\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_preprocessor_keywords_as_macro_names() {
+        let (text, _) = preprocess("#define inline\n#define if while\ninline int f; if");
+
+        assert_eq!(text, "int f; while\n");
+    }
+
+    #[test]
+    fn test_preprocessor_avoids_pasting_tokens() {
+        let (text, _) = preprocess("#define P +\n#define E\n+P -E- P=");
+
+        assert_eq!(text, "+ + - - + =\n");
+    }
+
+    #[test]
+    fn test_preprocessor_errors() {
+        let src = "#define\n\
+                   #define 1 2\n\
+                   #define F(x, x) x\n\
+                   #define X 1\n\
+                   #define X  1\n\
+                   #define X 2\n\
+                   #undef X Y\n\
+                   #foo\n\
+                   #line 1\n\
+                   #include\n\
+                   #include \"missing.h\"\n\
+                   ok";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "ok\n");
+        assert_eq!(
+            errors,
+            vec![
+                "main.c:1:2: ERROR: Macro names must be identifiers",
+                "main.c:2:9: ERROR: Macro names must be identifiers",
+                "main.c:3:14: ERROR: Duplicate macro parameter 'x'",
+                "main.c:6:9: ERROR: Macro 'X' redefined differently",
+                "main.c:7:10: ERROR: Extra tokens at end of #undef directive",
+                "main.c:8:2: ERROR: Invalid preprocessing directive '#foo'",
+                "main.c:9:2: ERROR: Directive '#line' is not supported yet",
+                "main.c:10:2: ERROR: #include expects \"FILENAME\" or <FILENAME>",
+                "main.c:11:10: ERROR: 'missing.h' file not found",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_function_like_macros() {
+        let src = "#define MAX(a, b) ((a) > (b) ? (a) : (b))\n\
+                   #define CALL(f, ...) f(__VA_ARGS__)\n\
+                   #define NOARGS() 0\n\
+                   #define NAME_ONLY (1)\n\
+                   int m = MAX(x, MAX(y, z));\n\
+                   CALL(g, 1, (2, 3))( MAX );\n\
+                   NOARGS ( ) + NOARGS + NAME_ONLY\n\
+                   MAX(\n\
+                   1,\n\
+                   2)";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(
+            text,
+            "int m = ((x) > (((y) > (z) ? (y) : (z))) ? (x) : (((y) > (z) ? (y) : (z))));\n\
+             g(1, (2, 3))( MAX );\n\
+             0 + NOARGS + (1)\n\
+             ((1) > (2) ? (1) : (2))\n"
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_preprocessor_macro_errors() {
+        let src = "#define A(x\n\
+                   #define B(x) #y\n\
+                   #define C(x) ## x\n\
+                   #define D __VA_ARGS__\n\
+                   #define E(...) __VA_OPT__\n\
+                   #define F(x, y) x ## y\n\
+                   #define G(x, y, ...) x\n\
+                   F(1)\n\
+                   F(+, -)\n\
+                   G(1)\n\
+                   F(a,\n\
+                   b";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "+ -\n");
+        assert_eq!(
+            errors,
+            vec![
+                "main.c:1:11: ERROR: Invalid macro parameter list",
+                "main.c:2:14: ERROR: '#' is not followed by a macro parameter",
+                "main.c:3:14: ERROR: '##' cannot appear at either end of a macro expansion",
+                "main.c:4:11: ERROR: '__VA_ARGS__' can only appear in the expansion of a variadic macro",
+                "main.c:5:16: ERROR: __VA_OPT__ must be followed by a parenthesized list",
+                "main.c:8:1: ERROR: Macro 'F' requires 2 arguments, but 1 given",
+                "main.c:9:3: ERROR: Pasting '+' and '-' does not give a valid preprocessing token",
+                "main.c:10:1: ERROR: Macro 'G' requires at least 2 arguments, but 1 given",
+                "main.c:11:1: ERROR: Unterminated argument list invoking macro 'F'",
+            ]
+        );
+
+        // Operands that can't be pasted stay two tokens, even where they'd lex as one unspaced
+        let (text, errors) = preprocess("#define P(x, y) x ## y\nP(.,.)");
+        assert_eq!(text, ". .\n");
+        assert_eq!(
+            errors,
+            ["main.c:2:3: ERROR: Pasting '.' and '.' does not give a valid preprocessing token"]
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_directives_in_arguments() {
+        let src = "#define F(x) [x]\n\
+                   F(1\n\
+                   #undef F\n\
+                   #define G 2\n\
+                   G) F(3)";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "[1 2] F(3)\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_preprocessor_pp_numbers() {
+        // Pasting and stringizing work on pp-numbers (C17 6.4.8), which only have to be valid
+        // constants once they are converted in translation phase 7
+        let src = "#define cat(a, b) a ## b\n\
+                   #define str(x) #x\n\
+                   #define xstr(x) str(x)\n\
+                   cat(1, e) xstr(cat(1, e)) cat(., 5e) cat(0x, 1p-3) str(1.2.3) str(1foo)";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "1e \"1e\" .5e 0x1p-3 \"1.2.3\" \"1foo\"\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    // Conformance with the examples of C17 6.10.3 and C23 6.10.5
+
+    #[test]
+    fn test_preprocessor_standard_example_rescanning() {
+        // C17 6.10.3.4p4: either result is allowed, Prosser's algorithm gives the second
+        let src = "#define f(a) a*g\n#define g(a) f(a)\nf(2)(9)";
+        assert_expands_to(src, "2*9*g");
+    }
+
+    #[test]
+    fn test_preprocessor_standard_example_hash_hash() {
+        // C17 6.10.3.3p4
+        let src = "#define hash_hash # ## #\n\
+                   #define mkstr(a) # a\n\
+                   #define in_between(a) mkstr(a)\n\
+                   #define join(c, d) in_between(c hash_hash d)\n\
+                   char p[] = join(x, y);";
+        assert_expands_to(src, "char p[] = \"x ## y\";");
+    }
+
+    #[test]
+    fn test_preprocessor_standard_example_3() {
+        let src = "#define x 3\n\
+                   #define f(a) f(x * (a))\n\
+                   #undef x\n\
+                   #define x 2\n\
+                   #define g f\n\
+                   #define z z[0]\n\
+                   #define h g(~\n\
+                   #define m(a) a(w)\n\
+                   #define w 0,1\n\
+                   #define t(a) a\n\
+                   #define p() int\n\
+                   #define q(x) x\n\
+                   #define r(x,y) x ## y\n\
+                   #define str(x) # x\n\
+                   f(y+1) + f(f(z)) % t(t(g)(0) + t)(1);\n\
+                   g(x+(3,4)-w) | h 5) & m\n\
+                   (f)^m(m);\n\
+                   p() i[q()] = { q(1), r(2,3), r(4,), r(,5), r(,) };\n\
+                   char c[2][6] = { str(hello), str() };";
+        let expected = "f(2 * (y+1)) + f(2 * (f(2 * (z[0])))) % f(2 * (0)) + t(1);\n\
+                        f(2 * (2+(3,4)-0,1)) | f(2 * (~ 5)) & f(2 * (0,1))^m(0,1);\n\
+                        int i[] = { 1, 23, 4, 5, };\n\
+                        char c[2][6] = { \"hello\", \"\" };";
+        assert_expands_to(src, expected);
+    }
+
+    #[test]
+    fn test_preprocessor_standard_example_4() {
+        let src = r#"#define str(s) # s
+#define xstr(s) str(s)
+#define debug(s, t) printf("x" # s "= %d, x" # t "= %s", \
+ x ## s, x ## t)
+#define INCFILE(n) vers ## n
+#define glue(a, b) a ## b
+#define xglue(a, b) glue(a, b)
+#define HIGHLOW "hello"
+#define LOW LOW ", world"
+debug(1, 2);
+fputs(str(strncmp("abc\0d", "abc", '\4') // this goes away
+ == 0) str(: @\n), s);
+glue(HIGH, LOW);
+xglue(HIGH, LOW)"#;
+        let expected = r#"printf("x" "1" "= %d, x" "2" "= %s", x1, x2);
+fputs("strncmp(\"abc\\0d\", \"abc\", '\\4') == 0" ": @\n", s);
+"hello";
+"hello" ", world""#;
+        assert_expands_to(src, expected);
+
+        // The header name is made by macros before the file is looked for
+        let src = "#define xstr(s) str(s)\n\
+                   #define str(s) # s\n\
+                   #define INCFILE(n) vers ## n\n\
+                   #include xstr(INCFILE(2).h)";
+        let (_, errors) = preprocess(src);
+        assert_eq!(errors, vec!["main.c:4:10: ERROR: 'vers2.h' file not found"]);
+    }
+
+    #[test]
+    fn test_preprocessor_standard_example_5() {
+        let src = "#define t(x,y,z) x ## y ## z\n\
+                   int j[] = { t(1,2,3), t(,4,5), t(6,,7), t(8,9,),\n\
+                   t(10,,), t(,11,), t(,,12), t(,,) };";
+        assert_expands_to(src, "int j[] = { 123, 45, 67, 89, 10, 11, 12, };");
+    }
+
+    #[test]
+    fn test_preprocessor_standard_example_6() {
+        let src = "#define OBJ_LIKE (1-1)\n\
+                   #define OBJ_LIKE /* white space */ (1-1) /* other */\n\
+                   #define FUNC_LIKE(a) ( a )\n\
+                   #define FUNC_LIKE( a )( /* note the white space */ \\\n\
+                   a /* other stuff on this line\n\
+                   */ )\n\
+                   #define OBJ_LIKE (0) // different token sequence\n\
+                   #define OBJ_LIKE (1 - 1) // different white space\n\
+                   #define FUNC_LIKE(b) ( a ) // different parameter usage\n\
+                   #define FUNC_LIKE(b) ( b ) // different parameter spelling\n";
+        let (_, errors) = preprocess(src);
+
+        assert_eq!(
+            errors,
+            vec![
+                "main.c:7:9: ERROR: Macro 'OBJ_LIKE' redefined differently",
+                "main.c:8:9: ERROR: Macro 'OBJ_LIKE' redefined differently",
+                "main.c:9:9: ERROR: Macro 'FUNC_LIKE' redefined differently",
+                "main.c:10:9: ERROR: Macro 'FUNC_LIKE' redefined differently",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_standard_example_7() {
+        let src = r#"#define debug(...) fprintf(stderr, __VA_ARGS__)
+#define showlist(...) puts(#__VA_ARGS__)
+#define report(test, ...) ((test)?puts(#test):\
+ printf(__VA_ARGS__))
+debug("Flag");
+debug("X = %d\n", x);
+showlist(The first, second, and third items.);
+report(x>y, "x is %d but y is %d", x, y);"#;
+        let expected = r#"fprintf(stderr, "Flag");
+fprintf(stderr, "X = %d\n", x);
+puts("The first, second, and third items.");
+((x>y)?puts("x>y"): printf("x is %d but y is %d", x, y));"#;
+        assert_expands_to(src, expected);
+    }
+
+    #[test]
+    fn test_preprocessor_standard_example_va_opt() {
+        // C23 6.10.5.1p10 and 6.10.5.2p4
+        let src = "#define F(...) f(0 __VA_OPT__(,) __VA_ARGS__)\n\
+                   #define G(X, ...) f(0, X __VA_OPT__(,) __VA_ARGS__)\n\
+                   #define SDEF(sname, ...) S sname __VA_OPT__(= { __VA_ARGS__ })\n\
+                   #define EMP\n\
+                   F(a, b, c) F() F(EMP)\n\
+                   G(a, b, c) G(a, ) G(a)\n\
+                   SDEF(foo); SDEF(bar, 1, 2);\n\
+                   #define H2(X, Y, ...) __VA_OPT__(X ## Y,) __VA_ARGS__\n\
+                   H2(a, b, c, d)\n\
+                   #define H3(X, ...) #__VA_OPT__(X##X X##X)\n\
+                   H3(, 0)\n\
+                   #define H4(X, ...) __VA_OPT__(a X ## X) ## b\n\
+                   H4(, 1)\n\
+                   #define H5A(...) __VA_OPT__()/**/__VA_OPT__()\n\
+                   #define H5B(X) a ## X ## b\n\
+                   #define H5C(X) H5B(X)\n\
+                   H5C(H5A())\n\
+                   #define LPAREN() (\n\
+                   #define K(Q) 42\n\
+                   #define L(R, X, ...) __VA_OPT__(K R X) )\n\
+                   int x = L(LPAREN(), 0, <:-);";
+        let expected = "f(0, a, b, c) f(0) f(0)\n\
+                        f(0, a, b, c) f(0, a) f(0, a)\n\
+                        S foo; S bar = { 1, 2 };\n\
+                        ab, c, d\n\
+                        \"\"\n\
+                        a b\n\
+                        ab\n\
+                        int x = 42;";
+        assert_expands_to(src, expected);
+    }
+
+    #[test]
+    fn test_preprocessor_conditionals() {
+        let src = "#define ONE 1\n\
+                   #if ONE\n\
+                   a\n\
+                   #elif 1 / 0\n\
+                   b\n\
+                   #else\n\
+                   c\n\
+                   #endif\n\
+                   #ifdef ONE\n\
+                   #  if 0\n\
+                   #    error not reached\n\
+                   #  elif defined ONE && defined(ONE) && !defined TWO\n\
+                   d\n\
+                   #  endif\n\
+                   #else\n\
+                   e\n\
+                   #endif\n\
+                   #ifndef ONE\n\
+                   #if unbalanced ( 'x\n\
+                   f\n\
+                   #endif\n\
+                   #else\n\
+                   g\n\
+                   #endif";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "a\nd\ng\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_preprocessor_if_expressions() {
+        let cases = [
+            ("1 + 2 * 3 == 7", true),
+            ("(1 + 2) * 3 == 7", false),
+            ("-1 < 0", true),
+            // Unsigned arithmetic is done in `uintmax_t`
+            ("-1 < 0u", false),
+            ("0xFFFFFFFFFFFFFFFF == -1", true),
+            ("18446744073709551615u / 2 == 9223372036854775807", true),
+            ("-9223372036854775807 - 1 < 0", true),
+            ("(1 << 62) > 0 && 1 << 63 < 0", true),
+            ("-16 >> 2 == -4 && 7 % -3 == 1 && -7 / 2 == -3", true),
+            (
+                "~0 == -1 && (5 & 3) == 1 && (5 | 3) == 7 && (5 ^ 3) == 6",
+                true,
+            ),
+            ("'a' == 97 && '\\377' < 0", true),
+            ("1 ? 2 : 0", true),
+            ("0 ? 1 : 0 ? 1 : 0", false),
+            ("(0, 1)", true),
+            // Identifiers that aren't macros are 0, keywords too
+            ("undefined_name || int", false),
+            // Division by zero is fine where it isn't evaluated
+            ("0 && 1 / 0", false),
+            ("1 || 1 % 0", true),
+            ("1 ? 1 : 1 / 0", true),
+        ];
+        for (expression, expected) in cases {
+            let src = format!("#if {}\nyes\n#else\nno\n#endif", expression);
+            let (text, errors) = preprocess(&src);
+
+            let expected = if expected { "yes\n" } else { "no\n" };
+            assert_eq!(text, expected, "#if {}", expression);
+            assert!(errors.is_empty(), "#if {}: {:?}", expression, errors);
+        }
+    }
+
+    #[test]
+    fn test_preprocessor_conditional_errors() {
+        let src = "#if\n\
+                   #endif\n\
+                   #if 1 +\n\
+                   #elif 1\n\
+                   #endif\n\
+                   #if (1\n\
+                   #else\n\
+                   #elif 1\n\
+                   #else\n\
+                   #endif\n\
+                   #if 1 / 0 == 1 = 1\n\
+                   #endif\n\
+                   #if defined\n\
+                   #endif\n\
+                   #endif\n\
+                   #else junk\n\
+                   #ifdef X Y\n\
+                   #endif\n\
+                   #if 18446744073709551615 || 0 && 9223372036854775808\n\
+                   #elif 1foo\n\
+                   #endif\n\
+                   #if 1\n\
+                   a";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "a\n");
+        assert_eq!(
+            errors,
+            vec![
+                "main.c:1:2: ERROR: #if with no expression",
+                "main.c:3:8: ERROR: Expected an expression in #if",
+                "main.c:6:7: ERROR: Expected ')' in #if",
+                "main.c:8:2: ERROR: #elif after #else",
+                "main.c:9:2: ERROR: #else after #else",
+                "main.c:11:7: ERROR: Division by zero in #if",
+                "main.c:13:5: ERROR: Macro names must be identifiers",
+                "main.c:15:2: ERROR: #endif without #if",
+                "main.c:16:2: ERROR: #else without #if",
+                "main.c:17:10: ERROR: Extra tokens at end of #ifdef directive",
+                "main.c:19:5: ERROR: Integer literal '18446744073709551615' is too large for any \
+                 integer type",
+                "main.c:20:8: ERROR: Invalid suffix 'foo' on integer literal '1': identifiers \
+                 can't start with a digit",
+                "main.c:22:2: ERROR: Unterminated conditional directive",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_error_and_warning_directives() {
+        let src = "#warning  this is   deprecated\n\
+                   #if 0\n\
+                   #error not reached\n\
+                   #endif\n\
+                   #error \"stop\" here\n\
+                   x";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "x\n");
+        assert_eq!(
+            errors,
+            vec![
+                "main.c:1:2: WARNING: #warning this is   deprecated",
+                "main.c:5:2: ERROR: #error \"stop\" here",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_predefined_macros() {
+        let src = "__STDC__ __STDC_VERSION__ __STDC_HOSTED__ __x86_64__ __linux__\n\
+                   #define LINE __LINE__\n\
+                   __FILE__ __LINE__ LINE\n\
+                   #if __LINE__ == 4 && defined __COUNTER__\n\
+                   __COUNTER__ __COUNTER__ __COUNTER__\n\
+                   #endif\n\
+                   #undef __LINE__\n\
+                   __LINE__";
+        let (text, errors) = preprocess(src);
+
+        assert_eq!(text, "1 201710L 1 1 1\n\"main.c\" 3 3\n0 1 2\n__LINE__\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let mut sources = SourceMap::new();
+        let main = sources.add_file(PathBuf::from("main.c"), "__STDC_VERSION__".to_string());
+        let mut preprocessor = Preprocessor::new(&mut sources, main, Standard::C89);
+        assert_eq!(preprocessor.preprocessed_text(), "__STDC_VERSION__\n");
+    }
+
+    #[test]
+    fn test_preprocessor_date_and_time() {
+        let (text, errors) = preprocess("__DATE__ __TIME__");
+        assert_eq!(text.len(), "\"Mmm dd yyyy\" \"hh:mm:ss\"\n".len());
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let format = |seconds| {
+            let (date, time) = predefined::date_time(seconds);
+            format!("{} {}", date, time)
+        };
+        assert_eq!(format(0), "\"Jan  1 1970\" \"00:00:00\"");
+        assert_eq!(format(951_782_400), "\"Feb 29 2000\" \"00:00:00\"");
+        assert_eq!(format(1_700_000_000), "\"Nov 14 2023\" \"22:13:20\"");
+    }
+
+    #[test]
+    fn test_preprocessor_command_line_macros() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file(PathBuf::from("main.c"), "A B C D(2) __linux__".to_string());
+        let macros = [
+            CommandLineMacro::Define("A".to_string()),
+            CommandLineMacro::Define("B=x + y".to_string()),
+            CommandLineMacro::Define("C=1".to_string()),
+            CommandLineMacro::Undefine("C".to_string()),
+            CommandLineMacro::Define("D(n)=n * n".to_string()),
+            CommandLineMacro::Undefine("__linux__".to_string()),
+            CommandLineMacro::Define("2".to_string()),
+        ];
+        let mut preprocessor = Preprocessor::new(&mut sources, main, Standard::default())
+            .with_command_line_macros(&macros);
+        let text = preprocessor.preprocessed_text();
+        let errors = preprocessor.into_errors();
+
+        assert_eq!(text, "1 x + y C 2 * 2 __linux__\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            sources.location(errors[0].span().start).to_string(),
+            "<command line>:7:9"
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_include() {
+        let dir = env::temp_dir().join(format!("c_compiler_include_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.h"), "#define A 1\nint a = A;\n").unwrap();
+        fs::write(dir.join("sub/b.h"), "#include \"c.h\"\nint b = B\n").unwrap();
+        fs::write(dir.join("sub/c.h"), "#define B 2;\n").unwrap();
+
+        let src = "#include \"a.h\"\n\
+                   #define HEADER \"sub/b.h\"\n\
+                   #include HEADER\n\
+                   int c = A + B;\n\
+                   #include \"a.h\" extra\n\
+                   #include <no_such_header_anywhere.h>\n\
+                   #if __has_include(\"sub/c.h\") && !__has_include(<no_such_header_anywhere.h>)\n\
+                   found\n\
+                   #endif\n";
+        // Absolute paths skip the search list, but must still name a file
+        let (a, missing) = (dir.join("a.h"), dir.join("missing.h"));
+        let src = format!(
+            "{}#if __has_include(\"{}\") && !__has_include(\"{}\")\nabsolute\n#endif\n\
+             #include \"{}\"",
+            src,
+            a.display(),
+            missing.display(),
+            missing.display()
+        );
+        let (text, errors) = preprocess_file(&dir.join("main.c"), &src);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            text,
+            "int a = 1;\nint b = 2;\nint c = 1 + 2;;\nfound\nabsolute\n"
+        );
+        assert_eq!(errors.len(), 3);
+        assert!(
+            errors[0].ends_with("main.c:5:16: ERROR: Extra tokens at end of #include directive")
+        );
+        assert!(errors[1].ends_with("ERROR: 'no_such_header_anywhere.h' file not found"));
+        assert!(errors[2].ends_with(&format!(
+            "main.c:13:10: ERROR: '{}' file not found",
+            missing.display()
+        )));
+    }
+
+    #[test]
+    fn test_preprocessor_search_order() {
+        let dir = env::temp_dir().join(format!("c_compiler_search_{}", std::process::id()));
+        for (path, text) in [
+            ("main/h.h", "main_dir"),
+            ("main/only_here.h", "main_dir"),
+            ("quote/h.h", "quote"),
+            ("quote/q.h", "quote"),
+            ("user/h.h", "user"),
+            ("user/q.h", "user"),
+            ("user/next.h", "user_next\n#include_next <next.h>"),
+            ("system/next.h", "system_next"),
+            (
+                "system/sys_warn.h",
+                "#warning from a system header\nsystem_warn",
+            ),
+            ("user/warn.h", "#warning from a user header\nuser_warn"),
+        ] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        let src = "#include \"h.h\"\n\
+                   #include <h.h>\n\
+                   #include \"q.h\"\n\
+                   #include <q.h>\n\
+                   #include <only_here.h>\n\
+                   #include <next.h>\n\
+                   #include <sys_warn.h>\n\
+                   #include \"../user/warn.h\"";
+        let paths = IncludePaths {
+            quote: vec![dir.join("quote")],
+            user: vec![dir.join("user")],
+            system: vec![dir.join("system")],
+        };
+        let (text, errors) = preprocess_with_paths(&dir.join("main/main.c"), src, paths);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            text,
+            "main_dir\nuser\nquote\nuser\nuser_next\nsystem_next\nsystem_warn\nuser_warn\n"
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].ends_with("ERROR: 'only_here.h' file not found"));
+        assert!(errors[1].ends_with("user/warn.h:1:2: WARNING: #warning from a user header"));
+    }
+
+    #[test]
+    fn test_preprocessor_include_once() {
+        let dir = env::temp_dir().join(format!("c_compiler_once_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("once.h"), "#pragma once\nonce\n").unwrap();
+        fs::write(
+            dir.join("guarded.h"),
+            "// comment\n#ifndef GUARDED_H\n#define GUARDED_H\nguarded\n#endif\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("unguarded.h"),
+            "#ifndef UNGUARDED_H\n#define UNGUARDED_H\n#endif\nunguarded\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("else.h"),
+            "#ifndef ELSE_H\n#define ELSE_H\n#else\nelse\n#endif\n",
+        )
+        .unwrap();
+
+        let mut sources = SourceMap::new();
+        let src = "#include \"once.h\"\n\
+                   #include \"./once.h\"\n\
+                   #include \"guarded.h\"\n\
+                   #include \"guarded.h\"\n\
+                   #include \"unguarded.h\"\n\
+                   #include \"unguarded.h\"\n\
+                   #include \"else.h\"\n\
+                   #include \"else.h\"\n\
+                   #undef GUARDED_H\n\
+                   #include \"guarded.h\"";
+        let main = sources.add_file(dir.join("main.c"), src.to_string());
+        let mut preprocessor = Preprocessor::new(&mut sources, main, Standard::default());
+        let text = preprocessor.preprocessed_text();
+        let guards: Vec<String> = preprocessor
+            .guards
+            .values()
+            .map(|g| g.to_string())
+            .collect();
+        let errors = preprocessor.into_errors();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(text, "once\nguarded\nunguarded\nunguarded\nelse\nguarded\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(guards, vec!["GUARDED_H"]);
+    }
+
+    #[test]
+    fn test_preprocessor_checkpoint_restore() {
+        let mut sources = SourceMap::new();
+        let src = "#define A 1\nx A\n#undef A\n#define A 2\nA y";
+        let main = sources.add_file("main.c".into(), src.to_string());
+        let mut preprocessor = Preprocessor::new(&mut sources, main, Standard::default());
+        let kinds = |preprocessor: &mut Preprocessor, n: usize| {
+            (0..n)
+                .map(|_| format!("{:?}", preprocessor.next_token().kind))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kinds(&mut preprocessor, 1), ["Identifier(\"x\")"]);
+        let checkpoint = preprocessor.checkpoint();
+        let rest = kinds(&mut preprocessor, 2);
+        assert_eq!(rest, ["IntegerLiteral(1, Int)", "IntegerLiteral(2, Int)"]);
+        assert_eq!(
+            preprocessor.peek_nth(0).kind,
+            TokenKind::Identifier(Interned::new("y"))
+        );
+
+        // The directives between the tokens are not run again
+        preprocessor.restore(checkpoint);
+        let outer = preprocessor.checkpoint();
+        assert_eq!(kinds(&mut preprocessor, 2), rest);
+        let inner = preprocessor.checkpoint();
+        assert_eq!(kinds(&mut preprocessor, 2), ["Identifier(\"y\")", "EOF"]);
+        preprocessor.restore(inner);
+        preprocessor.restore(outer);
+        assert_eq!(kinds(&mut preprocessor, 2), rest);
+        assert!(preprocessor.into_errors().is_empty());
+    }
+
+    #[test]
+    fn test_preprocessor_checkpoint_buffer_released() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file("main.c".into(), "x ".repeat(1000));
+        let mut preprocessor = Preprocessor::new(&mut sources, main, Standard::default());
+
+        let checkpoint = preprocessor.checkpoint();
+        preprocessor.next_token();
+        preprocessor.restore(checkpoint);
+        for _ in 0..500 {
+            preprocessor.next_token();
+        }
+        assert_eq!(preprocessor.consumed.capacity(), 0);
+
+        let outer = preprocessor.checkpoint();
+        let inner = preprocessor.checkpoint();
+        preprocessor.next_token();
+        preprocessor.release(inner);
+        preprocessor.next_token();
+        assert_eq!(preprocessor.consumed.len(), 2);
+        preprocessor.release(outer);
+        for _ in 0..498 {
+            preprocessor.next_token();
+        }
+        assert_eq!(preprocessor.consumed.capacity(), 0);
+        assert_eq!(preprocessor.next_token().kind, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_preprocessor_spans_point_into_headers() {
+        let dir = env::temp_dir().join(format!("c_compiler_spans_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("h.h"), "int x;\n").unwrap();
+
+        let mut sources = SourceMap::new();
+        let main = sources.add_file(dir.join("main.c"), "#include \"h.h\"\nint y;".to_string());
+        let mut preprocessor = Preprocessor::new(&mut sources, main, Standard::default());
+        let spans: Vec<Span> = (0..4).map(|_| preprocessor.next_token().span).collect();
+        assert_eq!(preprocessor.peek_nth(2).kind, TokenKind::EOF);
+        drop(preprocessor);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let locations: Vec<String> = spans
+            .iter()
+            .map(|span| {
+                let location = sources.location(span.start);
+                let file = location.path.file_name().unwrap().to_string_lossy();
+                format!(
+                    "{}:{}:{} {}",
+                    file,
+                    location.line,
+                    location.column,
+                    sources.text(*span)
+                )
+            })
+            .collect();
+        assert_eq!(
+            locations,
+            vec!["h.h:1:1 int", "h.h:1:5 x", "h.h:1:6 ;", "main.c:2:1 int"]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    env,
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::standard::Standard;
+
+/// Macros whose expansion is computed where they are used (C17 6.10.8.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Builtin {
+    Line,
+    File,
+    Date,
+    Time,
+    Counter,
+}
+
+pub(super) const BUILTINS: &[(&str, Builtin)] = &[
+    ("__LINE__", Builtin::Line),
+    ("__FILE__", Builtin::File),
+    ("__DATE__", Builtin::Date),
+    ("__TIME__", Builtin::Time),
+    ("__COUNTER__", Builtin::Counter),
+];
+
+/// `#define` lines for the macros with a fixed value: those of the standard and the ones system
+/// headers check to know the target, x86-64 Linux (LP64)
+pub(super) fn predefined_macros(standard: Standard) -> String {
+    let mut text = String::new();
+    let mut define = |name: &str, value: &str| {
+        writeln!(text, "#define {} {}", name, value).unwrap();
+    };
+
+    define("__STDC__", "1");
+    if let Some(version) = standard.stdc_version() {
+        define("__STDC_VERSION__", version);
+    }
+    define("__STDC_HOSTED__", "1");
+
+    for name in ["__x86_64__", "__x86_64", "__amd64__", "__amd64"] {
+        define(name, "1");
+    }
+    for name in [
+        "__linux__",
+        "__linux",
+        "__gnu_linux__",
+        "__unix__",
+        "__unix",
+    ] {
+        define(name, "1");
+    }
+    define("__ELF__", "1");
+    define("__LP64__", "1");
+    define("_LP64", "1");
+    define("__CHAR_BIT__", "8");
+    define("__SIZE_TYPE__", "unsigned long");
+    define("__PTRDIFF_TYPE__", "long");
+    define("__WCHAR_TYPE__", "int");
+    text
+}
+
+/// `__DATE__` and `__TIME__` as string literals, for the time preprocessing started. The time is
+/// UTC, and `SOURCE_DATE_EPOCH` replaces it for reproducible builds like with gcc
+pub(super) fn build_date_time() -> (String, String) {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs())
+        });
+    date_time(seconds)
+}
+
+/// `"Mmm dd yyyy"` and `"hh:mm:ss"`, the day padded with a space (C17 6.10.8.1)
+pub(super) fn date_time(seconds: u64) -> (String, String) {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    let date = format!("\"{} {:2} {}\"", MONTHS[month as usize - 1], day, year);
+    let time = format!(
+        "\"{:02}:{:02}:{:02}\"",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    (date, time)
+}
+
+/// Year, month and day of a number of days since 1970-01-01, in the proleptic Gregorian
+/// calendar (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months start in March, so that the leap day is last
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month as u32, day as u32)
+}
@@ -0,0 +1,189 @@
+use std::fmt;
+
+use crate::{
+    FloatType,
+    parser::ast::{BinaryOp, CType, Expression, ExpressionKind, Unary},
+};
+
+/// Value of a constant expression, in the representation of its type
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Constant {
+    Int(i64),
+    Float(f32),
+    Double(f64),
+}
+
+impl Constant {
+    pub fn ctype(self) -> CType {
+        match self {
+            Constant::Int(_) => CType::Int,
+            Constant::Float(_) => CType::Float,
+            Constant::Double(_) => CType::Double,
+        }
+    }
+
+    /// Converts the value to the arithmetic type `ctype`, as a cast would. Floating values out
+    /// of the range of `int` are undefined behavior in C and saturate here
+    pub fn convert(self, ctype: &CType) -> Constant {
+        match (ctype, self) {
+            (CType::Float, Constant::Int(value)) => Constant::Float(value as f32),
+            (CType::Float, Constant::Double(value)) => Constant::Float(value as f32),
+            (CType::Double, Constant::Int(value)) => Constant::Double(value as f64),
+            (CType::Double, Constant::Float(value)) => Constant::Double(value as f64),
+            (CType::Int, Constant::Float(value)) => Constant::Int(value as i32 as i64),
+            (CType::Int, Constant::Double(value)) => Constant::Int(value as i32 as i64),
+            _ => self,
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        match self {
+            Constant::Int(value) => value == 0,
+            Constant::Float(value) => value == 0.0,
+            Constant::Double(value) => value == 0.0,
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Constant::Int(value) => value as f64,
+            Constant::Float(value) => value as f64,
+            Constant::Double(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Int(value) => write!(f, "{}", value),
+            Constant::Float(value) => write!(f, "{:?}f", value),
+            Constant::Double(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// Type both operands of an arithmetic operator are converted to (C17 6.3.1.8)
+pub fn common_type(left: &CType, right: &CType) -> CType {
+    if *left == CType::Double || *right == CType::Double {
+        CType::Double
+    } else if *left == CType::Float || *right == CType::Float {
+        CType::Float
+    } else {
+        CType::Int
+    }
+}
+
+/// Folds a global initializer to its value, `None` if it isn't a constant expression
+pub fn evaluate_constant(expr: &Expression) -> Option<Constant> {
+    match &expr.kind {
+        ExpressionKind::IntegerLiteral(value, _) => Some(Constant::Int(*value as i64)),
+        ExpressionKind::FloatLiteral(value, FloatType::Float) => {
+            Some(Constant::Float(*value as f32))
+        }
+        ExpressionKind::FloatLiteral(value, _) => Some(Constant::Double(*value)),
+        ExpressionKind::CharacterLiteral(value) => Some(Constant::Int(*value as i64)),
+        ExpressionKind::Cast(ctype, expr) if ctype.is_arithmetic() => {
+            Some(evaluate_constant(expr)?.convert(ctype))
+        }
+        ExpressionKind::Unary(unary) => {
+            if unary.is_increment() {
+                return None;
+            }
+            let value = evaluate_constant(unary.operand())?;
+            match (unary.as_ref(), value) {
+                (Unary::Negative(_), Constant::Int(value)) => {
+                    Some(Constant::Int(value.wrapping_neg()))
+                }
+                (Unary::Negative(_), Constant::Float(value)) => Some(Constant::Float(-value)),
+                (Unary::Negative(_), Constant::Double(value)) => Some(Constant::Double(-value)),
+                (Unary::Not(_), value) => Some(Constant::Int(value.is_zero() as i64)),
+                (Unary::Complement(_), Constant::Int(value)) => Some(Constant::Int(!value)),
+                (Unary::Complement(_), _) => None,
+                _ => Some(value),
+            }
+        }
+        ExpressionKind::BinaryOp(binary) => {
+            if matches!(binary.as_ref(), BinaryOp::Assign(..) | BinaryOp::Comma(..)) {
+                return None;
+            }
+            let (l, r) = binary.operands();
+            binary_constant(binary, evaluate_constant(l)?, evaluate_constant(r)?)
+        }
+        ExpressionKind::Conditional {
+            condition,
+            then,
+            otherwise,
+        } => {
+            let (then, otherwise) = (evaluate_constant(then)?, evaluate_constant(otherwise)?);
+            let ctype = common_type(&then.ctype(), &otherwise.ctype());
+            if !evaluate_constant(condition)?.is_zero() {
+                Some(then.convert(&ctype))
+            } else {
+                Some(otherwise.convert(&ctype))
+            }
+        }
+        // The address of a string is only known once the program is linked
+        ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Identifier(_)
+        | ExpressionKind::Cast(..)
+        | ExpressionKind::CompoundAssign(_)
+        | ExpressionKind::FunctionCall(..)
+        | ExpressionKind::Subscript(..)
+        | ExpressionKind::Member(..)
+        | ExpressionKind::PointerMember(..) => None,
+    }
+}
+
+fn binary_constant(binary: &BinaryOp, l: Constant, r: Constant) -> Option<Constant> {
+    let bool_constant = |value: bool| Some(Constant::Int(value as i64));
+    match binary {
+        BinaryOp::LogicalAnd(..) => return bool_constant(!l.is_zero() && !r.is_zero()),
+        BinaryOp::LogicalOr(..) => return bool_constant(!l.is_zero() || !r.is_zero()),
+        _ => {}
+    }
+
+    if let (Constant::Int(l), Constant::Int(r)) = (l, r) {
+        return match binary {
+            BinaryOp::Add(..) => Some(Constant::Int(l.wrapping_add(r))),
+            BinaryOp::Subtract(..) => Some(Constant::Int(l.wrapping_sub(r))),
+            BinaryOp::Multiply(..) => Some(Constant::Int(l.wrapping_mul(r))),
+            BinaryOp::Divide(..) => l.checked_div(r).map(Constant::Int),
+            BinaryOp::Remainder(..) => l.checked_rem(r).map(Constant::Int),
+            BinaryOp::Equals(..) => bool_constant(l == r),
+            BinaryOp::NotEquals(..) => bool_constant(l != r),
+            BinaryOp::LessThan(..) => bool_constant(l < r),
+            BinaryOp::LessOrEqual(..) => bool_constant(l <= r),
+            BinaryOp::GreaterThan(..) => bool_constant(l > r),
+            BinaryOp::GreaterOrEqual(..) => bool_constant(l >= r),
+            BinaryOp::BitwiseAnd(..) => Some(Constant::Int(l & r)),
+            BinaryOp::BitwiseOr(..) => Some(Constant::Int(l | r)),
+            BinaryOp::BitwiseXor(..) => Some(Constant::Int(l ^ r)),
+            BinaryOp::ShiftLeft(..) => l.checked_shl(u32::try_from(r).ok()?).map(Constant::Int),
+            BinaryOp::ShiftRight(..) => l.checked_shr(u32::try_from(r).ok()?).map(Constant::Int),
+            BinaryOp::LogicalAnd(..)
+            | BinaryOp::LogicalOr(..)
+            | BinaryOp::Assign(..)
+            | BinaryOp::Comma(..) => unreachable!(),
+        };
+    }
+
+    // Rounding the exact `double` result to `float` gives the correctly rounded `float` result
+    let ctype = common_type(&l.ctype(), &r.ctype());
+    let (l, r) = (l.as_f64(), r.as_f64());
+    let value = match binary {
+        BinaryOp::Add(..) => l + r,
+        BinaryOp::Subtract(..) => l - r,
+        BinaryOp::Multiply(..) => l * r,
+        BinaryOp::Divide(..) => l / r,
+        BinaryOp::Equals(..) => return bool_constant(l == r),
+        BinaryOp::NotEquals(..) => return bool_constant(l != r),
+        BinaryOp::LessThan(..) => return bool_constant(l < r),
+        BinaryOp::LessOrEqual(..) => return bool_constant(l <= r),
+        BinaryOp::GreaterThan(..) => return bool_constant(l > r),
+        BinaryOp::GreaterOrEqual(..) => return bool_constant(l >= r),
+        // Only defined for integers
+        _ => return None,
+    };
+    Some(Constant::Double(value).convert(&ctype))
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use crate::{parser::ast::CType, span::Span};
+
+pub type SemanticResult<T> = Result<T, SemanticError>;
+
+#[derive(Debug)]
+pub enum SemanticError {
+    UndeclaredIdentifier(String, Span),
+    UndeclaredFunction(String, Span),
+    /// A local declared twice in the same scope
+    Redeclaration(String, Span),
+    MissingParameterName(Span),
+    InvalidLvalue(Span),
+    NonConstantInitializer(String, Span),
+    BreakOutsideLoop(Span),
+    ContinueOutsideLoop(Span),
+    DuplicateLabel(String, Span),
+    UndefinedLabel(String, Span),
+    /// A function name used where a value is expected
+    FunctionUsedAsVariable(String, Span),
+    /// A call to something that isn't a function
+    NotAFunction(String, Span),
+    /// Name of the function, expected and found number of arguments
+    WrongArgumentCount(String, usize, usize, Span),
+    /// Expected and found type of an expression
+    MismatchedTypes(CType, CType, Span),
+    /// A redeclaration that doesn't match the previous declaration
+    ConflictingTypes(String, Span),
+    Redefinition(String, Span),
+    ReturnValueInVoidFunction(Span),
+    /// `return;` in a function that returns a value
+    MissingReturnValue(Span),
+    VoidVariable(String, Span),
+    /// Operator applied to an operand of a type it doesn't accept
+    InvalidOperand(&'static str, CType, Span),
+    InvalidCast(CType, CType, Span),
+    /// A literal whose type isn't implemented yet, e.g. `10u`, `5000000000` or `1.0L`
+    UnsupportedLiteralType(&'static str, Span),
+}
+
+impl SemanticError {
+    /// Location in the source the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            SemanticError::UndeclaredIdentifier(_, span)
+            | SemanticError::UndeclaredFunction(_, span)
+            | SemanticError::Redeclaration(_, span)
+            | SemanticError::MissingParameterName(span)
+            | SemanticError::InvalidLvalue(span)
+            | SemanticError::NonConstantInitializer(_, span)
+            | SemanticError::BreakOutsideLoop(span)
+            | SemanticError::ContinueOutsideLoop(span)
+            | SemanticError::DuplicateLabel(_, span)
+            | SemanticError::UndefinedLabel(_, span)
+            | SemanticError::FunctionUsedAsVariable(_, span)
+            | SemanticError::NotAFunction(_, span)
+            | SemanticError::WrongArgumentCount(_, _, _, span)
+            | SemanticError::MismatchedTypes(_, _, span)
+            | SemanticError::ConflictingTypes(_, span)
+            | SemanticError::Redefinition(_, span)
+            | SemanticError::ReturnValueInVoidFunction(span)
+            | SemanticError::MissingReturnValue(span)
+            | SemanticError::VoidVariable(_, span)
+            | SemanticError::InvalidOperand(_, _, span)
+            | SemanticError::InvalidCast(_, _, span)
+            | SemanticError::UnsupportedLiteralType(_, span) => *span,
+        }
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticError::UndeclaredIdentifier(name, _) => {
+                write!(f, "ERROR: Use of undeclared identifier '{}'", name)
+            }
+            SemanticError::UndeclaredFunction(name, _) => {
+                write!(f, "ERROR: Call to undeclared function '{}'", name)
+            }
+            SemanticError::Redeclaration(name, _) => {
+                write!(f, "ERROR: Redeclaration of '{}' in the same scope", name)
+            }
+            SemanticError::MissingParameterName(_) => {
+                write!(f, "ERROR: Parameter name omitted in a function definition")
+            }
+            SemanticError::InvalidLvalue(_) => {
+                write!(f, "ERROR: Expression is not assignable")
+            }
+            SemanticError::NonConstantInitializer(name, _) => write!(
+                f,
+                "ERROR: Initializer of global variable '{}' is not a constant",
+                name
+            ),
+            SemanticError::BreakOutsideLoop(_) => {
+                write!(f, "ERROR: 'break' statement not in a loop")
+            }
+            SemanticError::ContinueOutsideLoop(_) => {
+                write!(f, "ERROR: 'continue' statement not in a loop")
+            }
+            SemanticError::DuplicateLabel(label, _) => {
+                write!(f, "ERROR: Redefinition of label '{}'", label)
+            }
+            SemanticError::UndefinedLabel(label, _) => {
+                write!(f, "ERROR: Use of undeclared label '{}'", label)
+            }
+            SemanticError::FunctionUsedAsVariable(name, _) => {
+                write!(f, "ERROR: Function '{}' used as a variable", name)
+            }
+            SemanticError::NotAFunction(name, _) => {
+                write!(f, "ERROR: Called object '{}' is not a function", name)
+            }
+            SemanticError::WrongArgumentCount(name, expected, found, _) => write!(
+                f,
+                "ERROR: Function '{}' expects {} argument(s), but got {}",
+                name, expected, found
+            ),
+            SemanticError::MismatchedTypes(expected, found, _) => write!(
+                f,
+                "ERROR: Expected an expression of type '{}', but got '{}'",
+                expected, found
+            ),
+            SemanticError::ConflictingTypes(name, _) => {
+                write!(f, "ERROR: Conflicting types for '{}'", name)
+            }
+            SemanticError::Redefinition(name, _) => {
+                write!(f, "ERROR: Redefinition of '{}'", name)
+            }
+            SemanticError::ReturnValueInVoidFunction(_) => {
+                write!(f, "ERROR: Void function should not return a value")
+            }
+            SemanticError::MissingReturnValue(_) => {
+                write!(f, "ERROR: Non-void function should return a value")
+            }
+            SemanticError::VoidVariable(name, _) => {
+                write!(f, "ERROR: Variable '{}' declared void", name)
+            }
+            SemanticError::InvalidOperand(operator, ctype, _) => write!(
+                f,
+                "ERROR: Invalid operand of type '{}' to '{}'",
+                ctype, operator
+            ),
+            SemanticError::InvalidCast(from, to, _) => {
+                write!(f, "ERROR: Cannot cast from '{}' to '{}'", from, to)
+            }
+            SemanticError::UnsupportedLiteralType(ty, _) => {
+                write!(f, "ERROR: Literals of type '{}' are not supported yet", ty)
+            }
+        }
+    }
+}
@@ -0,0 +1,393 @@
+mod constant;
+mod error;
+mod resolve;
+mod symbols;
+mod typecheck;
+
+pub use crate::semantic::{
+    constant::{Constant, common_type, evaluate_constant},
+    error::{SemanticError, SemanticResult},
+    symbols::{Symbol, SymbolKind, SymbolTable},
+};
+use crate::{
+    parser::ast::TranslationUnit,
+    semantic::{resolve::Resolver, typecheck::TypeChecker},
+};
+
+/// Public API for validating a parsed `TranslationUnit`. Identifiers are resolved in place, so
+/// after a successful analysis every local has a name unique in the translation unit and every
+/// expression is annotated with its type
+pub fn analyze(unit: &mut TranslationUnit) -> Result<SymbolTable, Vec<SemanticError>> {
+    Resolver::default().resolve(unit)?;
+    TypeChecker::default().check(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse,
+        parser::ast::{CType, Declaration, Expression, ExpressionKind, StatementKind},
+        span::Span,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn analyze_src(src: &str) -> Result<TranslationUnit, Vec<SemanticError>> {
+        let mut unit = parse(src).unwrap();
+        analyze(&mut unit).map(|_| unit)
+    }
+
+    /// Names declared by the top-level statements of the first function
+    fn declared_names(unit: &TranslationUnit) -> Vec<String> {
+        let Declaration::Function(function) = &unit.declarations[0] else {
+            panic!("expected a function");
+        };
+        let mut names: Vec<_> = function
+            .parameters
+            .iter()
+            .flatten()
+            .filter_map(|parameter| parameter.name.clone())
+            .collect();
+        for statement in function.body.iter().flatten() {
+            match &statement.kind {
+                StatementKind::Declaration(variable) => names.push(variable.name.clone()),
+                StatementKind::Compound(statements) => {
+                    for statement in statements {
+                        if let StatementKind::Declaration(variable) = &statement.kind {
+                            names.push(variable.name.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn test_semantic_renames_shadowed_variables() {
+        let unit =
+            analyze_src("int f(int a) { int b = a; { int a = 2; int b = a; } return b; }").unwrap();
+
+        assert_eq!(declared_names(&unit), ["a.0", "b.1", "a.2", "b.3"]);
+    }
+
+    #[test]
+    fn test_semantic_redeclaration_in_same_scope() {
+        let errors = analyze_src("int main(void) { int a; int a; }").unwrap_err();
+        assert!(
+            matches!(&errors[..], [SemanticError::Redeclaration(name, span)]
+            if name == "a" && *span == Span::new(24, 30))
+        );
+
+        let errors = analyze_src("int f(int a) { int a; }").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::Redeclaration(..)]));
+    }
+
+    #[test]
+    fn test_semantic_invalid_lvalue() {
+        let errors = analyze_src("int main(void) { int a; a = 6 = 8; }").unwrap_err();
+
+        assert!(matches!(&errors[..], [SemanticError::InvalidLvalue(span)]
+            if *span == Span::new(28, 29)));
+    }
+
+    #[test]
+    fn test_semantic_increment_needs_lvalue() {
+        let errors =
+            analyze_src("int main(void) { int a; ++a; a--; (a + 1)++; a += 1 = 2; }").unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::InvalidLvalue(first), SemanticError::InvalidLvalue(second)]
+                if *first == Span::new(34, 41) && *second == Span::new(50, 51)
+        ));
+    }
+
+    #[test]
+    fn test_semantic_no_aggregate_types_yet() {
+        let errors = analyze_src("int main(void) { int a; return a[0] + a.b; }").unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [
+                SemanticError::InvalidOperand("[]", CType::Int, _),
+                SemanticError::InvalidOperand(".", CType::Int, _),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_semantic_folds_constant_operators() {
+        let unit = parse("int a = 7 % 4 << 2 | !0; int b = 1 ? 2 : 3 > 4 && 0;").unwrap();
+        let [Declaration::Variable(a), Declaration::Variable(b)] = &unit.declarations[..] else {
+            panic!("expected two globals");
+        };
+
+        assert_eq!(
+            evaluate_constant(a.initilizer.as_ref().unwrap()),
+            Some(Constant::Int(13))
+        );
+        assert_eq!(
+            evaluate_constant(b.initilizer.as_ref().unwrap()),
+            Some(Constant::Int(2))
+        );
+    }
+
+    #[test]
+    fn test_semantic_reports_all_errors() {
+        let errors =
+            analyze_src("int main(void) { a = 1; { int b; } return b + f(); }").unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [
+                SemanticError::UndeclaredIdentifier(a, _),
+                SemanticError::UndeclaredIdentifier(b, _),
+                SemanticError::UndeclaredFunction(f, _),
+            ] if a == "a" && b == "b" && f == "f"
+        ));
+    }
+
+    #[test]
+    fn test_semantic_jump_errors() {
+        let errors = analyze_src("int main(void) { break; }").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::BreakOutsideLoop(_)]));
+
+        let errors = analyze_src("int main(void) { goto nowhere; }").unwrap_err();
+        assert!(
+            matches!(&errors[..], [SemanticError::UndefinedLabel(label, _)]
+            if label == "nowhere")
+        );
+
+        let errors = analyze_src("int main(void) { a: ; a: ; }").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::DuplicateLabel(label, _)] if label == "a"));
+    }
+
+    #[test]
+    fn test_semantic_global_initializer_must_be_constant() {
+        let errors = analyze_src("int a = 1; int b = a;").unwrap_err();
+
+        assert!(
+            matches!(&errors[..], [SemanticError::NonConstantInitializer(name, _)]
+            if name == "b")
+        );
+    }
+
+    #[test]
+    fn test_semantic_symbol_table() {
+        let mut unit = parse("int f(int a); int x; int x = 3; int f(int b) { return b; }").unwrap();
+        let symbols = analyze(&mut unit).unwrap();
+
+        assert_eq!(
+            symbols.get("f").unwrap().kind,
+            SymbolKind::Function {
+                return_type: CType::Int,
+                params: Some(vec![CType::Int]),
+                defined: true,
+            }
+        );
+        assert_eq!(
+            symbols.get("x").unwrap().kind,
+            SymbolKind::Global {
+                ctype: CType::Int,
+                init: Some(Constant::Int(3)),
+            }
+        );
+        assert_eq!(
+            symbols.get("b.1").unwrap().kind,
+            SymbolKind::Local(CType::Int)
+        );
+    }
+
+    #[test]
+    fn test_semantic_annotates_expression_types() {
+        let unit = analyze_src("void g(void); int main(void) { g(); return 1 + 2; }").unwrap();
+
+        let Declaration::Function(main) = &unit.declarations[1] else {
+            panic!("expected a function");
+        };
+        let body = main.body.as_ref().unwrap();
+        let (StatementKind::ExpressionStatment(call), StatementKind::Return(Some(sum))) =
+            (&body[0].kind, &body[1].kind)
+        else {
+            panic!("unexpected statements");
+        };
+        assert_eq!(call.ctype, Some(CType::Void));
+        assert_eq!(sum.ctype, Some(CType::Int));
+    }
+
+    #[test]
+    fn test_semantic_call_errors() {
+        let errors = analyze_src("int f(int a); int main(void) { return f(1, 2); }").unwrap_err();
+        assert!(
+            matches!(&errors[..], [SemanticError::WrongArgumentCount(name, 1, 2, _)]
+            if name == "f")
+        );
+
+        let errors = analyze_src("void g(void); int f(int a); int main(void) { return f(g()); }")
+            .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::MismatchedTypes(CType::Int, CType::Void, span)]
+                if *span == Span::new(54, 57)
+        ));
+
+        let errors = analyze_src("int main(void) { int f = 1; return f(); }").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::NotAFunction(name, _)] if name == "f"));
+
+        let errors = analyze_src("int f(void); int main(void) { return f + 1; }").unwrap_err();
+        assert!(
+            matches!(&errors[..], [SemanticError::FunctionUsedAsVariable(name, _)]
+            if name == "f")
+        );
+    }
+
+    #[test]
+    fn test_semantic_conflicting_declarations() {
+        let errors = analyze_src("int f(int a); int f(void);").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::ConflictingTypes(name, _)] if name == "f"));
+
+        let errors = analyze_src("int f; int f(void);").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::ConflictingTypes(..)]));
+
+        let errors =
+            analyze_src("int f(void) { return 1; } int f(void) { return 2; }").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::Redefinition(name, _)] if name == "f"));
+
+        let errors = analyze_src("int x = 1; int x = 2;").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::Redefinition(name, _)] if name == "x"));
+    }
+
+    #[test]
+    fn test_semantic_void_errors() {
+        let errors = analyze_src("void f(void) { return 1; }").unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::ReturnValueInVoidFunction(_)]
+        ));
+
+        let errors = analyze_src("int f(void) { return; }").unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::MissingReturnValue(span)] if *span == Span::new(14, 21)
+        ));
+
+        // Void functions can return early
+        analyze_src("void f(int x) { if (x) return; x = 1; return; }").unwrap();
+
+        let errors = analyze_src("int main(void) { void a; }").unwrap_err();
+        assert!(matches!(&errors[..], [SemanticError::VoidVariable(name, _)] if name == "a"));
+    }
+
+    #[test]
+    fn test_semantic_unsupported_literals() {
+        for src in [
+            "int main(void) { return 4000000000u > 0; }",
+            "int main(void) { return 0xFFFFFFFF > 0; }",
+            "int main(void) { return -1 < 0u; }",
+            "int main(void) { int x = 5000000000; return x; }",
+            "int x = 5000000000;",
+            "int main(void) { return 1L; }",
+            "int main(void) { return 1.0L > 0; }",
+            "double d = 2.5l;",
+        ] {
+            let errors = analyze_src(src).unwrap_err();
+            assert!(
+                matches!(&errors[..], [SemanticError::UnsupportedLiteralType(..)]),
+                "{}",
+                src
+            );
+        }
+
+        let errors = analyze_src("int main(void) { return 10u; }").unwrap_err();
+        assert_eq!(errors[0].span(), Span::new(24, 27));
+        assert_eq!(
+            errors[0].to_string(),
+            "ERROR: Literals of type 'unsigned int' are not supported yet"
+        );
+
+        // Hexadecimal and octal literals that fit in an `int` are `int`
+        analyze_src("int main(void) { return 0x7FFFFFFF + 017 + 2147483647; }").unwrap();
+    }
+
+    #[test]
+    fn test_semantic_string_literals() {
+        let src = r#"int puts(); int main(void) { puts("hi"); return 'a'; }"#;
+        assert!(analyze_src(src).is_ok());
+
+        let errors = analyze_src(r#"int main(void) { return "a"; }"#).unwrap_err();
+        let char_pointer = CType::Pointer(Box::new(CType::Char));
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::MismatchedTypes(CType::Int, found, span)]
+                if *found == char_pointer && *span == Span::new(24, 27)
+        ));
+
+        let errors = analyze_src(r#"int main(void) { return 1 ? "a" : 0; }"#).unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::InvalidOperand("?:", _, _)]
+        ));
+
+        let errors = analyze_src(r#"int x = "a";"#).unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::NonConstantInitializer(..)]
+        ));
+        assert_eq!(
+            evaluate_constant(&Expression::new(
+                ExpressionKind::CharacterLiteral(-1),
+                Span::new(0, 0)
+            )),
+            Some(Constant::Int(-1))
+        );
+    }
+
+    #[test]
+    fn test_semantic_floating_conversions() {
+        let mut unit = parse("int x = 2.5 * 2; float y = 1; double z = 1.0f / 4;").unwrap();
+        let symbols = analyze(&mut unit).unwrap();
+
+        let init = |name| match &symbols.get(name).unwrap().kind {
+            SymbolKind::Global { init, .. } => *init,
+            _ => panic!("expected a global"),
+        };
+        assert_eq!(init("x"), Some(Constant::Int(5)));
+        assert_eq!(init("y"), Some(Constant::Float(1.0)));
+        assert_eq!(init("z"), Some(Constant::Double(0.25)));
+
+        // The `int` operand is converted to the common type `double`
+        let unit = analyze_src("int main(void) { int i = 1; return i + 0.5; }").unwrap();
+        let Declaration::Function(main) = &unit.declarations[0] else {
+            panic!("expected a function");
+        };
+        let StatementKind::Return(Some(sum)) = &main.body.as_ref().unwrap()[1].kind else {
+            panic!("expected a return");
+        };
+        assert_eq!(sum.ctype, Some(CType::Int));
+        let ExpressionKind::Cast(CType::Int, sum) = &sum.kind else {
+            panic!("expected a conversion to the return type");
+        };
+        assert_eq!(sum.ctype, Some(CType::Double));
+    }
+
+    #[test]
+    fn test_semantic_floating_errors() {
+        let errors = analyze_src("int main(void) { return 1.5 % 2; }").unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::InvalidOperand("%", CType::Double, _)]
+        ));
+
+        let errors = analyze_src(r#"int main(void) { return (double)"a"; }"#).unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [SemanticError::InvalidCast(
+                CType::Pointer(_),
+                CType::Double,
+                _
+            )]
+        ));
+    }
+}
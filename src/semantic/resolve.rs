@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use crate::{
+    parser::ast::{
+        BinaryOp, Declaration, Expression, ExpressionKind, FnParameter, ForInit,
+        FunctionDeclaration, Statement, StatementKind, TranslationUnit, VariableDeclaration,
+    },
+    semantic::SemanticError,
+    span::Span,
+};
+
+/// Resolves every identifier to its declaration, renaming locals so that each one has a name
+/// unique in the whole translation unit. Errors are collected instead of stopping at the first one
+#[derive(Default)]
+pub struct Resolver {
+    /// Identifiers visible at the current point, innermost scope last. The first scope is the
+    /// file scope, whose names are kept as they are
+    scopes: Vec<HashMap<String, String>>,
+    /// Number of loops enclosing the current statement
+    loop_depth: usize,
+    /// Labels defined in the function being resolved
+    labels: HashMap<String, Span>,
+    /// `goto` statements in the function being resolved, checked once all labels are known
+    gotos: Vec<(String, Span)>,
+    /// Counter for unique variable names
+    counter: usize,
+    errors: Vec<SemanticError>,
+}
+
+impl Resolver {
+    pub fn resolve(mut self, unit: &mut TranslationUnit) -> Result<(), Vec<SemanticError>> {
+        self.scopes.push(HashMap::new());
+        for declaration in &mut unit.declarations {
+            match declaration {
+                Declaration::Variable(variable) => self.global_variable(variable),
+                Declaration::Function(function) => self.function(function),
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn global_variable(&mut self, variable: &VariableDeclaration) {
+        // The initializer must be a constant, which the type checker verifies
+        self.scopes[0].insert(variable.name.clone(), variable.name.clone());
+    }
+
+    fn function(&mut self, function: &mut FunctionDeclaration) {
+        // Declared before the body, so the function can call itself
+        self.scopes[0].insert(function.name.clone(), function.name.clone());
+
+        // Parameters live in the same scope as the outermost block of the body
+        self.scopes.push(HashMap::new());
+        for parameter in function.parameters.iter_mut().flatten() {
+            self.parameter(parameter, function.body.is_some());
+        }
+
+        if let Some(body) = &mut function.body {
+            self.labels.clear();
+            self.gotos.clear();
+            for statement in body {
+                self.statement(statement);
+            }
+
+            for (label, span) in std::mem::take(&mut self.gotos) {
+                if !self.labels.contains_key(&label) {
+                    self.errors.push(SemanticError::UndefinedLabel(label, span));
+                }
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn parameter(&mut self, parameter: &mut FnParameter, is_definition: bool) {
+        match &mut parameter.name {
+            Some(name) => *name = self.declare_local(name, parameter.span),
+            None if is_definition => self
+                .errors
+                .push(SemanticError::MissingParameterName(parameter.span)),
+            None => {}
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StatementKind::Declaration(variable) => self.local_variable(variable),
+            StatementKind::ExpressionStatment(expr) | StatementKind::Return(Some(expr)) => {
+                self.expression(expr)
+            }
+            StatementKind::Return(None) => {}
+            StatementKind::Compound(statements) => {
+                self.scopes.push(HashMap::new());
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            StatementKind::Null => {}
+            StatementKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expression(condition);
+                self.statement(then);
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise);
+                }
+            }
+            StatementKind::While { condition, body } => {
+                self.expression(condition);
+                self.loop_body(body);
+            }
+            StatementKind::DoWhile { body, condition } => {
+                self.loop_body(body);
+                self.expression(condition);
+            }
+            StatementKind::For {
+                init,
+                condition,
+                post,
+                body,
+            } => {
+                // A declaration in the init clause is scoped to the loop
+                self.scopes.push(HashMap::new());
+                match init {
+                    ForInit::Declaration(variable) => self.local_variable(variable),
+                    ForInit::Expression(Some(expr)) => self.expression(expr),
+                    ForInit::Expression(None) => {}
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                if let Some(post) = post {
+                    self.expression(post);
+                }
+                self.loop_body(body);
+                self.scopes.pop();
+            }
+            StatementKind::Break => {
+                if self.loop_depth == 0 {
+                    self.errors
+                        .push(SemanticError::BreakOutsideLoop(statement.span));
+                }
+            }
+            StatementKind::Continue => {
+                if self.loop_depth == 0 {
+                    self.errors
+                        .push(SemanticError::ContinueOutsideLoop(statement.span));
+                }
+            }
+            StatementKind::Goto(label) => self.gotos.push((label.clone(), statement.span)),
+            StatementKind::Labeled(label, inner) => {
+                if self.labels.insert(label.clone(), statement.span).is_some() {
+                    self.errors
+                        .push(SemanticError::DuplicateLabel(label.clone(), statement.span));
+                }
+                self.statement(inner);
+            }
+        }
+    }
+
+    fn local_variable(&mut self, variable: &mut VariableDeclaration) {
+        variable.name = self.declare_local(&variable.name, variable.span);
+        // The variable is in scope in its own initializer, as in C
+        if let Some(init) = &mut variable.initilizer {
+            self.expression(init);
+        }
+    }
+
+    fn loop_body(&mut self, body: &mut Statement) {
+        self.loop_depth += 1;
+        self.statement(body);
+        self.loop_depth -= 1;
+    }
+
+    fn expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            ExpressionKind::IntegerLiteral(..)
+            | ExpressionKind::FloatLiteral(..)
+            | ExpressionKind::CharacterLiteral(_)
+            | ExpressionKind::StringLiteral(_) => {}
+            ExpressionKind::Identifier(name) => match self.lookup(name) {
+                Some(unique) => *name = unique,
+                None => self
+                    .errors
+                    .push(SemanticError::UndeclaredIdentifier(name.clone(), expr.span)),
+            },
+            ExpressionKind::Unary(unary) => {
+                if unary.is_increment() {
+                    self.check_lvalue(unary.operand());
+                }
+                self.expression(unary.operand_mut());
+            }
+            ExpressionKind::BinaryOp(binary) => {
+                if let BinaryOp::Assign(target, _) = binary.as_ref() {
+                    self.check_lvalue(target);
+                }
+                let (left, right) = binary.operands_mut();
+                self.expression(left);
+                self.expression(right);
+            }
+            ExpressionKind::CompoundAssign(binary) => {
+                self.check_lvalue(binary.operands().0);
+                let (target, value) = binary.operands_mut();
+                self.expression(target);
+                self.expression(value);
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expression(condition);
+                self.expression(then);
+                self.expression(otherwise);
+            }
+            ExpressionKind::Cast(_, expr) => self.expression(expr),
+            ExpressionKind::Subscript(array, index) => {
+                self.expression(array);
+                self.expression(index);
+            }
+            // Member names are looked up by the type checker, in the type of the operand
+            ExpressionKind::Member(expr, _) | ExpressionKind::PointerMember(expr, _) => {
+                self.expression(expr)
+            }
+            ExpressionKind::FunctionCall(name, args) => {
+                match self.lookup(name) {
+                    Some(unique) => *name = unique,
+                    None => self
+                        .errors
+                        .push(SemanticError::UndeclaredFunction(name.clone(), expr.span)),
+                }
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+        }
+    }
+
+    /// Only expressions designating an object can be assigned to
+    fn check_lvalue(&mut self, expr: &Expression) {
+        if !matches!(
+            expr.kind,
+            ExpressionKind::Identifier(_)
+                | ExpressionKind::Subscript(..)
+                | ExpressionKind::Member(..)
+                | ExpressionKind::PointerMember(..)
+        ) {
+            self.errors.push(SemanticError::InvalidLvalue(expr.span));
+        }
+    }
+
+    /// Declares `name` in the innermost scope and returns its unique name
+    fn declare_local(&mut self, name: &str, span: Span) -> String {
+        let unique = format!("{}.{}", name, self.counter);
+        self.counter += 1;
+
+        let scope = self.scopes.last_mut().expect("a local scope is open");
+        if scope.insert(name.to_string(), unique.clone()).is_some() {
+            self.errors
+                .push(SemanticError::Redeclaration(name.to_string(), span));
+        }
+
+        unique
+    }
+
+    /// Unique name of the innermost declaration of `name`
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use crate::{parser::ast::CType, semantic::Constant, span::Span};
+
+/// Every function and variable of a translation unit, keyed by its resolved name
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Symbol> {
+        self.symbols.get_mut(name)
+    }
+
+    pub fn insert(&mut self, name: String, symbol: Symbol) {
+        self.symbols.insert(name, symbol);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    /// The first declaration of the symbol
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SymbolKind {
+    Function {
+        return_type: CType,
+        /// `None` until a declaration with a prototype is seen
+        params: Option<Vec<CType>>,
+        defined: bool,
+    },
+    Global {
+        ctype: CType,
+        /// `None` while only tentative definitions (without initializer) were seen
+        init: Option<Constant>,
+    },
+    Local(CType),
+}
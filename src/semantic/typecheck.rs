@@ -0,0 +1,582 @@
+use crate::{
+    FloatType, IntegerType,
+    parser::ast::{
+        BinaryOp, CType, Declaration, Expression, ExpressionKind, ForInit, FunctionDeclaration,
+        Statement, StatementKind, TranslationUnit, Unary, VariableDeclaration,
+    },
+    semantic::{
+        SemanticError, common_type, evaluate_constant,
+        symbols::{Symbol, SymbolKind, SymbolTable},
+    },
+    span::Span,
+};
+
+/// Records every declaration in a `SymbolTable` and annotates each expression with its type.
+/// Runs after the resolver, so every name refers to exactly one symbol
+#[derive(Default)]
+pub struct TypeChecker {
+    symbols: SymbolTable,
+    /// Return type of the function being checked
+    return_type: Option<CType>,
+    errors: Vec<SemanticError>,
+}
+
+impl TypeChecker {
+    pub fn check(mut self, unit: &mut TranslationUnit) -> Result<SymbolTable, Vec<SemanticError>> {
+        for declaration in &mut unit.declarations {
+            match declaration {
+                Declaration::Variable(variable) => self.global_variable(variable),
+                Declaration::Function(function) => self.function(function),
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.symbols)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn global_variable(&mut self, variable: &mut VariableDeclaration) {
+        self.check_not_void(variable);
+        let init = match &mut variable.initilizer {
+            Some(expr) => {
+                let init = evaluate_constant(expr);
+                match init {
+                    // Constant expressions contain no identifiers, so they are safe to check
+                    Some(_) => self.assign(expr, &variable.ctype),
+                    None => self.errors.push(SemanticError::NonConstantInitializer(
+                        variable.name.clone(),
+                        expr.span,
+                    )),
+                }
+                init.map(|init| init.convert(&variable.ctype))
+            }
+            None => None,
+        };
+
+        let Some(symbol) = self.symbols.get_mut(&variable.name) else {
+            self.symbols.insert(
+                variable.name.clone(),
+                Symbol {
+                    kind: SymbolKind::Global {
+                        ctype: variable.ctype.clone(),
+                        init,
+                    },
+                    span: variable.span,
+                },
+            );
+            return;
+        };
+
+        match &mut symbol.kind {
+            SymbolKind::Global { ctype, .. } if *ctype != variable.ctype => self.errors.push(
+                SemanticError::ConflictingTypes(variable.name.clone(), variable.span),
+            ),
+            SymbolKind::Global {
+                init: previous @ None,
+                ..
+            } => *previous = init,
+            SymbolKind::Global { .. } if init.is_none() => {}
+            SymbolKind::Global { .. } => self.errors.push(SemanticError::Redefinition(
+                variable.name.clone(),
+                variable.span,
+            )),
+            SymbolKind::Function { .. } | SymbolKind::Local(_) => self.errors.push(
+                SemanticError::ConflictingTypes(variable.name.clone(), variable.span),
+            ),
+        }
+    }
+
+    fn function(&mut self, function: &mut FunctionDeclaration) {
+        let params: Option<Vec<CType>> = function
+            .parameters
+            .as_ref()
+            .map(|params| params.iter().map(|param| param.ctype.clone()).collect());
+        self.declare_function(function, params);
+
+        for param in function.parameters.iter().flatten() {
+            if param.ctype == CType::Void {
+                let name = param.name.as_deref().map_or("", source_name);
+                self.errors
+                    .push(SemanticError::VoidVariable(name.to_string(), param.span));
+            }
+            if let Some(name) = &param.name {
+                self.declare_local(name, param.ctype.clone(), param.span);
+            }
+        }
+
+        if let Some(body) = &mut function.body {
+            self.return_type = Some(function.return_type.clone());
+            for statement in body {
+                self.statement(statement);
+            }
+            self.return_type = None;
+        }
+    }
+
+    /// Records a function declaration, checking that it agrees with the previous ones
+    fn declare_function(&mut self, function: &FunctionDeclaration, params: Option<Vec<CType>>) {
+        let is_definition = function.body.is_some();
+        let Some(symbol) = self.symbols.get_mut(&function.name) else {
+            self.symbols.insert(
+                function.name.clone(),
+                Symbol {
+                    kind: SymbolKind::Function {
+                        return_type: function.return_type.clone(),
+                        params,
+                        defined: is_definition,
+                    },
+                    span: function.span,
+                },
+            );
+            return;
+        };
+
+        let SymbolKind::Function {
+            return_type,
+            params: previous_params,
+            defined,
+        } = &mut symbol.kind
+        else {
+            self.errors.push(SemanticError::ConflictingTypes(
+                function.name.clone(),
+                function.span,
+            ));
+            return;
+        };
+
+        // A declaration without a prototype `()` is compatible with any parameter list
+        let params_conflict = matches!((&previous_params, &params), (Some(a), Some(b)) if a != b);
+        if *return_type != function.return_type || params_conflict {
+            self.errors.push(SemanticError::ConflictingTypes(
+                function.name.clone(),
+                function.span,
+            ));
+        } else if *defined && is_definition {
+            self.errors.push(SemanticError::Redefinition(
+                function.name.clone(),
+                function.span,
+            ));
+        }
+
+        if previous_params.is_none() {
+            *previous_params = params;
+        }
+        *defined |= is_definition;
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StatementKind::Declaration(variable) => self.local_variable(variable),
+            StatementKind::ExpressionStatment(expr) => {
+                self.expression(expr);
+            }
+            StatementKind::Return(expr) => match (self.return_type.clone(), expr) {
+                (Some(CType::Void), Some(expr)) => {
+                    self.expression(expr);
+                    self.errors
+                        .push(SemanticError::ReturnValueInVoidFunction(expr.span));
+                }
+                (Some(CType::Void), None) => {}
+                (Some(return_type), Some(expr)) => self.assign(expr, &return_type),
+                (Some(_), None) => self
+                    .errors
+                    .push(SemanticError::MissingReturnValue(statement.span)),
+                (None, _) => unreachable!("return statements are only checked inside functions"),
+            },
+            StatementKind::Compound(statements) => {
+                for statement in statements {
+                    self.statement(statement);
+                }
+            }
+            StatementKind::Null
+            | StatementKind::Break
+            | StatementKind::Continue
+            | StatementKind::Goto(_) => {}
+            StatementKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.condition(condition);
+                self.statement(then);
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise);
+                }
+            }
+            StatementKind::While { condition, body }
+            | StatementKind::DoWhile { body, condition } => {
+                self.condition(condition);
+                self.statement(body);
+            }
+            StatementKind::For {
+                init,
+                condition,
+                post,
+                body,
+            } => {
+                match init {
+                    ForInit::Declaration(variable) => self.local_variable(variable),
+                    ForInit::Expression(Some(expr)) => {
+                        self.expression(expr);
+                    }
+                    ForInit::Expression(None) => {}
+                }
+                if let Some(condition) = condition {
+                    self.condition(condition);
+                }
+                if let Some(post) = post {
+                    self.expression(post);
+                }
+                self.statement(body);
+            }
+            StatementKind::Labeled(_, inner) => self.statement(inner),
+        }
+    }
+
+    fn local_variable(&mut self, variable: &mut VariableDeclaration) {
+        self.check_not_void(variable);
+        self.declare_local(&variable.name, variable.ctype.clone(), variable.span);
+        if let Some(init) = &mut variable.initilizer {
+            self.assign(init, &variable.ctype);
+        }
+    }
+
+    fn declare_local(&mut self, name: &str, ctype: CType, span: Span) {
+        self.symbols.insert(
+            name.to_string(),
+            Symbol {
+                kind: SymbolKind::Local(ctype),
+                span,
+            },
+        );
+    }
+
+    fn check_not_void(&mut self, variable: &VariableDeclaration) {
+        if variable.ctype == CType::Void {
+            self.errors.push(SemanticError::VoidVariable(
+                source_name(&variable.name).to_string(),
+                variable.span,
+            ));
+        }
+    }
+
+    /// Checks an expression whose value is used, which rules out `void`
+    fn value(&mut self, expr: &mut Expression) {
+        let ctype = self.expression(expr);
+        if ctype == CType::Void {
+            self.errors
+                .push(SemanticError::MismatchedTypes(CType::Int, ctype, expr.span));
+        }
+    }
+
+    /// Checks an expression whose value is converted to `target` as if by assignment. Only
+    /// conversions between arithmetic types are implicit
+    fn assign(&mut self, expr: &mut Expression, target: &CType) {
+        let ctype = self.expression(expr);
+        if ctype.is_arithmetic() && target.is_arithmetic() {
+            convert(expr, target);
+        } else if ctype != *target {
+            self.errors.push(SemanticError::MismatchedTypes(
+                target.clone(),
+                ctype,
+                expr.span,
+            ));
+        }
+    }
+
+    /// Checks the controlling expression of a statement or conditional operator, which is
+    /// compared against zero
+    fn condition(&mut self, expr: &mut Expression) {
+        let ctype = self.expression(expr);
+        if !ctype.is_arithmetic() {
+            self.errors
+                .push(SemanticError::MismatchedTypes(CType::Int, ctype, expr.span));
+        }
+    }
+
+    /// Checks the operand of an operator that works on any arithmetic type and returns its type
+    fn arithmetic(&mut self, expr: &mut Expression, operator: &'static str) -> CType {
+        let ctype = self.expression(expr);
+        if ctype.is_arithmetic() {
+            ctype
+        } else {
+            self.errors
+                .push(SemanticError::InvalidOperand(operator, ctype, expr.span));
+            CType::Int
+        }
+    }
+
+    /// Checks the operand of an operator that is only defined for integers
+    fn integer(&mut self, expr: &mut Expression, operator: &'static str) {
+        let ctype = self.expression(expr);
+        if !ctype.is_arithmetic() || ctype.is_floating() {
+            self.errors
+                .push(SemanticError::InvalidOperand(operator, ctype, expr.span));
+        }
+    }
+
+    /// Annotates `expr` and its subexpressions with their types and returns the type of `expr`
+    fn expression(&mut self, expr: &mut Expression) -> CType {
+        let ctype = match &mut expr.kind {
+            ExpressionKind::IntegerLiteral(_, IntegerType::Int) => CType::Int,
+            // Only `int` is implemented so far. Treating the other types as `int` would silently
+            // change values and comparisons
+            ExpressionKind::IntegerLiteral(_, ty) => {
+                self.errors
+                    .push(SemanticError::UnsupportedLiteralType(ty.name(), expr.span));
+                CType::Int
+            }
+            ExpressionKind::FloatLiteral(_, FloatType::Float) => CType::Float,
+            ExpressionKind::FloatLiteral(_, FloatType::Double) => CType::Double,
+            // Computing in `double` instead would lose the extra precision without a word
+            ExpressionKind::FloatLiteral(_, FloatType::LongDouble) => {
+                self.errors.push(SemanticError::UnsupportedLiteralType(
+                    "long double",
+                    expr.span,
+                ));
+                CType::Double
+            }
+            ExpressionKind::CharacterLiteral(_) => CType::Int,
+            // The array a string literal denotes decays to a pointer to its first character
+            ExpressionKind::StringLiteral(_) => CType::Pointer(Box::new(CType::Char)),
+            ExpressionKind::Identifier(name) => match self.symbols.get(name).map(|s| &s.kind) {
+                Some(SymbolKind::Global { ctype, .. } | SymbolKind::Local(ctype)) => ctype.clone(),
+                Some(SymbolKind::Function { .. }) => {
+                    self.errors.push(SemanticError::FunctionUsedAsVariable(
+                        source_name(name).to_string(),
+                        expr.span,
+                    ));
+                    CType::Int
+                }
+                None => unreachable!("identifiers are resolved before type checking"),
+            },
+            // Any value can be discarded with a cast to `void`
+            ExpressionKind::Cast(target, operand) => {
+                let ctype = self.expression(operand);
+                let valid = *target == CType::Void
+                    || (target.is_arithmetic() && ctype.is_arithmetic())
+                    || *target == ctype;
+                if !valid {
+                    self.errors
+                        .push(SemanticError::InvalidCast(ctype, target.clone(), expr.span));
+                }
+                target.clone()
+            }
+            ExpressionKind::Unary(unary) => match unary.as_mut() {
+                Unary::Not(operand) => {
+                    self.arithmetic(operand, "!");
+                    CType::Int
+                }
+                Unary::Complement(operand) => {
+                    self.integer(operand, "~");
+                    CType::Int
+                }
+                unary => {
+                    let operator = unary.symbol();
+                    self.arithmetic(unary.operand_mut(), operator)
+                }
+            },
+            ExpressionKind::BinaryOp(binary) => match binary.as_mut() {
+                BinaryOp::Assign(target, value) => {
+                    let ctype = self.expression(target);
+                    self.assign(value, &ctype);
+                    ctype
+                }
+                // The left operand is evaluated only for its side effects
+                BinaryOp::Comma(left, right) => {
+                    self.expression(left);
+                    self.expression(right)
+                }
+                // Each operand is compared against zero on its own
+                binary @ (BinaryOp::LogicalAnd(..) | BinaryOp::LogicalOr(..)) => {
+                    let operator = binary.symbol();
+                    let (left, right) = binary.operands_mut();
+                    self.arithmetic(left, operator);
+                    self.arithmetic(right, operator);
+                    CType::Int
+                }
+                binary if binary.is_integer_only() => {
+                    let operator = binary.symbol();
+                    let (left, right) = binary.operands_mut();
+                    self.integer(left, operator);
+                    self.integer(right, operator);
+                    CType::Int
+                }
+                binary => {
+                    let operator = binary.symbol();
+                    let is_comparison = matches!(
+                        binary,
+                        BinaryOp::Equals(..)
+                            | BinaryOp::NotEquals(..)
+                            | BinaryOp::LessThan(..)
+                            | BinaryOp::LessOrEqual(..)
+                            | BinaryOp::GreaterThan(..)
+                            | BinaryOp::GreaterOrEqual(..)
+                    );
+                    let (left, right) = binary.operands_mut();
+                    let ctype = common_type(
+                        &self.arithmetic(left, operator),
+                        &self.arithmetic(right, operator),
+                    );
+                    convert(left, &ctype);
+                    convert(right, &ctype);
+                    if is_comparison { CType::Int } else { ctype }
+                }
+            },
+            // The value is converted to the type the operation is performed in, the target is
+            // converted to and from it when the TACKY is generated
+            ExpressionKind::CompoundAssign(binary) => {
+                let operator = binary.symbol();
+                let is_integer_only = binary.is_integer_only();
+                let (target, value) = binary.operands_mut();
+                if is_integer_only {
+                    self.integer(target, operator);
+                    self.integer(value, operator);
+                    CType::Int
+                } else {
+                    let target_type = self.arithmetic(target, operator);
+                    let value_type = self.arithmetic(value, operator);
+                    convert(value, &common_type(&target_type, &value_type));
+                    target_type
+                }
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.condition(condition);
+                let then_type = self.expression(then);
+                let otherwise_type = self.expression(otherwise);
+                match (&then_type, &otherwise_type) {
+                    (CType::Void, CType::Void) => then_type,
+                    (then_type, otherwise_type)
+                        if then_type.is_arithmetic() && otherwise_type.is_arithmetic() =>
+                    {
+                        let ctype = common_type(then_type, otherwise_type);
+                        convert(then, &ctype);
+                        convert(otherwise, &ctype);
+                        ctype
+                    }
+                    // The backend has no temporaries wider than an `int` to hold the result yet
+                    (CType::Pointer(_), _) => {
+                        self.errors
+                            .push(SemanticError::InvalidOperand("?:", then_type, then.span));
+                        CType::Int
+                    }
+                    (_, CType::Pointer(_)) => {
+                        self.errors.push(SemanticError::InvalidOperand(
+                            "?:",
+                            otherwise_type,
+                            otherwise.span,
+                        ));
+                        CType::Int
+                    }
+                    _ => {
+                        self.errors.push(SemanticError::MismatchedTypes(
+                            then_type.clone(),
+                            otherwise_type,
+                            otherwise.span,
+                        ));
+                        then_type
+                    }
+                }
+            }
+            // There are no arrays, pointer arithmetic or structures yet, so these can never type
+            // check
+            ExpressionKind::Subscript(array, index) => {
+                let ctype = self.expression(array);
+                self.errors
+                    .push(SemanticError::InvalidOperand("[]", ctype, array.span));
+                self.value(index);
+                CType::Int
+            }
+            ExpressionKind::Member(operand, _) => {
+                let ctype = self.expression(operand);
+                self.errors
+                    .push(SemanticError::InvalidOperand(".", ctype, operand.span));
+                CType::Int
+            }
+            ExpressionKind::PointerMember(operand, _) => {
+                let ctype = self.expression(operand);
+                self.errors
+                    .push(SemanticError::InvalidOperand("->", ctype, operand.span));
+                CType::Int
+            }
+            ExpressionKind::FunctionCall(name, args) => self.call(name, args, expr.span),
+        };
+
+        expr.ctype = Some(ctype.clone());
+        ctype
+    }
+
+    /// Checks a call against the declaration of the callee and returns its result type
+    fn call(&mut self, name: &str, args: &mut [Expression], span: Span) -> CType {
+        let (return_type, params) = match self.symbols.get(name).map(|s| &s.kind) {
+            Some(SymbolKind::Function {
+                return_type,
+                params,
+                ..
+            }) => (return_type.clone(), params.clone()),
+            _ => {
+                self.errors.push(SemanticError::NotAFunction(
+                    source_name(name).to_string(),
+                    span,
+                ));
+                (CType::Int, None)
+            }
+        };
+
+        match params {
+            Some(params) if params.len() == args.len() => {
+                for (param, arg) in params.into_iter().zip(args) {
+                    self.assign(arg, &param);
+                }
+            }
+            params => {
+                if let Some(params) = params {
+                    self.errors.push(SemanticError::WrongArgumentCount(
+                        name.to_string(),
+                        params.len(),
+                        args.len(),
+                        span,
+                    ));
+                }
+                // Without a prototype any argument is accepted, as long as it has a value. The
+                // default argument promotions pass a `float` as a `double`
+                for arg in args {
+                    self.value(arg);
+                    if arg.ctype == Some(CType::Float) {
+                        convert(arg, &CType::Double);
+                    }
+                }
+            }
+        }
+
+        return_type
+    }
+}
+
+/// Wraps an already checked `expr` in an implicit conversion to `target`, unless it has that
+/// type already
+fn convert(expr: &mut Expression, target: &CType) {
+    if expr.ctype.as_ref() == Some(target) {
+        return;
+    }
+
+    let span = expr.span;
+    let placeholder = Expression::new(ExpressionKind::IntegerLiteral(0, IntegerType::Int), span);
+    let inner = std::mem::replace(expr, placeholder);
+    *expr = Expression {
+        kind: ExpressionKind::Cast(target.clone(), Box::new(inner)),
+        span,
+        ctype: Some(target.clone()),
+    };
+}
+
+/// Name as written in the source, without the unique suffix the resolver gives locals
+fn source_name(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
@@ -0,0 +1,192 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Byte range `start..end` into the source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// Maps byte offsets in a source file to line and column numbers
+pub struct LineIndex<'a> {
+    src: &'a str,
+    /// Byte offset at which each line starts
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(src: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self { src, line_starts }
+    }
+
+    /// The indexed source
+    pub fn src(&self) -> &'a str {
+        self.src
+    }
+
+    /// 1-based line and column (in characters) of the byte `offset`
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.src.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let column = self.src[line_start..offset].chars().count();
+
+        (line + 1, column + 1)
+    }
+}
+
+/// Index of a file in a `SourceMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileId(usize);
+
+/// Every file read during a compilation: the main file and what it includes. Each file gets its
+/// own range of offsets, so a `Span` alone tells which file it is in
+///
+/// Sources are leaked like interned strings: they live for the whole compilation, and lexers
+/// borrow them while more files are added
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+pub struct SourceFile {
+    path: PathBuf,
+    /// Offset of the first byte of the file
+    start: usize,
+    lines: LineIndex<'static>,
+}
+
+/// A byte offset resolved to its file, line and column, displayed as `path:line:column`
+pub struct Location<'a> {
+    pub path: &'a Path,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, path: PathBuf, src: String) -> FileId {
+        // One past the end of the previous file, so that the offset of its `EOF` stays in it
+        let start = match self.files.last() {
+            Some(file) => file.start + file.src().len() + 1,
+            None => 0,
+        };
+        let src: &'static str = Box::leak(src.into_boxed_str());
+        self.files.push(SourceFile {
+            path,
+            start,
+            lines: LineIndex::new(src),
+        });
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    /// The file containing the byte `offset`
+    pub fn lookup(&self, offset: usize) -> &SourceFile {
+        let index = self.files.partition_point(|file| file.start <= offset) - 1;
+        &self.files[index]
+    }
+
+    /// Source text covered by `span`, which must be within one file
+    pub fn text(&self, span: Span) -> &'static str {
+        let file = self.lookup(span.start);
+        &file.src()[span.start - file.start..span.end - file.start]
+    }
+
+    pub fn location(&self, offset: usize) -> Location<'_> {
+        let file = self.lookup(offset);
+        let (line, column) = file.lines.line_col(offset - file.start);
+        Location {
+            path: &file.path,
+            line,
+            column,
+        }
+    }
+}
+
+impl SourceFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Offset of the first byte of the file, spans of its tokens are shifted by it
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn src(&self) -> &'static str {
+        self.lines.src()
+    }
+
+    pub fn lines(&self) -> &LineIndex<'static> {
+        &self.lines
+    }
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path.display(), self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index_line_col() {
+        let index = LineIndex::new("int\n  main\n\nx");
+
+        assert_eq!(index.line_col(0), (1, 1));
+        assert_eq!(index.line_col(3), (1, 4));
+        assert_eq!(index.line_col(6), (2, 3));
+        assert_eq!(index.line_col(11), (3, 1));
+        assert_eq!(index.line_col(12), (4, 1));
+    }
+
+    #[test]
+    fn test_line_index_counts_characters() {
+        let index = LineIndex::new("é = 1");
+
+        assert_eq!(index.line_col(3), (1, 3));
+    }
+
+    #[test]
+    fn test_source_map_locations() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file(PathBuf::from("main.c"), "int x;\nint y;".to_string());
+        let header = sources.add_file(PathBuf::from("a.h"), "\n z".to_string());
+
+        assert_eq!(sources.file(main).start(), 0);
+        assert_eq!(sources.file(header).start(), 14);
+        assert_eq!(sources.location(11).to_string(), "main.c:2:5");
+        // The end of a file is still in it
+        assert_eq!(sources.location(13).to_string(), "main.c:2:7");
+        assert_eq!(sources.location(16).to_string(), "a.h:2:2");
+        assert_eq!(sources.text(Span::new(15, 17)), " z");
+    }
+}
@@ -0,0 +1,58 @@
+use std::{fmt, str::FromStr};
+
+/// Revision of the C standard the source is compiled against, selected with `-std=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Standard {
+    C89,
+    C99,
+    C11,
+    #[default]
+    C17,
+}
+
+impl Standard {
+    /// Whether identifiers may contain non-ASCII characters and universal character names.
+    /// C89 only has the basic source character set
+    pub fn extended_identifiers(self) -> bool {
+        self >= Standard::C99
+    }
+
+    /// Value of `__STDC_VERSION__`, which C89 doesn't define
+    pub fn stdc_version(self) -> Option<&'static str> {
+        match self {
+            Standard::C89 => None,
+            Standard::C99 => Some("199901L"),
+            Standard::C11 => Some("201112L"),
+            Standard::C17 => Some("201710L"),
+        }
+    }
+}
+
+impl FromStr for Standard {
+    type Err = String;
+
+    /// Accepts the names gcc does. The GNU dialects are treated as their ISO counterparts
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c89" | "c90" | "ansi" | "iso9899:1990" | "gnu89" | "gnu90" => Ok(Standard::C89),
+            "c99" | "iso9899:1999" | "gnu99" => Ok(Standard::C99),
+            "c11" | "iso9899:2011" | "gnu11" => Ok(Standard::C11),
+            "c17" | "c18" | "iso9899:2017" | "iso9899:2018" | "gnu17" | "gnu18" => {
+                Ok(Standard::C17)
+            }
+            _ => Err(format!("unrecognized standard '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Standard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Standard::C89 => "c89",
+            Standard::C99 => "c99",
+            Standard::C11 => "c11",
+            Standard::C17 => "c17",
+        };
+        write!(f, "{}", name)
+    }
+}
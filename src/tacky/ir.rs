@@ -0,0 +1,217 @@
+use std::fmt;
+
+use crate::{parser::ast::CType, semantic::Constant};
+
+/// A linear three-address intermediate representation (TACKY) of a translation unit
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub top_level: Vec<TopLevel>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TopLevel {
+    Function(Function),
+    StaticVariable(StaticVariable),
+    StaticConstant(StaticConstant),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Instruction>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StaticVariable {
+    pub name: String,
+    /// Also gives the type of the variable
+    pub init: Constant,
+}
+
+/// Read-only data, such as the characters of a string literal
+#[derive(Debug, PartialEq)]
+pub struct StaticConstant {
+    pub name: String,
+    /// Bytes of the string, including the terminating null byte
+    pub init: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Instruction {
+    Return(Value),
+    Unary {
+        op: UnaryOperator,
+        src: Value,
+        dst: Value,
+    },
+    Binary {
+        op: BinaryOperator,
+        left: Value,
+        right: Value,
+        dst: Value,
+    },
+    Copy {
+        src: Value,
+        dst: Value,
+    },
+    /// Converts between arithmetic types, `dst` has type `to`
+    Convert {
+        src: Value,
+        dst: Value,
+        to: CType,
+    },
+    Jump(String),
+    JumpIfZero(Value, String),
+    JumpIfNotZero(Value, String),
+    Label(String),
+    FunCall {
+        name: String,
+        args: Vec<Value>,
+        dst: Value,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Constant(Constant),
+    /// A local variable, temporary or static variable
+    Var(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOperator {
+    Negate,
+    Complement,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for top_level in &self.top_level {
+            match top_level {
+                TopLevel::Function(function) => writeln!(f, "{}", function)?,
+                TopLevel::StaticVariable(variable) => {
+                    writeln!(f, "static {} = {}", variable.name, variable.init)?
+                }
+                TopLevel::StaticConstant(constant) => writeln!(
+                    f,
+                    "constant {} = \"{}\"",
+                    constant.name,
+                    constant.init.escape_ascii()
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "function {}({}) {{", self.name, self.params.join(", "))?;
+        for instruction in &self.body {
+            match instruction {
+                Instruction::Label(label) => writeln!(f, "  {}:", label)?,
+                instruction => writeln!(f, "    {}", instruction)?,
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Return(value) => write!(f, "return {}", value),
+            Instruction::Unary { op, src, dst } => write!(f, "{} = {}{}", dst, op, src),
+            Instruction::Binary {
+                op,
+                left,
+                right,
+                dst,
+            } => write!(f, "{} = {} {} {}", dst, left, op, right),
+            Instruction::Copy { src, dst } => write!(f, "{} = {}", dst, src),
+            Instruction::Convert { src, dst, to } => write!(f, "{} = ({}) {}", dst, to, src),
+            Instruction::Jump(label) => write!(f, "jump {}", label),
+            Instruction::JumpIfZero(value, label) => write!(f, "jump_if_zero {}, {}", value, label),
+            Instruction::JumpIfNotZero(value, label) => {
+                write!(f, "jump_if_not_zero {}, {}", value, label)
+            }
+            Instruction::Label(label) => write!(f, "{}:", label),
+            Instruction::FunCall { name, args, dst } => {
+                write!(f, "{} = {}(", dst, name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Constant(value) => write!(f, "{}", value),
+            Value::Var(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOperator::Negate => write!(f, "-"),
+            UnaryOperator::Complement => write!(f, "~"),
+            UnaryOperator::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::BitwiseAnd => "&",
+            BinaryOperator::BitwiseOr => "|",
+            BinaryOperator::BitwiseXor => "^",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
+        };
+        write!(f, "{}", op)
+    }
+}
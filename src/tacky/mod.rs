@@ -0,0 +1,749 @@
+pub mod ir;
+
+use std::collections::HashSet;
+
+use crate::{
+    FloatType,
+    parser::ast::{
+        BinaryOp, CType, Declaration, Expression, ExpressionKind, ForInit, FunctionDeclaration,
+        Statement, StatementKind, TranslationUnit, Unary, VariableDeclaration,
+    },
+    semantic::{Constant, Symbol, SymbolKind, SymbolTable},
+    span::Span,
+    tacky::ir::{
+        BinaryOperator, Function, Instruction, Program, StaticConstant, StaticVariable, TopLevel,
+        UnaryOperator, Value,
+    },
+};
+
+/// Public API for lowering a `TranslationUnit` to TACKY. The unit must have passed
+/// `semantic::analyze`, which makes every local name unique and rejects invalid programs.
+/// The temporaries TACKY introduces are added to `symbols` with their types
+pub fn generate(unit: &TranslationUnit, symbols: &mut SymbolTable) -> Program {
+    let mut top_level = Vec::new();
+    // A global may be declared several times, but is defined only once
+    let mut statics = HashSet::new();
+    for declaration in &unit.declarations {
+        if let Declaration::Variable(variable) = declaration
+            && statics.insert(&variable.name)
+        {
+            top_level.push(TopLevel::StaticVariable(static_variable(variable, symbols)));
+        }
+    }
+
+    let mut generator = Generator::new(symbols);
+    for declaration in &unit.declarations {
+        match declaration {
+            Declaration::Variable(_) => {}
+            Declaration::Function(function) => {
+                if let Some(function) = generator.function(function) {
+                    top_level.push(TopLevel::Function(function));
+                }
+            }
+        }
+    }
+    top_level.extend(
+        generator
+            .constants
+            .into_iter()
+            .map(TopLevel::StaticConstant),
+    );
+
+    Program { top_level }
+}
+
+/// Tentative definitions without any initializer are zero-initialized
+fn static_variable(variable: &VariableDeclaration, symbols: &SymbolTable) -> StaticVariable {
+    let init = match symbols.get(&variable.name).map(|symbol| &symbol.kind) {
+        Some(SymbolKind::Global { ctype, init }) => {
+            init.unwrap_or_else(|| Constant::Int(0).convert(ctype))
+        }
+        _ => unreachable!("globals are recorded by the type checker"),
+    };
+
+    StaticVariable {
+        name: variable.name.clone(),
+        init,
+    }
+}
+
+/// TACKY operator computing `binary` (or the operation of a compound assignment)
+fn binary_operator(binary: &BinaryOp) -> BinaryOperator {
+    match binary {
+        BinaryOp::Add(..) => BinaryOperator::Add,
+        BinaryOp::Subtract(..) => BinaryOperator::Subtract,
+        BinaryOp::Multiply(..) => BinaryOperator::Multiply,
+        BinaryOp::Divide(..) => BinaryOperator::Divide,
+        BinaryOp::Remainder(..) => BinaryOperator::Remainder,
+        BinaryOp::Equals(..) => BinaryOperator::Equal,
+        BinaryOp::NotEquals(..) => BinaryOperator::NotEqual,
+        BinaryOp::LessThan(..) => BinaryOperator::LessThan,
+        BinaryOp::LessOrEqual(..) => BinaryOperator::LessOrEqual,
+        BinaryOp::GreaterThan(..) => BinaryOperator::GreaterThan,
+        BinaryOp::GreaterOrEqual(..) => BinaryOperator::GreaterOrEqual,
+        BinaryOp::BitwiseAnd(..) => BinaryOperator::BitwiseAnd,
+        BinaryOp::BitwiseOr(..) => BinaryOperator::BitwiseOr,
+        BinaryOp::BitwiseXor(..) => BinaryOperator::BitwiseXor,
+        BinaryOp::ShiftLeft(..) => BinaryOperator::ShiftLeft,
+        BinaryOp::ShiftRight(..) => BinaryOperator::ShiftRight,
+        BinaryOp::LogicalAnd(..)
+        | BinaryOp::LogicalOr(..)
+        | BinaryOp::Assign(..)
+        | BinaryOp::Comma(..) => unreachable!("not a single TACKY operation"),
+    }
+}
+
+struct Generator<'a> {
+    /// Types of all variables, which the temporaries are added to
+    symbols: &'a mut SymbolTable,
+    /// Name of the function being generated
+    function: String,
+    /// `break` and `continue` targets of the enclosing loops, innermost last
+    loops: Vec<(String, String)>,
+    /// Counter for unique temporary and label names
+    counter: usize,
+    instructions: Vec<Instruction>,
+    /// String literals of the whole translation unit
+    constants: Vec<StaticConstant>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(symbols: &'a mut SymbolTable) -> Self {
+        Self {
+            symbols,
+            function: String::new(),
+            loops: Vec::new(),
+            counter: 0,
+            instructions: Vec::new(),
+            constants: Vec::new(),
+        }
+    }
+
+    /// Returns `None` for declarations without a body
+    fn function(&mut self, function: &FunctionDeclaration) -> Option<Function> {
+        let body = function.body.as_ref()?;
+
+        self.function = function.name.clone();
+        let params = function
+            .parameters
+            .iter()
+            .flatten()
+            .map(|parameter| {
+                parameter
+                    .name
+                    .clone()
+                    .expect("parameter names checked by semantic analysis")
+            })
+            .collect();
+
+        for statement in body {
+            self.statement(statement);
+        }
+
+        // Falling off the end of a function returns 0 (required for `main`)
+        self.emit(Instruction::Return(Value::Constant(Constant::Int(0))));
+
+        Some(Function {
+            name: function.name.clone(),
+            params,
+            body: std::mem::take(&mut self.instructions),
+        })
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Declaration(variable) => self.local_variable(variable),
+            StatementKind::ExpressionStatment(expr) => {
+                self.expression(expr);
+            }
+            StatementKind::Return(Some(expr)) => {
+                let value = self.expression(expr);
+                self.emit(Instruction::Return(value));
+            }
+            // Only in `void` functions, whose callers ignore the value
+            StatementKind::Return(None) => {
+                self.emit(Instruction::Return(Value::Constant(Constant::Int(0))))
+            }
+            StatementKind::Compound(statements) => {
+                for statement in statements {
+                    self.statement(statement);
+                }
+            }
+            StatementKind::Null => {}
+            StatementKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let else_label = self.make_name("if_else");
+                let end_label = self.make_name("if_end");
+
+                let condition = self.expression(condition);
+                self.emit(Instruction::JumpIfZero(condition, else_label.clone()));
+                self.statement(then);
+                if let Some(otherwise) = otherwise {
+                    self.emit(Instruction::Jump(end_label.clone()));
+                    self.emit(Instruction::Label(else_label));
+                    self.statement(otherwise);
+                } else {
+                    self.emit(Instruction::Label(else_label));
+                }
+                self.emit(Instruction::Label(end_label));
+            }
+            StatementKind::While { condition, body } => {
+                let (break_label, continue_label) = self.make_loop_labels();
+
+                self.emit(Instruction::Label(continue_label.clone()));
+                let condition = self.expression(condition);
+                self.emit(Instruction::JumpIfZero(condition, break_label.clone()));
+                self.loop_body(body, &break_label, &continue_label);
+                self.emit(Instruction::Jump(continue_label));
+                self.emit(Instruction::Label(break_label));
+            }
+            StatementKind::DoWhile { body, condition } => {
+                let (break_label, continue_label) = self.make_loop_labels();
+                let start_label = self.make_name("loop_start");
+
+                self.emit(Instruction::Label(start_label.clone()));
+                self.loop_body(body, &break_label, &continue_label);
+                self.emit(Instruction::Label(continue_label));
+                let condition = self.expression(condition);
+                self.emit(Instruction::JumpIfNotZero(condition, start_label));
+                self.emit(Instruction::Label(break_label));
+            }
+            StatementKind::For {
+                init,
+                condition,
+                post,
+                body,
+            } => {
+                let (break_label, continue_label) = self.make_loop_labels();
+                let start_label = self.make_name("loop_start");
+
+                match init {
+                    ForInit::Declaration(variable) => self.local_variable(variable),
+                    ForInit::Expression(Some(expr)) => {
+                        self.expression(expr);
+                    }
+                    ForInit::Expression(None) => {}
+                }
+
+                self.emit(Instruction::Label(start_label.clone()));
+                if let Some(condition) = condition {
+                    let condition = self.expression(condition);
+                    self.emit(Instruction::JumpIfZero(condition, break_label.clone()));
+                }
+                self.loop_body(body, &break_label, &continue_label);
+                self.emit(Instruction::Label(continue_label));
+                if let Some(post) = post {
+                    self.expression(post);
+                }
+                self.emit(Instruction::Jump(start_label));
+                self.emit(Instruction::Label(break_label));
+            }
+            StatementKind::Break => {
+                let (break_label, _) = self.loops.last().expect("break checked to be in a loop");
+                self.emit(Instruction::Jump(break_label.clone()));
+            }
+            StatementKind::Continue => {
+                let (_, continue_label) =
+                    self.loops.last().expect("continue checked to be in a loop");
+                self.emit(Instruction::Jump(continue_label.clone()));
+            }
+            StatementKind::Goto(label) => self.emit(Instruction::Jump(self.user_label(label))),
+            StatementKind::Labeled(label, inner) => {
+                self.emit(Instruction::Label(self.user_label(label)));
+                self.statement(inner);
+            }
+        }
+    }
+
+    fn local_variable(&mut self, variable: &VariableDeclaration) {
+        if let Some(init) = &variable.initilizer {
+            let src = self.expression(init);
+            self.emit(Instruction::Copy {
+                src,
+                dst: Value::Var(variable.name.clone()),
+            });
+        }
+    }
+
+    /// Generates a loop body, with `break` and `continue` jumping to the given labels
+    fn loop_body(&mut self, body: &Statement, break_label: &str, continue_label: &str) {
+        self.loops
+            .push((break_label.to_string(), continue_label.to_string()));
+        self.statement(body);
+        self.loops.pop();
+    }
+
+    fn make_loop_labels(&mut self) -> (String, String) {
+        (self.make_name("break"), self.make_name("continue"))
+    }
+
+    /// TACKY label of a label written in the source. Labels are function scoped and identifiers
+    /// can't start with a digit, so this never clashes with a generated label
+    fn user_label(&self, label: &str) -> String {
+        format!("{}.{}", self.function, label)
+    }
+
+    /// Emits the instructions computing `expr` and returns where its result is stored
+    fn expression(&mut self, expr: &Expression) -> Value {
+        match &expr.kind {
+            ExpressionKind::IntegerLiteral(value, _) => {
+                Value::Constant(Constant::Int(*value as i64))
+            }
+            ExpressionKind::FloatLiteral(value, FloatType::Float) => {
+                Value::Constant(Constant::Float(*value as f32))
+            }
+            ExpressionKind::FloatLiteral(value, _) => Value::Constant(Constant::Double(*value)),
+            ExpressionKind::CharacterLiteral(value) => {
+                Value::Constant(Constant::Int(*value as i64))
+            }
+            // The value of a string is the address of its characters, stored as read-only data
+            ExpressionKind::StringLiteral(bytes) => {
+                let name = self.make_name("string");
+                let mut init = bytes.clone();
+                init.push(0);
+                self.constants.push(StaticConstant {
+                    name: name.clone(),
+                    init,
+                });
+                Value::Var(name)
+            }
+            ExpressionKind::Identifier(name) => Value::Var(name.clone()),
+            ExpressionKind::Cast(ctype, inner) => {
+                let src = self.expression(inner);
+                if ctype == ctype_of(inner) || *ctype == CType::Void {
+                    return src;
+                }
+                self.convert(src, ctype)
+            }
+            ExpressionKind::Unary(unary) => self.unary(unary, ctype_of(expr)),
+            ExpressionKind::BinaryOp(binary) => self.binary(binary, ctype_of(expr)),
+            ExpressionKind::CompoundAssign(binary) => {
+                let (target, value) = binary.operands();
+                // Semantic analysis only accepts variables as assignment targets
+                let dst = self.expression(target);
+                let right = self.expression(value);
+                let op = binary_operator(binary);
+
+                // The operation is performed in the type of the converted right operand
+                let operation_type = ctype_of(value);
+                if operation_type == ctype_of(target) {
+                    self.emit(Instruction::Binary {
+                        op,
+                        left: dst.clone(),
+                        right,
+                        dst: dst.clone(),
+                    });
+                } else {
+                    let left = self.convert(dst.clone(), operation_type);
+                    self.emit(Instruction::Binary {
+                        op,
+                        left: left.clone(),
+                        right,
+                        dst: left.clone(),
+                    });
+                    self.emit(Instruction::Convert {
+                        src: left,
+                        dst: dst.clone(),
+                        to: ctype_of(target).clone(),
+                    });
+                }
+                dst
+            }
+            ExpressionKind::FunctionCall(name, args) => {
+                let args = args.iter().map(|arg| self.expression(arg)).collect();
+                let dst = self.make_temporary(ctype_of(expr));
+                self.emit(Instruction::FunCall {
+                    name: name.clone(),
+                    args,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let else_label = self.make_name("cond_else");
+                let end_label = self.make_name("cond_end");
+                let dst = self.make_temporary(ctype_of(expr));
+
+                let condition = self.expression(condition);
+                self.emit(Instruction::JumpIfZero(condition, else_label.clone()));
+                let src = self.expression(then);
+                self.emit(Instruction::Copy {
+                    src,
+                    dst: dst.clone(),
+                });
+                self.emit(Instruction::Jump(end_label.clone()));
+                self.emit(Instruction::Label(else_label));
+                let src = self.expression(otherwise);
+                self.emit(Instruction::Copy {
+                    src,
+                    dst: dst.clone(),
+                });
+                self.emit(Instruction::Label(end_label));
+                dst
+            }
+            ExpressionKind::Subscript(..)
+            | ExpressionKind::Member(..)
+            | ExpressionKind::PointerMember(..) => {
+                unreachable!("rejected by the type checker")
+            }
+        }
+    }
+
+    /// `ctype` is the type of the result
+    fn unary(&mut self, unary: &Unary, ctype: &CType) -> Value {
+        let one = Value::Constant(Constant::Int(1).convert(ctype));
+        let op = match unary {
+            Unary::Positive(expr) => return self.expression(expr),
+            Unary::Negative(_) => UnaryOperator::Negate,
+            Unary::Not(_) => UnaryOperator::Not,
+            Unary::Complement(_) => UnaryOperator::Complement,
+            Unary::PreIncrement(target) | Unary::PreDecrement(target) => {
+                let op = if matches!(unary, Unary::PreIncrement(_)) {
+                    BinaryOperator::Add
+                } else {
+                    BinaryOperator::Subtract
+                };
+                let dst = self.expression(target);
+                self.emit(Instruction::Binary {
+                    op,
+                    left: dst.clone(),
+                    right: one,
+                    dst: dst.clone(),
+                });
+                return dst;
+            }
+            Unary::PostIncrement(target) | Unary::PostDecrement(target) => {
+                let op = if matches!(unary, Unary::PostIncrement(_)) {
+                    BinaryOperator::Add
+                } else {
+                    BinaryOperator::Subtract
+                };
+                // The result is the value from before the update
+                let var = self.expression(target);
+                let old = self.make_temporary(ctype);
+                self.emit(Instruction::Copy {
+                    src: var.clone(),
+                    dst: old.clone(),
+                });
+                self.emit(Instruction::Binary {
+                    op,
+                    left: var.clone(),
+                    right: one,
+                    dst: var,
+                });
+                return old;
+            }
+        };
+
+        let src = self.expression(unary.operand());
+        let dst = self.make_temporary(ctype);
+        self.emit(Instruction::Unary {
+            op,
+            src,
+            dst: dst.clone(),
+        });
+        dst
+    }
+
+    /// `ctype` is the type of the result
+    fn binary(&mut self, binary: &BinaryOp, ctype: &CType) -> Value {
+        match binary {
+            BinaryOp::Assign(target, value) => {
+                // Semantic analysis only accepts variables as assignment targets
+                let dst = self.expression(target);
+                let src = self.expression(value);
+                self.emit(Instruction::Copy {
+                    src,
+                    dst: dst.clone(),
+                });
+                return dst;
+            }
+            BinaryOp::Comma(left, right) => {
+                self.expression(left);
+                return self.expression(right);
+            }
+            BinaryOp::LogicalAnd(left, right) | BinaryOp::LogicalOr(left, right) => {
+                return self.logical(binary, left, right);
+            }
+            _ => {}
+        }
+
+        let (left, right) = binary.operands();
+        let left = self.expression(left);
+        let right = self.expression(right);
+        let dst = self.make_temporary(ctype);
+        self.emit(Instruction::Binary {
+            op: binary_operator(binary),
+            left,
+            right,
+            dst: dst.clone(),
+        });
+
+        dst
+    }
+
+    /// `&&` and `||` only evaluate their right operand if the left one doesn't decide the result
+    fn logical(&mut self, binary: &BinaryOp, left: &Expression, right: &Expression) -> Value {
+        let is_and = matches!(binary, BinaryOp::LogicalAnd(..));
+        let (prefix, short_circuit_result) = if is_and {
+            ("and_false", 0)
+        } else {
+            ("or_true", 1)
+        };
+        let short_circuit = self.make_name(prefix);
+        let end_label = self.make_name("logical_end");
+        let dst = self.make_temporary(&CType::Int);
+
+        for operand in [left, right] {
+            let value = self.expression(operand);
+            self.emit(if is_and {
+                Instruction::JumpIfZero(value, short_circuit.clone())
+            } else {
+                Instruction::JumpIfNotZero(value, short_circuit.clone())
+            });
+        }
+        self.emit(Instruction::Copy {
+            src: Value::Constant(Constant::Int(1 - short_circuit_result)),
+            dst: dst.clone(),
+        });
+        self.emit(Instruction::Jump(end_label.clone()));
+        self.emit(Instruction::Label(short_circuit));
+        self.emit(Instruction::Copy {
+            src: Value::Constant(Constant::Int(short_circuit_result)),
+            dst: dst.clone(),
+        });
+        self.emit(Instruction::Label(end_label));
+
+        dst
+    }
+
+    /// Emits the conversion of `src` to `ctype` and returns the converted value. Constants are
+    /// converted right away
+    fn convert(&mut self, src: Value, ctype: &CType) -> Value {
+        if let Value::Constant(constant) = src {
+            return Value::Constant(constant.convert(ctype));
+        }
+
+        let dst = self.make_temporary(ctype);
+        self.emit(Instruction::Convert {
+            src,
+            dst: dst.clone(),
+            to: ctype.clone(),
+        });
+        dst
+    }
+
+    /// Temporaries are named `%N`, which can't clash with the `name.N` of renamed locals
+    fn make_temporary(&mut self, ctype: &CType) -> Value {
+        let name = format!("%{}", self.counter);
+        self.counter += 1;
+        self.symbols.insert(
+            name.clone(),
+            Symbol {
+                kind: SymbolKind::Local(ctype.clone()),
+                span: Span::default(),
+            },
+        );
+        Value::Var(name)
+    }
+
+    /// Appends a unique suffix to `prefix`. The `.` guarantees no clash with C identifiers
+    fn make_name(&mut self, prefix: &str) -> String {
+        let name = format!("{}.{}", prefix, self.counter);
+        self.counter += 1;
+        name
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+}
+
+/// Type of an expression, as annotated by the type checker
+fn ctype_of(expr: &Expression) -> &CType {
+    expr.ctype
+        .as_ref()
+        .expect("expressions are annotated by the type checker")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, semantic};
+    use pretty_assertions::assert_eq;
+
+    fn generate_src(src: &str) -> Program {
+        let mut unit = parse(src).unwrap();
+        let mut symbols = semantic::analyze(&mut unit).unwrap();
+        generate(&unit, &mut symbols)
+    }
+
+    #[test]
+    fn test_tacky_return_binary_expression() {
+        let program = generate_src("int main(void) { return -2 * 3; }");
+
+        let expected = Program {
+            top_level: vec![TopLevel::Function(Function {
+                name: "main".to_string(),
+                params: vec![],
+                body: vec![
+                    Instruction::Unary {
+                        op: UnaryOperator::Negate,
+                        src: Value::Constant(Constant::Int(2)),
+                        dst: Value::Var("%0".to_string()),
+                    },
+                    Instruction::Binary {
+                        op: BinaryOperator::Multiply,
+                        left: Value::Var("%0".to_string()),
+                        right: Value::Constant(Constant::Int(3)),
+                        dst: Value::Var("%1".to_string()),
+                    },
+                    Instruction::Return(Value::Var("%1".to_string())),
+                    Instruction::Return(Value::Constant(Constant::Int(0))),
+                ],
+            })],
+        };
+
+        assert_eq!(expected, program);
+    }
+
+    #[test]
+    fn test_tacky_global_initializer_is_folded() {
+        let program = generate_src("int a = -2 * 3 + 1;");
+
+        let expected = Program {
+            top_level: vec![TopLevel::StaticVariable(StaticVariable {
+                name: "a".to_string(),
+                init: Constant::Int(-5),
+            })],
+        };
+
+        assert_eq!(expected, program);
+    }
+
+    #[test]
+    fn test_tacky_tentative_definitions() {
+        let program = generate_src("int a; int a = 4; int a;");
+
+        assert_eq!(program.to_string(), "static a = 4\n");
+    }
+
+    #[test]
+    fn test_tacky_block_scope() {
+        let src = "int main(void) { int a = 1; { int a = 2; a = 3; } return a; }";
+        let program = generate_src(src);
+
+        let TopLevel::Function(main) = &program.top_level[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            main.body[..4],
+            [
+                Instruction::Copy {
+                    src: Value::Constant(Constant::Int(1)),
+                    dst: Value::Var("a.0".to_string()),
+                },
+                Instruction::Copy {
+                    src: Value::Constant(Constant::Int(2)),
+                    dst: Value::Var("a.1".to_string()),
+                },
+                Instruction::Copy {
+                    src: Value::Constant(Constant::Int(3)),
+                    dst: Value::Var("a.1".to_string()),
+                },
+                Instruction::Return(Value::Var("a.0".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tacky_function_call() {
+        let src = "int add(int a, int b) { return a + b; } int main(void) { return add(1, 2); }";
+        let program = generate_src(src);
+
+        assert_eq!(
+            program.to_string(),
+            "function add(a.0, b.1) {\n    %0 = a.0 + b.1\n    return %0\n    return 0\n}\n\
+             function main() {\n    %1 = add(1, 2)\n    return %1\n    return 0\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_tacky_while_loop() {
+        let src =
+            "int main(void) { int i = 3; while (i) { if (i == 1) break; i = i - 1; } return i; }";
+        let program = generate_src(src);
+
+        assert_eq!(
+            program.to_string(),
+            "function main() {
+    i.0 = 3
+  continue.1:
+    jump_if_zero i.0, break.0
+    %4 = i.0 == 1
+    jump_if_zero %4, if_else.2
+    jump break.0
+  if_else.2:
+  if_end.3:
+    %5 = i.0 - 1
+    i.0 = %5
+    jump continue.1
+  break.0:
+    return i.0
+    return 0
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_tacky_display() {
+        let program = generate_src("int a = 1; int main(void) { return a + 2; }");
+
+        assert_eq!(
+            "static a = 1\nfunction main() {\n    %0 = a + 2\n    return %0\n    return 0\n}\n",
+            program.to_string()
+        );
+    }
+
+    #[test]
+    fn test_tacky_string_literals() {
+        let src = r#"int puts(); int main(void) { puts("a\n" "b"); return 'c'; }"#;
+        let program = generate_src(src);
+
+        assert_eq!(
+            program.to_string(),
+            "function main() {\n    \
+            %1 = puts(string.0)\n    \
+            return 99\n    \
+            return 0\n\
+            }\n\
+            constant string.0 = \"a\\nb\\x00\"\n"
+        );
+    }
+
+    #[test]
+    fn test_tacky_floating_conversions() {
+        let src = "double f(int i) { float x = i; return x * 2; }";
+        let program = generate_src(src);
+
+        assert_eq!(
+            program.to_string(),
+            "function f(i.0) {\n    \
+            %0 = (float) i.0\n    \
+            x.1 = %0\n    \
+            %1 = x.1 * 2.0f\n    \
+            %2 = (double) %1\n    \
+            return %2\n    \
+            return 0\n\
+            }\n"
+        );
+    }
+}
@@ -1,34 +1,399 @@
-#[derive(Debug, PartialEq, Clone)]
-pub enum Token {
-    // Keywords
+use crate::{intern::Interned, span::Span};
+
+/// A token together with its location in the source. Tokens are `Copy`, their text is interned
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Where diagnostics about the token point: the part at fault for lexing errors, the whole
+    /// token otherwise
+    pub fn error_span(&self) -> Span {
+        match self.kind {
+            TokenKind::Error(_, offset) => {
+                Span::new(self.span.start + offset as usize, self.span.end)
+            }
+            _ => self.span,
+        }
+    }
+}
+
+/// Where the parser gets its tokens from: the lexer, or the preprocessor on top of it. Once the
+/// end is reached every call returns `EOF`
+pub trait TokenSource {
+    fn next_token(&mut self) -> Token;
+
+    /// Looks at the token `n` tokens past the next one without consuming anything
+    fn peek_nth(&mut self, n: usize) -> Token;
+
+    /// Saves the current position, so that the tokens consumed from now on can be read again.
+    /// Every checkpoint must end with either `restore` or `release`, innermost first
+    fn checkpoint(&mut self) -> Checkpoint;
+
+    /// Goes back to a position saved with `checkpoint`, which ends it
+    fn restore(&mut self, checkpoint: Checkpoint);
+
+    /// Ends a checkpoint without going back, so that the tokens kept for it can be dropped
+    fn release(&mut self, checkpoint: Checkpoint);
+}
+
+impl<T: TokenSource + ?Sized> TokenSource for &mut T {
+    fn next_token(&mut self) -> Token {
+        (**self).next_token()
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Token {
+        (**self).peek_nth(n)
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        (**self).checkpoint()
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        (**self).restore(checkpoint)
+    }
+
+    fn release(&mut self, checkpoint: Checkpoint) {
+        (**self).release(checkpoint)
+    }
+}
+
+/// Position in a token stream to go back to with `TokenSource::restore`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checkpoint {
+    /// Where the next token is: a byte offset in the lexer, a count of consumed tokens in the
+    /// preprocessor
+    pub(crate) pos: usize,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TokenKind {
+    // Keywords (C17 6.4.1)
+    Auto,
+    Break,
+    Case,
+    Char,
+    Const,
+    Continue,
+    Default,
+    Do,
+    Double,
+    Else,
+    Enum,
+    Extern,
+    Float,
+    For,
+    Goto,
+    If,
+    Inline,
     Int,
-    Void,
+    Long,
+    Register,
+    Restrict,
     Return,
+    Short,
+    Signed,
+    Sizeof,
+    Static,
+    Struct,
+    Switch,
+    Typedef,
+    Union,
+    Unsigned,
+    Void,
+    Volatile,
+    While,
+    Alignas,      // _Alignas
+    Alignof,      // _Alignof
+    Atomic,       // _Atomic
+    Bool,         // _Bool
+    Complex,      // _Complex
+    Generic,      // _Generic
+    Imaginary,    // _Imaginary
+    Noreturn,     // _Noreturn
+    StaticAssert, // _Static_assert
+    ThreadLocal,  // _Thread_local
 
     // Identifiers (e.g., 'main')
-    Identifier(String),
+    Identifier(Interned),
 
     // Literals
-    IntegerLiteral(i64),
-    CharacterLiteral(char), // 'x'
+    IntegerLiteral(u64, IntegerType),
+    FloatLiteral(f64, FloatType),
+    CharacterLiteral(i32),   // 'x', which has type `int` in C
+    StringLiteral(Interned), // "x", escapes replaced, without the terminating null byte
 
     // Punctuation
-    OpenParen,  // (
-    CloseParen, // )
-    OpenBrace,  // {
-    CloseBrace, // }
-    Semicolon,  // ;
-    Colon,      // :
+    OpenParen,    // (
+    CloseParen,   // )
+    OpenBrace,    // { or <%
+    CloseBrace,   // } or %>
+    OpenBracket,  // [ or <:
+    CloseBracket, // ] or :>
+    Semicolon,    // ;
+    Colon,        // :
+    Comma,        // ,
+    Ellipsis,     // ...
+    Hash,         // # or %:
+    HashHash,     // ## or %:%:
 
     // Operators
-    Plus,       // +
-    Minus,      // -
-    Asterix,    // *
-    Slash,      // /
-    Assignment, // =
-    Equal,      // ==
+    Plus,         // +
+    Minus,        // -
+    Asterix,      // *
+    Slash,        // /
+    Percent,      // %
+    Increment,    // ++
+    Decrement,    // --
+    Ampersand,    // &
+    Pipe,         // |
+    Caret,        // ^
+    Tilde,        // ~
+    Bang,         // !
+    ShiftLeft,    // <<
+    ShiftRight,   // >>
+    LogicalAnd,   // &&
+    LogicalOr,    // ||
+    Equal,        // ==
+    NotEqual,     // !=
+    Less,         // <
+    Greater,      // >
+    LessEqual,    // <=
+    GreaterEqual, // >=
+    Question,     // ?
+    Dot,          // .
+    Arrow,        // ->
+
+    // Assignment operators
+    Assignment,           // =
+    PlusAssignment,       // +=
+    MinusAssignment,      // -=
+    AsterixAssignment,    // *=
+    SlashAssignment,      // /=
+    PercentAssignment,    // %=
+    AmpersandAssignment,  // &=
+    PipeAssignment,       // |=
+    CaretAssignment,      // ^=
+    ShiftLeftAssignment,  // <<=
+    ShiftRightAssignment, // >>=
 
     // Utils
-    Error(String),
+    /// A lexing error: its message, and the offset in the token of the part that is wrong
+    /// (e.g. the suffix of `1foo`), which diagnostics point at
+    Error(Interned, u32),
     EOF,
 }
+
+impl TokenKind {
+    /// The keyword spelled `name`, if it is one
+    pub fn from_keyword(name: &[u8]) -> Option<TokenKind> {
+        use TokenKind::*;
+
+        let kind = match name {
+            b"auto" => Auto,
+            b"break" => Break,
+            b"case" => Case,
+            b"char" => Char,
+            b"const" => Const,
+            b"continue" => Continue,
+            b"default" => Default,
+            b"do" => Do,
+            b"double" => Double,
+            b"else" => Else,
+            b"enum" => Enum,
+            b"extern" => Extern,
+            b"float" => Float,
+            b"for" => For,
+            b"goto" => Goto,
+            b"if" => If,
+            b"inline" => Inline,
+            b"int" => Int,
+            b"long" => Long,
+            b"register" => Register,
+            b"restrict" => Restrict,
+            b"return" => Return,
+            b"short" => Short,
+            b"signed" => Signed,
+            b"sizeof" => Sizeof,
+            b"static" => Static,
+            b"struct" => Struct,
+            b"switch" => Switch,
+            b"typedef" => Typedef,
+            b"union" => Union,
+            b"unsigned" => Unsigned,
+            b"void" => Void,
+            b"volatile" => Volatile,
+            b"while" => While,
+            b"_Alignas" => Alignas,
+            b"_Alignof" => Alignof,
+            b"_Atomic" => Atomic,
+            b"_Bool" => Bool,
+            b"_Complex" => Complex,
+            b"_Generic" => Generic,
+            b"_Imaginary" => Imaginary,
+            b"_Noreturn" => Noreturn,
+            b"_Static_assert" => StaticAssert,
+            b"_Thread_local" => ThreadLocal,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Spelling of a keyword, `None` for other tokens
+    pub fn keyword(&self) -> Option<&'static str> {
+        use TokenKind::*;
+
+        let name = match self {
+            Auto => "auto",
+            Break => "break",
+            Case => "case",
+            Char => "char",
+            Const => "const",
+            Continue => "continue",
+            Default => "default",
+            Do => "do",
+            Double => "double",
+            Else => "else",
+            Enum => "enum",
+            Extern => "extern",
+            Float => "float",
+            For => "for",
+            Goto => "goto",
+            If => "if",
+            Inline => "inline",
+            Int => "int",
+            Long => "long",
+            Register => "register",
+            Restrict => "restrict",
+            Return => "return",
+            Short => "short",
+            Signed => "signed",
+            Sizeof => "sizeof",
+            Static => "static",
+            Struct => "struct",
+            Switch => "switch",
+            Typedef => "typedef",
+            Union => "union",
+            Unsigned => "unsigned",
+            Void => "void",
+            Volatile => "volatile",
+            While => "while",
+            Alignas => "_Alignas",
+            Alignof => "_Alignof",
+            Atomic => "_Atomic",
+            Bool => "_Bool",
+            Complex => "_Complex",
+            Generic => "_Generic",
+            Imaginary => "_Imaginary",
+            Noreturn => "_Noreturn",
+            StaticAssert => "_Static_assert",
+            ThreadLocal => "_Thread_local",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// Broad class of the token, as shown by `--lex`: `keyword`, `identifier`, `integer`,
+    /// `float`, `character`, `string`, `punctuator`, `error` or `eof`
+    pub fn category(&self) -> &'static str {
+        match self {
+            _ if self.keyword().is_some() => "keyword",
+            TokenKind::Identifier(_) => "identifier",
+            TokenKind::IntegerLiteral(..) => "integer",
+            TokenKind::FloatLiteral(..) => "float",
+            TokenKind::CharacterLiteral(_) => "character",
+            TokenKind::StringLiteral(_) => "string",
+            TokenKind::Error(..) => "error",
+            TokenKind::EOF => "eof",
+            _ => "punctuator",
+        }
+    }
+
+    /// Keywords that start a declaration
+    pub fn is_type_specifier(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Int | TokenKind::Float | TokenKind::Double | TokenKind::Void
+        )
+    }
+}
+
+/// Type of an integer constant, chosen from its value, base and suffix (C17 6.4.4.1)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IntegerType {
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+}
+
+impl IntegerType {
+    /// First type in the list for the literal's suffix that can represent `value`. Decimal
+    /// literals without a `u` suffix only get signed types. `None` if no type is large enough
+    pub fn for_literal(value: u64, is_decimal: bool, unsigned: bool, longs: usize) -> Option<Self> {
+        use IntegerType::*;
+
+        let candidates: &[IntegerType] = match (unsigned, longs, is_decimal) {
+            (false, 0, true) => &[Int, Long, LongLong],
+            (false, 0, false) => &[
+                Int,
+                UnsignedInt,
+                Long,
+                UnsignedLong,
+                LongLong,
+                UnsignedLongLong,
+            ],
+            (true, 0, _) => &[UnsignedInt, UnsignedLong, UnsignedLongLong],
+            (false, 1, true) => &[Long, LongLong],
+            (false, 1, false) => &[Long, UnsignedLong, LongLong, UnsignedLongLong],
+            (true, 1, _) => &[UnsignedLong, UnsignedLongLong],
+            (false, _, true) => &[LongLong],
+            (false, _, false) => &[LongLong, UnsignedLongLong],
+            (true, _, _) => &[UnsignedLongLong],
+        };
+
+        candidates
+            .iter()
+            .copied()
+            .find(|ty| value <= ty.max_value())
+    }
+
+    /// The type as it is spelled in C
+    pub fn name(self) -> &'static str {
+        match self {
+            IntegerType::Int => "int",
+            IntegerType::UnsignedInt => "unsigned int",
+            IntegerType::Long => "long",
+            IntegerType::UnsignedLong => "unsigned long",
+            IntegerType::LongLong => "long long",
+            IntegerType::UnsignedLongLong => "unsigned long long",
+        }
+    }
+
+    /// Largest representable value on x86-64 Linux (LP64)
+    pub fn max_value(self) -> u64 {
+        match self {
+            IntegerType::Int => i32::MAX as u64,
+            IntegerType::UnsignedInt => u32::MAX as u64,
+            IntegerType::Long | IntegerType::LongLong => i64::MAX as u64,
+            IntegerType::UnsignedLong | IntegerType::UnsignedLongLong => u64::MAX,
+        }
+    }
+}
+
+/// Type of a floating constant, given by its suffix (C17 6.4.4.2)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FloatType {
+    Float,
+    Double,
+    LongDouble,
+}
@@ -0,0 +1,172 @@
+//! Runs the compiled `c_compiler` binary like a user would, checking which files it leaves behind
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Output},
+};
+
+const PROGRAM: &str = "int helper(void);\nint main(void) { return helper() + 1; }\n";
+
+/// Assembly the user wrote by hand, which the compiler must never overwrite or delete
+const HELPER_ASM: &str = "\t.globl helper\nhelper:\n\tmovl $41, %eax\n\tret\n";
+
+/// A fresh directory for one test, removed when the test ends
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(test: &str) -> Self {
+        let path = env::temp_dir().join(format!("c_compiler-test-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    fn write(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.path(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Runs the compiler in the directory, whether it succeeds or not
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_c_compiler"))
+            .args(args)
+            .current_dir(&self.0)
+            .output()
+            .unwrap()
+    }
+
+    fn compile(&self, args: &[&str]) -> Output {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "c_compiler {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn exit_code(executable: &Path) -> Option<i32> {
+    Command::new(executable).status().unwrap().code()
+}
+
+#[test]
+fn test_driver_keeps_assembly_input() {
+    let scratch = Scratch::new("assembly-input");
+    scratch.write("prog.c", PROGRAM);
+    let asm = scratch.write("prog.s", HELPER_ASM);
+
+    scratch.compile(&["prog.c", "prog.s", "-o", "prog"]);
+
+    assert_eq!(fs::read_to_string(&asm).unwrap(), HELPER_ASM);
+    assert_eq!(exit_code(&scratch.path("prog")), Some(42));
+}
+
+#[test]
+fn test_driver_object_keeps_existing_assembly() {
+    let scratch = Scratch::new("object");
+    scratch.write("prog.c", PROGRAM);
+    let asm = scratch.write("prog.s", HELPER_ASM);
+
+    scratch.compile(&["-c", "prog.c"]);
+
+    assert_eq!(fs::read_to_string(&asm).unwrap(), HELPER_ASM);
+    assert!(scratch.path("prog.o").exists());
+}
+
+#[test]
+fn test_driver_leaves_no_intermediates() {
+    let scratch = Scratch::new("no-intermediates");
+    scratch.write("prog.c", "int main(void) { return 3; }\n");
+
+    scratch.compile(&["prog.c"]);
+
+    assert_eq!(exit_code(&scratch.path("prog")), Some(3));
+    let mut files: Vec<_> = fs::read_dir(&scratch.0)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["prog", "prog.c"]);
+}
+
+#[test]
+fn test_driver_save_temps() {
+    let scratch = Scratch::new("save-temps");
+    scratch.write("prog.c", "int main(void) { return 3; }\n");
+
+    scratch.compile(&["-save-temps", "prog.c"]);
+    assert!(
+        fs::read_to_string(scratch.path("prog.s"))
+            .unwrap()
+            .contains("main")
+    );
+
+    fs::remove_file(scratch.path("prog.s")).unwrap();
+    scratch.compile(&["-S", "prog.c"]);
+    assert!(scratch.path("prog.s").exists());
+}
+
+#[test]
+fn test_driver_assembles_assembly_with_c() {
+    let scratch = Scratch::new("assemble");
+    scratch.write("helper.s", HELPER_ASM);
+
+    scratch.compile(&["-c", "helper.s"]);
+
+    assert!(scratch.path("helper.o").exists());
+}
+
+#[test]
+fn test_driver_stage_flags_compile_any_input() {
+    let scratch = Scratch::new("stage-flags");
+    scratch.write("prog.i", "int main(void) { return 3; }\n");
+
+    let output = scratch.compile(&["--parse", "prog.i"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("main"));
+
+    scratch.compile(&["-S", "prog.i"]);
+    assert!(scratch.path("prog.s").exists());
+}
+
+#[test]
+fn test_driver_rejects_inputs_it_cannot_compile() {
+    let scratch = Scratch::new("unusable-input");
+    scratch.write("notes.txt", "not code\n");
+
+    let output = scratch.run(&["-c", "notes.txt"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).lines().next(),
+        Some("ERROR: 'notes.txt' is not a C or assembly file, it can't be compiled with '-c'")
+    );
+}
+
+#[test]
+fn test_driver_reports_unwritable_output() {
+    let scratch = Scratch::new("unwritable-output");
+    scratch.write("prog.c", "int main(void) { return 3; }\n");
+
+    let output = scratch.run(&["-E", "prog.c", "-o", "missing/prog.i"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("ERROR: Cannot write 'missing/prog.i': "),
+        "unexpected error: {}",
+        stderr
+    );
+}